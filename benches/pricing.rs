@@ -0,0 +1,57 @@
+//! Host-side pricing benchmarks across supply ranges: the f64 curve and the
+//! fixed-point quotes the program charges.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use friendtech::{dual_phase_pricing, math, quote_buy, quote_sell, Config, Market};
+use solana_program::pubkey::Pubkey;
+
+const SUPPLIES: [u64; 4] = [5, 1_000, 100_000, 10_000_000];
+const TRADE_AMOUNT: u64 = 25;
+
+fn config() -> Config {
+    Config {
+        protocol_fee_bps: 500,
+        subject_fee_bps: 500,
+        veto_threshold_bps: 5_000,
+        ..Config::new(Pubkey::new_unique(), 255)
+    }
+}
+
+fn market(config: &Config, supply: u64) -> Market {
+    Market { supply, ..Market::new(Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), config, 255) }
+}
+
+fn bench_curves(c: &mut Criterion) {
+    let config = config();
+    let mut group = c.benchmark_group("pricing");
+    for supply in SUPPLIES {
+        group.bench_with_input(BenchmarkId::new("f64_curve", supply), &supply, |b, &supply| {
+            b.iter(|| dual_phase_pricing(black_box(supply as u32), 10.0, 7.0, 1.0) * TRADE_AMOUNT as f64)
+        });
+        let market = market(&config, supply);
+        group.bench_with_input(BenchmarkId::new("fixed_point_quote_buy", supply), &market, |b, market| {
+            b.iter(|| quote_buy(&config, black_box(market), TRADE_AMOUNT))
+        });
+        group.bench_with_input(BenchmarkId::new("fixed_point_quote_sell", supply), &market, |b, market| {
+            b.iter(|| quote_sell(&config, black_box(market), TRADE_AMOUNT))
+        });
+    }
+    group.finish();
+}
+
+fn bench_math(c: &mut Criterion) {
+    let mut group = c.benchmark_group("math");
+    for supply in SUPPLIES {
+        let x = supply as u128 * math::ONE;
+        group.bench_with_input(BenchmarkId::new("sqrt", supply), &x, |b, &x| b.iter(|| math::sqrt(black_box(x))));
+        group.bench_with_input(BenchmarkId::new("ln", supply), &x, |b, &x| b.iter(|| math::ln(black_box(x))));
+    }
+    for x in [-20, -1, 1, 20, 40] {
+        let x = x as i128 * math::ONE as i128;
+        group.bench_with_input(BenchmarkId::new("exp", x), &x, |b, &x| b.iter(|| math::exp(black_box(x))));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_curves, bench_math);
+criterion_main!(benches);
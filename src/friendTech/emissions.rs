@@ -0,0 +1,49 @@
+//! Liquidity-mining emissions to share holders.
+//!
+//! Each market streams its emission rate of reward tokens per second to its
+//! holders in proportion to balance times time. The market keeps a
+//! cumulative index of rewards per share, scaled by `REWARD_INDEX_SCALE`,
+//! and each position remembers the index it last accrued at, so a holder's
+//! rewards are their balance times the index growth since.
+
+/// Fixed-point scale of reward indices.
+pub const REWARD_INDEX_SCALE: u128 = 1_000_000_000_000;
+
+/// Index after `elapsed` seconds of emitting `rate` tokens per second over
+/// `supply` shares, or `None` on overflow. Nothing accrues while no shares
+/// exist.
+pub fn advance_index(index: u128, rate: u64, elapsed: i64, supply: u64) -> Option<u128> {
+    if supply == 0 || rate == 0 || elapsed <= 0 {
+        return Some(index);
+    }
+    let emitted = (rate as u128).checked_mul(elapsed as u128)?.checked_mul(REWARD_INDEX_SCALE)?;
+    index.checked_add(emitted / supply as u128)
+}
+
+/// Rewards earned by `balance` shares while the index grew from `from` to
+/// `to`, rounded down, or `None` if they do not fit in a u64.
+pub fn earned(balance: u64, from: u128, to: u128) -> Option<u64> {
+    let earned = (balance as u128).checked_mul(to.checked_sub(from)?)? / REWARD_INDEX_SCALE;
+    u64::try_from(earned).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emissions() {
+        // 100 tokens a second over 1_000 shares for 10 seconds.
+        let index = advance_index(0, 100, 10, 1_000).unwrap();
+        assert_eq!(earned(250, 0, index), Some(250));
+        assert_eq!(earned(1_000, 0, index), Some(1_000));
+        // Holders who joined later only earn the growth since.
+        let later = advance_index(index, 100, 10, 1_000).unwrap();
+        assert_eq!(earned(500, index, later), Some(500));
+
+        assert_eq!(advance_index(index, 100, 10, 0), Some(index));
+        assert_eq!(advance_index(index, 100, -5, 1_000), Some(index));
+        assert_eq!(advance_index(0, u64::MAX, i64::MAX, 1), None);
+        assert_eq!(earned(1, later, index), None);
+    }
+}
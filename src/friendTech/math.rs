@@ -0,0 +1,164 @@
+//! Deterministic integer math for on-chain curves.
+//!
+//! Fixed-point values are scaled by `ONE` (18 decimals). Every function is
+//! pure integer arithmetic, so results are identical on every validator, and
+//! returns `None` instead of overflowing.
+
+/// Fixed-point 1.0.
+pub const ONE: u128 = 1_000_000_000_000_000_000;
+/// Fixed-point ln(2), rounded down.
+pub const LN_2: i128 = 693_147_180_559_945_309;
+/// Largest accepted `exp` input, 47.0; e^47.28 no longer fits in a u128.
+pub const MAX_EXP_INPUT: i128 = 47_000_000_000_000_000_000;
+
+const ONE_SIGNED: i128 = ONE as i128;
+
+/// Floor of the square root of `n`, exact for every input.
+pub fn isqrt(n: u128) -> u128 {
+    if n < 2 {
+        return n;
+    }
+    // Newton's method from an initial guess at or above the root decreases
+    // monotonically to the floor.
+    let mut x = 1u128 << ((128 - n.leading_zeros()).div_ceil(2));
+    loop {
+        let y = (x + n / x) / 2;
+        if y >= x {
+            return x;
+        }
+        x = y;
+    }
+}
+
+/// Square root of a fixed-point value, rounded down to the nearest
+/// fixed-point unit, i.e. exact to within 1e-18.
+pub fn sqrt(x: u128) -> Option<u128> {
+    Some(isqrt(x.checked_mul(ONE)?))
+}
+
+/// e^x for a signed fixed-point `x`, or `None` above `MAX_EXP_INPUT`.
+///
+/// `x` is reduced to `k * ln 2 + r` with `0 <= r < ln 2` and e^r is summed as
+/// a Taylor series until its terms vanish. Each term is truncated once, so
+/// e^r is at most 30 units below the true value: a relative error below
+/// 1e-16 that scaling by 2^k preserves. Scaling down for negative inputs
+/// truncates up to one more unit, so results below one unit return 0.
+pub fn exp(x: i128) -> Option<u128> {
+    if x > MAX_EXP_INPUT {
+        return None;
+    }
+    let k = x.div_euclid(LN_2);
+    let r = x.rem_euclid(LN_2) as u128;
+
+    let mut sum = ONE;
+    let mut term = ONE;
+    let mut n = 1u128;
+    while term > 0 {
+        term = term * r / ONE / n;
+        sum += term;
+        n += 1;
+    }
+
+    if k >= 0 {
+        let shifted = sum.checked_shl(k as u32)?;
+        // `checked_shl` only rejects oversized shift amounts.
+        (shifted >> k == sum).then_some(shifted)
+    } else if k > -128 {
+        Some(sum >> -k)
+    } else {
+        Some(0)
+    }
+}
+
+/// Natural logarithm of a positive fixed-point `x`, or `None` for 0.
+///
+/// `x` is normalized to `m * 2^k` with `1 <= m < 2` and ln m is summed as
+/// `2 * atanh((m - 1) / (m + 1))`, whose argument is at most 1/3. The result
+/// is within 1e-16 of the true value, plus the error of `k * LN_2`, which is
+/// below 1e-16 for every u128 input.
+pub fn ln(x: u128) -> Option<i128> {
+    if x == 0 {
+        return None;
+    }
+    let k = (127 - x.leading_zeros()) as i128 - (127 - ONE.leading_zeros()) as i128;
+    let mut m = if k >= 0 { x >> k } else { x << -k };
+    // `ONE` is not a power of two, so normalization can be off by one octave.
+    let mut k = k;
+    if m < ONE {
+        m <<= 1;
+        k -= 1;
+    } else if m >= 2 * ONE {
+        m >>= 1;
+        k += 1;
+    }
+
+    let z = ((m - ONE) * ONE / (m + ONE)) as i128;
+    let z_squared = z * z / ONE_SIGNED;
+    let mut sum = 0i128;
+    let mut power = z;
+    let mut n = 1i128;
+    while power > 0 {
+        sum += power / n;
+        power = power * z_squared / ONE_SIGNED;
+        n += 2;
+    }
+    Some(2 * sum + k * LN_2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_f64(x: u128) -> f64 {
+        x as f64 / ONE as f64
+    }
+
+    fn from_f64(x: f64) -> i128 {
+        (x * ONE as f64) as i128
+    }
+
+    #[test]
+    fn test_isqrt() {
+        for n in [0u128, 1, 2, 3, 4, 15, 16, 17, 1 << 64, u64::MAX as u128, u128::MAX] {
+            let root = isqrt(n);
+            assert!(root * root <= n);
+            assert!(root.checked_add(1).and_then(|r| r.checked_mul(r)).is_none_or(|square| square > n));
+        }
+        assert_eq!(isqrt(u128::MAX), u64::MAX as u128);
+        assert_eq!(sqrt(2 * ONE), Some(1_414_213_562_373_095_048));
+        assert_eq!(sqrt(u128::MAX), None);
+    }
+
+    #[test]
+    fn test_exp() {
+        assert_eq!(exp(0), Some(ONE));
+        assert_eq!(exp(LN_2), Some(2 * ONE));
+        for x in [-12.0, -5.5, -1.0, -0.25, 0.5, 1.0, 3.3, 20.0, 47.0] {
+            let actual = to_f64(exp(from_f64(x)).unwrap());
+            let expected = f64::exp(x);
+            assert!((actual - expected).abs() / expected < 1e-12, "exp({x}) = {actual}, expected {expected}");
+        }
+        // Tiny results are off by at most the final unit of truncation.
+        let tiny = exp(-40 * ONE_SIGNED).unwrap() as f64;
+        assert!((tiny - f64::exp(-40.0) * ONE as f64).abs() <= 2.0);
+        assert!(exp(MAX_EXP_INPUT).is_some());
+        assert_eq!(exp(MAX_EXP_INPUT + 1), None);
+        assert_eq!(exp(-100 * ONE_SIGNED), Some(0));
+    }
+
+    #[test]
+    fn test_ln() {
+        assert_eq!(ln(0), None);
+        assert_eq!(ln(ONE), Some(0));
+        assert!((ln(2 * ONE).unwrap() - LN_2).abs() <= 1);
+        for x in [1e-12, 0.3, 0.999, 1.5, 2.0, 10.0, 12_345.678, 1e20] {
+            let actual = ln(from_f64(x) as u128).unwrap() as f64 / ONE as f64;
+            let expected = f64::ln(x);
+            assert!((actual - expected).abs() < 1e-9, "ln({x}) = {actual}, expected {expected}");
+        }
+        // ln inverts exp to within the combined error bounds.
+        for x in [-3 * ONE_SIGNED, ONE_SIGNED / 7, 25 * ONE_SIGNED] {
+            assert!((ln(exp(x).unwrap()).unwrap() - x).abs() < 1_000);
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! Keccak Merkle trees for allowlists and airdrops.
+//!
+//! Leaves and inner nodes hash under different prefix bytes, so a node can
+//! never be passed off as a leaf. Inner nodes hash their children in sorted
+//! order, so proofs are plain sibling lists without left/right flags. A node
+//! without a sibling moves up a level unchanged.
+
+use solana_program::keccak::hashv;
+
+const LEAF_PREFIX: &[u8] = &[0];
+const NODE_PREFIX: &[u8] = &[1];
+
+/// Hash of a leaf made of the concatenated `parts`.
+pub fn leaf(parts: &[&[u8]]) -> [u8; 32] {
+    let mut values = Vec::with_capacity(parts.len() + 1);
+    values.push(LEAF_PREFIX);
+    values.extend_from_slice(parts);
+    hashv(&values).to_bytes()
+}
+
+/// Whether `proof` links `leaf` to `root`.
+pub fn verify(root: &[u8; 32], leaf: [u8; 32], proof: &[[u8; 32]]) -> bool {
+    proof.iter().fold(leaf, |node, sibling| parent(&node, sibling)) == *root
+}
+
+/// Root of the tree over `leaves`, or all zeroes for no leaves.
+pub fn root(leaves: &[[u8; 32]]) -> [u8; 32] {
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = level.chunks(2).map(|pair| pair.get(1).map_or(pair[0], |right| parent(&pair[0], right))).collect();
+    }
+    level.first().copied().unwrap_or_default()
+}
+
+/// Proof for the leaf at `index` of the tree over `leaves`, or `None` when
+/// out of range.
+pub fn proof(leaves: &[[u8; 32]], mut index: usize) -> Option<Vec<[u8; 32]>> {
+    if index >= leaves.len() {
+        return None;
+    }
+    let mut proof = Vec::new();
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        if let Some(sibling) = level.get(index ^ 1) {
+            proof.push(*sibling);
+        }
+        level = level.chunks(2).map(|pair| pair.get(1).map_or(pair[0], |right| parent(&pair[0], right))).collect();
+        index /= 2;
+    }
+    Some(proof)
+}
+
+fn parent(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (low, high) = if a <= b { (a, b) } else { (b, a) };
+    hashv(&[NODE_PREFIX, low, high]).to_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proofs() {
+        let leaves: Vec<[u8; 32]> = (0u8..5).map(|i| leaf(&[&[i]])).collect();
+        let root = root(&leaves);
+        for (i, leaf) in leaves.iter().enumerate() {
+            let proof = proof(&leaves, i).unwrap();
+            assert!(verify(&root, *leaf, &proof));
+            assert!(!verify(&root, super::leaf(&[&[9]]), &proof));
+        }
+        assert_eq!(proof(&leaves, 5), None);
+
+        // Leaf data spelling out an inner node's children does not hash to it.
+        let (low, high) = if leaves[0] <= leaves[1] { (leaves[0], leaves[1]) } else { (leaves[1], leaves[0]) };
+        assert_ne!(leaf(&[&low, &high]), parent(&leaves[0], &leaves[1]));
+        assert_eq!(super::root(&leaves[..1]), leaves[0]);
+    }
+}
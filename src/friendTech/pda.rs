@@ -0,0 +1,198 @@
+//! Canonical seeds of every FriendTech PDA.
+//!
+//! Each account lives at its seed constant followed by the keys it is
+//! unique for, in the order given by its `find_*_address` function. The
+//! program derives its own accounts from these functions, so clients and
+//! CPI callers using them always agree with it. Every function returns the
+//! address and its canonical bump.
+
+use solana_program::pubkey::Pubkey;
+
+pub const CONFIG_SEED: &[u8] = b"config";
+pub const PROFILE_SEED: &[u8] = b"profile";
+pub const MARKET_SEED: &[u8] = b"market";
+pub const POSITION_SEED: &[u8] = b"position";
+pub const VAULT_SEED: &[u8] = b"vault";
+pub const STATS_SEED: &[u8] = b"stats";
+pub const PROPOSAL_SEED: &[u8] = b"proposal";
+pub const VETO_SEED: &[u8] = b"veto";
+pub const HOLDING_SEED: &[u8] = b"holding";
+pub const DCA_SEED: &[u8] = b"dca";
+pub const DCA_ESCROW_SEED: &[u8] = b"dca_escrow";
+pub const INSURANCE_SEED: &[u8] = b"insurance";
+pub const TREASURY_SEED: &[u8] = b"treasury";
+pub const TREASURY_STAKE_SEED: &[u8] = b"treasury_stake";
+pub const AIRDROP_SEED: &[u8] = b"airdrop";
+pub const AIRDROP_ESCROW_SEED: &[u8] = b"airdrop_escrow";
+pub const PRESALE_SEED: &[u8] = b"presale";
+pub const REWARDS_MINT_SEED: &[u8] = b"rewards_mint";
+pub const RECEIPT_SEED: &[u8] = b"receipt";
+pub const ACCESS_SEED: &[u8] = b"access";
+pub const FAUCET_MINT_SEED: &[u8] = b"faucet_mint";
+pub const CANDLES_SEED: &[u8] = b"candles";
+pub const COLLATERAL_SEED: &[u8] = b"collateral";
+
+/// `[CONFIG_SEED]`: the global config.
+pub fn find_config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED], program_id)
+}
+
+/// `[STATS_SEED]`: the protocol-wide counters.
+pub fn find_stats_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[STATS_SEED], program_id)
+}
+
+/// `[PROFILE_SEED, subject]`: `subject`'s profile.
+pub fn find_profile_address(program_id: &Pubkey, subject: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PROFILE_SEED, subject.as_ref()], program_id)
+}
+
+/// `[MARKET_SEED, subject, quote_mint]`: `subject`'s market for `quote_mint`.
+pub fn find_market_address(program_id: &Pubkey, subject: &Pubkey, quote_mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[MARKET_SEED, subject.as_ref(), quote_mint.as_ref()], program_id)
+}
+
+/// `[VAULT_SEED, market]`: `market`'s quote token vault.
+pub fn find_vault_address(program_id: &Pubkey, market: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VAULT_SEED, market.as_ref()], program_id)
+}
+
+/// `[POSITION_SEED, market, owner]`: `owner`'s shares in `market`.
+pub fn find_position_address(program_id: &Pubkey, market: &Pubkey, owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[POSITION_SEED, market.as_ref(), owner.as_ref()], program_id)
+}
+
+/// `[HOLDING_SEED, holder, subject]`: the social graph edge from `holder`
+/// to `subject`.
+pub fn find_holding_address(program_id: &Pubkey, holder: &Pubkey, subject: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[HOLDING_SEED, holder.as_ref(), subject.as_ref()], program_id)
+}
+
+/// `[PROPOSAL_SEED, market]`: `market`'s current fee proposal.
+pub fn find_proposal_address(program_id: &Pubkey, market: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PROPOSAL_SEED, market.as_ref()], program_id)
+}
+
+/// `[VETO_SEED, proposal, voter]`: `voter`'s veto record for `proposal`.
+pub fn find_veto_address(program_id: &Pubkey, proposal: &Pubkey, voter: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VETO_SEED, proposal.as_ref(), voter.as_ref()], program_id)
+}
+
+/// `[DCA_SEED, market, owner]`: `owner`'s DCA schedule for `market`.
+pub fn find_dca_schedule_address(program_id: &Pubkey, market: &Pubkey, owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[DCA_SEED, market.as_ref(), owner.as_ref()], program_id)
+}
+
+/// `[DCA_ESCROW_SEED, schedule]`: the token account escrowing `schedule`'s
+/// quote tokens.
+pub fn find_dca_escrow_address(program_id: &Pubkey, schedule: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[DCA_ESCROW_SEED, schedule.as_ref()], program_id)
+}
+
+/// `[INSURANCE_SEED, quote_mint]`: the insurance vault for `quote_mint`.
+pub fn find_insurance_vault_address(program_id: &Pubkey, quote_mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[INSURANCE_SEED, quote_mint.as_ref()], program_id)
+}
+
+/// `[TREASURY_SEED]`: the protocol treasury.
+pub fn find_treasury_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[TREASURY_SEED], program_id)
+}
+
+/// `[TREASURY_STAKE_SEED, stake_index]`: the treasury's stake account number
+/// `stake_index`, as little-endian bytes.
+pub fn find_treasury_stake_address(program_id: &Pubkey, stake_index: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[TREASURY_STAKE_SEED, &stake_index.to_le_bytes()], program_id)
+}
+
+/// `[AIRDROP_SEED, market, airdrop_id]`: airdrop `airdrop_id` of `market`,
+/// the id as little-endian bytes.
+pub fn find_airdrop_address(program_id: &Pubkey, market: &Pubkey, airdrop_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[AIRDROP_SEED, market.as_ref(), &airdrop_id.to_le_bytes()], program_id)
+}
+
+/// `[AIRDROP_ESCROW_SEED, airdrop]`: the token account escrowing a quote
+/// token airdrop.
+pub fn find_airdrop_escrow_address(program_id: &Pubkey, airdrop: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[AIRDROP_ESCROW_SEED, airdrop.as_ref()], program_id)
+}
+
+/// `[PRESALE_SEED, market]`: `market`'s presale.
+pub fn find_presale_address(program_id: &Pubkey, market: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PRESALE_SEED, market.as_ref()], program_id)
+}
+
+/// `[REWARDS_MINT_SEED]`: the liquidity-mining rewards mint.
+pub fn find_rewards_mint_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[REWARDS_MINT_SEED], program_id)
+}
+
+/// `[RECEIPT_SEED, trader, receipt_id]`: `trader`'s receipt of the trade
+/// that asked for `receipt_id`.
+pub fn find_receipt_address(program_id: &Pubkey, trader: &Pubkey, receipt_id: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[RECEIPT_SEED, trader.as_ref(), receipt_id], program_id)
+}
+
+/// `[ACCESS_SEED, market, sender]`: `sender`'s paid messages to `market`'s
+/// subject.
+pub fn find_access_address(program_id: &Pubkey, market: &Pubkey, sender: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ACCESS_SEED, market.as_ref(), sender.as_ref()], program_id)
+}
+
+/// `[FAUCET_MINT_SEED]`: the devnet faucet's quote mint.
+pub fn find_faucet_mint_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[FAUCET_MINT_SEED], program_id)
+}
+
+/// `[CANDLES_SEED, market]`: the market's price candles.
+pub fn find_candles_address(program_id: &Pubkey, market: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CANDLES_SEED, market.as_ref()], program_id)
+}
+
+/// `[COLLATERAL_SEED]` under `lending_program`, not this program: the signer
+/// a collateral program locks and unlocks positions with.
+pub fn find_collateral_authority_address(lending_program: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[COLLATERAL_SEED], lending_program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeds_are_distinct() {
+        let seeds = [
+            CONFIG_SEED,
+            PROFILE_SEED,
+            MARKET_SEED,
+            POSITION_SEED,
+            VAULT_SEED,
+            STATS_SEED,
+            PROPOSAL_SEED,
+            VETO_SEED,
+            HOLDING_SEED,
+            DCA_SEED,
+            DCA_ESCROW_SEED,
+            INSURANCE_SEED,
+            TREASURY_SEED,
+            TREASURY_STAKE_SEED,
+            AIRDROP_SEED,
+            AIRDROP_ESCROW_SEED,
+            PRESALE_SEED,
+            REWARDS_MINT_SEED,
+            RECEIPT_SEED,
+            ACCESS_SEED,
+            FAUCET_MINT_SEED,
+            CANDLES_SEED,
+            COLLATERAL_SEED,
+        ];
+        for (i, a) in seeds.iter().enumerate() {
+            assert!(seeds[i + 1..].iter().all(|b| a != b));
+        }
+
+        // Accounts unique for the same keys still get distinct addresses.
+        let (program_id, key) = (Pubkey::new_unique(), Pubkey::new_unique());
+        assert_ne!(find_vault_address(&program_id, &key), find_proposal_address(&program_id, &key));
+        assert_ne!(find_dca_escrow_address(&program_id, &key), find_airdrop_escrow_address(&program_id, &key));
+    }
+}
@@ -0,0 +1,362 @@
+//! Instruction processing. `process_instruction` decodes a
+//! `FriendtechInstruction` and routes it to the handler of its subsystem;
+//! account checks shared by several handlers live in `accounts`.
+
+use crate::{ErrorReport, FriendtechInstruction, TradeEvent, TradeSettlement, EVENT_SCHEMA_VERSION, TRADE_LOG_TAG};
+use borsh::BorshSerialize;
+use solana_program::{account_info::AccountInfo, log::sol_log_data, program_error::ProgramError, pubkey::Pubkey};
+
+mod accounts;
+mod admin;
+mod airdrop;
+mod buy;
+mod candles;
+mod collateral;
+mod create_market;
+mod dca;
+#[cfg(feature = "devnet")]
+mod faucet;
+mod fees;
+mod governance;
+mod message;
+mod presale;
+mod profile;
+mod receipt;
+mod rewards;
+mod sell;
+#[cfg(feature = "devnet")]
+mod snapshot;
+mod subject;
+mod treasury;
+mod view;
+mod wind_down;
+
+use admin::{
+    process_accept_admin, process_initialize_config, process_set_features, process_set_fee_switch,
+    process_sweep_lamports, process_transfer_admin,
+};
+use airdrop::{process_claim_airdrop, process_set_airdrop_root};
+use buy::{process_buy_basket, process_buy_shares, BuySize};
+use candles::process_create_candles;
+use collateral::{process_lock_position, process_set_collateral_programs, process_unlock_position};
+use create_market::process_create_market;
+use dca::{process_cancel_dca_schedule, process_create_dca_schedule, process_execute_dca};
+#[cfg(feature = "devnet")]
+use faucet::{process_create_faucet_mint, process_fast_forward_market, process_faucet_mint};
+use fees::{
+    process_claim_protocol_fees, process_claim_streamed_fees, process_claim_subject_fees,
+    process_compensate_from_insurance, process_create_insurance_vault, process_set_fee_split, process_set_fee_stream,
+    process_set_insurance_fee_bps,
+};
+use governance::{process_execute_proposal, process_propose_parameters, process_veto_proposal};
+use message::process_pay_message_fee;
+use presale::{process_buy_presale, process_start_presale};
+use profile::{process_attest_identity, process_check_mutual_holding};
+use receipt::process_close_trade_receipt;
+use rewards::{process_claim_rewards, process_create_rewards_mint, process_set_emission_rate};
+use sell::{process_sell_shares, SellSize};
+#[cfg(feature = "devnet")]
+use snapshot::{process_export_market_snapshot, process_import_market_snapshot};
+use subject::{
+    process_accept_subject_role, process_nominate_successor, process_set_market_paused, process_set_message_fee,
+    process_set_min_hold_duration, process_set_router_policy, process_set_sell_tax,
+};
+use treasury::{
+    process_deactivate_treasury_stake, process_delegate_treasury_stake, process_withdraw_treasury,
+    process_withdraw_treasury_stake,
+};
+use view::{
+    process_collect_market_report, process_prove_holding, process_view_fees, process_view_holder_balance,
+    process_view_supply,
+};
+use wind_down::{process_redeem_shares, process_start_wind_down};
+
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> Result<(), ProgramError> {
+    execute_instruction(program_id, accounts, instruction_data).map_err(|report| {
+        report.log();
+        report.error
+    })
+}
+
+fn execute_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> Result<(), ErrorReport> {
+    let instruction = FriendtechInstruction::unpack(instruction_data)?;
+
+    match instruction {
+        FriendtechInstruction::BuyShares { amount, client_id, interface_fee, receipt_id } => {
+            process_buy_shares(program_id, accounts, BuySize::Shares(amount), client_id, interface_fee, receipt_id)?;
+        }
+        FriendtechInstruction::SellShares { amount, client_id, interface_fee, receipt_id } => {
+            process_sell_shares(program_id, accounts, SellSize::Shares(amount), client_id, interface_fee, receipt_id)?;
+        }
+        FriendtechInstruction::InitializeConfig {
+            attestor,
+            protocol_fee_bps,
+            subject_fee_bps,
+            proposal_delay,
+            veto_threshold_bps,
+            max_interface_fee_bps,
+            insurance_fee_bps,
+        } => {
+            process_initialize_config(
+                program_id,
+                accounts,
+                attestor,
+                protocol_fee_bps,
+                subject_fee_bps,
+                proposal_delay,
+                veto_threshold_bps,
+                max_interface_fee_bps,
+                insurance_fee_bps,
+            )?;
+        }
+        FriendtechInstruction::AttestIdentity { handle_hash, attestor_sig } => {
+            process_attest_identity(program_id, accounts, handle_hash, attestor_sig)?;
+        }
+        FriendtechInstruction::CreateMarket => {
+            process_create_market(program_id, accounts, &[])?;
+        }
+        FriendtechInstruction::CreateGroupMarket { members } => {
+            process_create_market(program_id, accounts, &members)?;
+        }
+        FriendtechInstruction::SweepLamports => {
+            process_sweep_lamports(program_id, accounts)?;
+        }
+        FriendtechInstruction::ClaimSubjectFees => {
+            process_claim_subject_fees(program_id, accounts)?;
+        }
+        FriendtechInstruction::ClaimProtocolFees => {
+            process_claim_protocol_fees(program_id, accounts)?;
+        }
+        FriendtechInstruction::BuyBasket { budget, weights_bps, client_id } => {
+            process_buy_basket(program_id, accounts, budget, weights_bps, client_id)?;
+        }
+        FriendtechInstruction::ProposeParameters { subject_fee_bps, sell_tax_bps, sell_tax_period } => {
+            process_propose_parameters(program_id, accounts, subject_fee_bps, sell_tax_bps, sell_tax_period)?;
+        }
+        FriendtechInstruction::VetoProposal => {
+            process_veto_proposal(program_id, accounts)?;
+        }
+        FriendtechInstruction::ExecuteProposal => {
+            process_execute_proposal(program_id, accounts)?;
+        }
+        FriendtechInstruction::SetFeeSwitch { protocol_fees_enabled } => {
+            process_set_fee_switch(program_id, accounts, protocol_fees_enabled)?;
+        }
+        FriendtechInstruction::SetMinHoldDuration { min_hold_duration } => {
+            process_set_min_hold_duration(program_id, accounts, min_hold_duration)?;
+        }
+        FriendtechInstruction::CheckMutualHolding { a, b } => {
+            process_check_mutual_holding(program_id, accounts, a, b)?;
+        }
+        FriendtechInstruction::SetMarketPaused { paused, allow_sells } => {
+            process_set_market_paused(program_id, accounts, paused, allow_sells)?;
+        }
+        FriendtechInstruction::BuyMaxForBudget { budget, min_shares } => {
+            process_buy_shares(program_id, accounts, BuySize::Budget { budget, min_shares }, None, None, None)?;
+        }
+        FriendtechInstruction::SellAll { close_position } => {
+            process_sell_shares(program_id, accounts, SellSize::All { close_position }, None, None, None)?;
+        }
+        FriendtechInstruction::CreateDcaSchedule { market, amount_per_interval, interval, total } => {
+            process_create_dca_schedule(program_id, accounts, market, amount_per_interval, interval, total)?;
+        }
+        FriendtechInstruction::ExecuteDca => {
+            process_execute_dca(program_id, accounts)?;
+        }
+        FriendtechInstruction::CancelDcaSchedule => {
+            process_cancel_dca_schedule(program_id, accounts)?;
+        }
+        FriendtechInstruction::CreateInsuranceVault => {
+            process_create_insurance_vault(program_id, accounts)?;
+        }
+        FriendtechInstruction::SetInsuranceFeeBps { insurance_fee_bps } => {
+            process_set_insurance_fee_bps(program_id, accounts, insurance_fee_bps)?;
+        }
+        FriendtechInstruction::CompensateFromInsurance { amount } => {
+            process_compensate_from_insurance(program_id, accounts, amount)?;
+        }
+        FriendtechInstruction::SetSellTax { sell_tax_bps, sell_tax_period } => {
+            process_set_sell_tax(program_id, accounts, sell_tax_bps, sell_tax_period)?;
+        }
+        FriendtechInstruction::DelegateTreasuryStake { stake_index, lamports } => {
+            process_delegate_treasury_stake(program_id, accounts, stake_index, lamports)?;
+        }
+        FriendtechInstruction::DeactivateTreasuryStake { stake_index } => {
+            process_deactivate_treasury_stake(program_id, accounts, stake_index)?;
+        }
+        FriendtechInstruction::WithdrawTreasuryStake { stake_index, lamports } => {
+            process_withdraw_treasury_stake(program_id, accounts, stake_index, lamports)?;
+        }
+        FriendtechInstruction::WithdrawTreasury { lamports } => {
+            process_withdraw_treasury(program_id, accounts, lamports)?;
+        }
+        FriendtechInstruction::SetAirdropRoot { airdrop_id, root, kind, total } => {
+            process_set_airdrop_root(program_id, accounts, airdrop_id, root, kind, total)?;
+        }
+        FriendtechInstruction::ClaimAirdrop { index, amount, proof } => {
+            process_claim_airdrop(program_id, accounts, index, amount, proof)?;
+        }
+        FriendtechInstruction::StartPresale { root, price, allocation, opens_at } => {
+            process_start_presale(program_id, accounts, root, price, allocation, opens_at)?;
+        }
+        FriendtechInstruction::BuyPresale { amount, cap, proof } => {
+            process_buy_presale(program_id, accounts, amount, cap, proof)?;
+        }
+        FriendtechInstruction::TransferAdmin { new_admin } => {
+            process_transfer_admin(program_id, accounts, new_admin)?;
+        }
+        FriendtechInstruction::AcceptAdmin => {
+            process_accept_admin(program_id, accounts)?;
+        }
+        FriendtechInstruction::SetFeatures { features } => {
+            process_set_features(program_id, accounts, features)?;
+        }
+        FriendtechInstruction::CreateRewardsMint { decimals } => {
+            process_create_rewards_mint(program_id, accounts, decimals)?;
+        }
+        FriendtechInstruction::SetEmissionRate { emission_rate } => {
+            process_set_emission_rate(program_id, accounts, emission_rate)?;
+        }
+        FriendtechInstruction::ClaimRewards => {
+            process_claim_rewards(program_id, accounts)?;
+        }
+        FriendtechInstruction::SetRouterPolicy { policy, routers } => {
+            process_set_router_policy(program_id, accounts, policy, routers)?;
+        }
+        FriendtechInstruction::SetFeeSplit { recipients } => {
+            process_set_fee_split(program_id, accounts, recipients)?;
+        }
+        FriendtechInstruction::NominateSuccessor { successor, delay } => {
+            process_nominate_successor(program_id, accounts, successor, delay)?;
+        }
+        FriendtechInstruction::AcceptSubjectRole => {
+            process_accept_subject_role(program_id, accounts)?;
+        }
+        FriendtechInstruction::CloseTradeReceipt => {
+            process_close_trade_receipt(program_id, accounts)?;
+        }
+        FriendtechInstruction::ViewSupply => {
+            process_view_supply(program_id, accounts)?;
+        }
+        FriendtechInstruction::ViewHolderBalance { owner } => {
+            process_view_holder_balance(program_id, accounts, owner)?;
+        }
+        FriendtechInstruction::ViewFees => {
+            process_view_fees(program_id, accounts)?;
+        }
+        FriendtechInstruction::SetCollateralPrograms { programs } => {
+            process_set_collateral_programs(program_id, accounts, programs)?;
+        }
+        FriendtechInstruction::LockPositionAsCollateral { bump } => {
+            process_lock_position(program_id, accounts, bump)?;
+        }
+        FriendtechInstruction::UnlockPosition { bump } => {
+            process_unlock_position(program_id, accounts, bump)?;
+        }
+        FriendtechInstruction::SetMessageFee { message_fee } => {
+            process_set_message_fee(program_id, accounts, message_fee)?;
+        }
+        FriendtechInstruction::PayMessageFee { count } => {
+            process_pay_message_fee(program_id, accounts, count)?;
+        }
+        FriendtechInstruction::SetFeeStream { beneficiary, rate_per_second } => {
+            process_set_fee_stream(program_id, accounts, beneficiary, rate_per_second)?;
+        }
+        FriendtechInstruction::ClaimStreamedFees => {
+            process_claim_streamed_fees(program_id, accounts)?;
+        }
+        FriendtechInstruction::BuyIfPriceBetween { min, max, amount } => {
+            process_buy_shares(program_id, accounts, BuySize::PriceBand { amount, min, max }, None, None, None)?;
+        }
+        FriendtechInstruction::CollectMarketReport => {
+            process_collect_market_report(program_id, accounts)?;
+        }
+        FriendtechInstruction::ProveHolding { min_balance, nonce } => {
+            process_prove_holding(program_id, accounts, min_balance, nonce)?;
+        }
+        FriendtechInstruction::CreateCandles => {
+            process_create_candles(program_id, accounts)?;
+        }
+        FriendtechInstruction::StartWindDown { deadline } => {
+            process_start_wind_down(program_id, accounts, deadline)?;
+        }
+        FriendtechInstruction::RedeemShares => {
+            process_redeem_shares(program_id, accounts)?;
+        }
+        #[cfg(feature = "devnet")]
+        FriendtechInstruction::ExportMarketSnapshot => {
+            process_export_market_snapshot(program_id, accounts)?;
+        }
+        #[cfg(feature = "devnet")]
+        FriendtechInstruction::ImportMarketSnapshot { snapshot } => {
+            process_import_market_snapshot(program_id, accounts, *snapshot)?;
+        }
+        #[cfg(feature = "devnet")]
+        FriendtechInstruction::CreateFaucetMint { decimals } => {
+            process_create_faucet_mint(program_id, accounts, decimals)?;
+        }
+        #[cfg(feature = "devnet")]
+        FriendtechInstruction::FaucetMint { amount } => {
+            process_faucet_mint(program_id, accounts, amount)?;
+        }
+        #[cfg(feature = "devnet")]
+        FriendtechInstruction::FastForwardMarket { seconds, epochs } => {
+            process_fast_forward_market(program_id, accounts, seconds, epochs)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn log_trade(
+    market: &Pubkey,
+    trader: &Pubkey,
+    is_buy: bool,
+    amount: u64,
+    settlement: &TradeSettlement,
+    client_id: Option<[u8; 8]>,
+    interface_fee_recipient: Option<&Pubkey>,
+) -> Result<(), ProgramError> {
+    let event = TradeEvent {
+        market: *market,
+        trader: *trader,
+        is_buy,
+        amount,
+        settlement: settlement.clone(),
+        client_id,
+        interface_fee_recipient: interface_fee_recipient.copied(),
+    };
+    log_event(TRADE_LOG_TAG, &event)
+}
+
+/// Logs `event` under `tag` at the current `EVENT_SCHEMA_VERSION`.
+fn log_event(tag: &[u8], event: &impl BorshSerialize) -> Result<(), ProgramError> {
+    sol_log_data(&[tag, &[EVENT_SCHEMA_VERSION], &event.try_to_vec()?]);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_instruction_routes_decoded_instructions() {
+        let program_id = Pubkey::new_unique();
+        assert_eq!(process_instruction(&program_id, &[], &[u8::MAX]), Err(ProgramError::InvalidInstructionData));
+        // A well-formed buy reaches its handler, which finds no accounts.
+        let buy =
+            FriendtechInstruction::BuyShares { amount: 1, client_id: None, interface_fee: None, receipt_id: None };
+        assert_eq!(
+            process_instruction(&program_id, &[], &buy.try_to_vec().unwrap()),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+    }
+}
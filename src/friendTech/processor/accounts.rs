@@ -0,0 +1,744 @@
+//! Account validation and creation shared by the instruction handlers.
+//!
+//! Loaders check an account's owner, address and discriminator before
+//! unpacking it; `check_*` helpers fail with the error the handler reports.
+
+use crate::{
+    find_holding_address, find_position_address, router_allowed, AccountType, Config, ErrorReport, FriendtechError,
+    Holding, InterfaceFee, LegacyShareAccount, Market, Position, Profile, RouterPolicy, Stats, BPS_DENOMINATOR,
+    CONFIG_SEED, HOLDING_SEED, LEGACY_SHARE_ACCOUNT_LEN, MARKET_SEED, MAX_FEE_BPS, MAX_SELL_TAX_BPS,
+    MAX_SELL_TAX_PERIOD, NO_ACCOUNT, POSITION_SEED, STATS_SEED, VAULT_SEED,
+};
+use borsh::BorshDeserialize;
+use solana_program::{
+    account_info::AccountInfo,
+    instruction::{get_stack_height, TRANSACTION_LEVEL_STACK_HEIGHT},
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction, system_program,
+    sysvar::{instructions as sysvar_instructions, Sysvar},
+};
+use spl_token::{instruction as spl_token_instruction, state::Account as SplTokenAccount};
+
+/// Transfers `amount` quote tokens out of the vault, signed by the market PDA.
+pub(super) fn transfer_from_vault<'a>(
+    market: &Market,
+    market_account: &AccountInfo<'a>,
+    vault: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    token_program: &AccountInfo<'a>,
+    amount: u64,
+) -> Result<(), ErrorReport> {
+    let balance = token_balance(vault)?;
+    let expected = balance
+        .checked_sub(amount)
+        .ok_or_else(|| ErrorReport::at(FriendtechError::InsufficientFunds, NO_ACCOUNT).values(amount, balance))?;
+    let ix = spl_token_instruction::transfer(
+        token_program.key,
+        vault.key,
+        destination.key,
+        market_account.key,
+        &[],
+        amount,
+    )?;
+    invoke_signed(
+        &ix,
+        &[vault.clone(), destination.clone(), market_account.clone(), token_program.clone()],
+        &[&[MARKET_SEED, market.subject.as_ref(), market.quote_mint.as_ref(), &[market.bump]]],
+    )?;
+    check_vault_balance(vault, expected)
+}
+
+pub(super) fn token_balance(token_account: &AccountInfo) -> Result<u64, ProgramError> {
+    Ok(SplTokenAccount::unpack(&token_account.data.borrow())?.amount)
+}
+
+/// Checks that `vault` holds exactly `expected` quote tokens after a
+/// transfer, so a token program, or an account passed twice, that moves a
+/// different amount cannot desynchronize the market's accounting from the
+/// vault.
+pub(super) fn check_vault_balance(vault: &AccountInfo, expected: u64) -> Result<(), ErrorReport> {
+    let actual = token_balance(vault)?;
+    if actual != expected {
+        return Err(ErrorReport::at(FriendtechError::VaultBalanceMismatch, NO_ACCOUNT).values(expected, actual));
+    }
+    Ok(())
+}
+
+/// Returns the key acting as subject of a program-owned account, if any.
+pub(super) fn account_subject(account: &AccountInfo) -> Result<Option<Pubkey>, ProgramError> {
+    let data = account.data.borrow();
+    let subject = match AccountType::deserialize(&mut &data[..])? {
+        AccountType::Market => Some(Market::unpack(&data)?.authority),
+        AccountType::Profile => Some(Profile::unpack(&data)?.subject),
+        _ => None,
+    };
+    Ok(subject)
+}
+
+/// Closes a program-owned account, moving all its lamports to `recipient`
+/// and zeroing its data so it cannot be loaded again in this transaction.
+pub(super) fn close_account(account: &AccountInfo, recipient: &AccountInfo) -> Result<(), ProgramError> {
+    if account.key == recipient.key {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let recipient_lamports =
+        recipient.lamports().checked_add(account.lamports()).ok_or(ProgramError::ArithmeticOverflow)?;
+    **account.try_borrow_mut_lamports()? = 0;
+    **recipient.try_borrow_mut_lamports()? = recipient_lamports;
+    account.try_borrow_mut_data()?.fill(0);
+    Ok(())
+}
+
+/// Rent payers fund new accounts, so they must sign and be writable. They
+/// carry no authority over the accounts they pay for.
+pub(super) fn check_payer(payer: &AccountInfo) -> Result<(), ProgramError> {
+    if !payer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if !payer.is_writable {
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(())
+}
+
+/// Loads a market after checking it is owned by this program.
+pub(super) fn load_market(program_id: &Pubkey, market_account: &AccountInfo) -> Result<Market, ProgramError> {
+    if market_account.owner != program_id {
+        return Err(FriendtechError::IncorrectOwner.into());
+    }
+    Market::unpack(&market_account.data.borrow())
+}
+
+pub(super) fn check_signer(account: &AccountInfo) -> Result<(), ProgramError> {
+    if !account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    Ok(())
+}
+
+pub(super) fn check_token_program(token_program: &AccountInfo) -> Result<(), ProgramError> {
+    if *token_program.key != spl_token::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
+
+/// Checks the market's `RouterPolicy` lets the executing trade through. Only
+/// `Allowlist` needs the caller identified, by `calling_program`;
+/// `DirectOnly` turns away any trade by CPI.
+pub(super) fn check_router(market: &Market, accounts: &[AccountInfo], market_index: u8) -> Result<(), ErrorReport> {
+    let rejected = match market.router_policy {
+        RouterPolicy::Open => return Ok(()),
+        RouterPolicy::DirectOnly => get_stack_height() > TRANSACTION_LEVEL_STACK_HEIGHT,
+        RouterPolicy::Allowlist => calling_program(accounts)?.is_some_and(|router| !router_allowed(market, &router)),
+    };
+    if rejected {
+        return Err(ErrorReport::at(FriendtechError::RouterNotAllowed, market_index));
+    }
+    Ok(())
+}
+
+/// The program calling the executing instruction by CPI, or `None` when it
+/// runs at transaction level. A call is attributed to the program of the
+/// transaction instruction it runs under, read from the instructions sysvar
+/// anywhere in `accounts`.
+pub(super) fn calling_program(accounts: &[AccountInfo]) -> Result<Option<Pubkey>, ErrorReport> {
+    if get_stack_height() <= TRANSACTION_LEVEL_STACK_HEIGHT {
+        return Ok(None);
+    }
+    let instructions_sysvar = accounts
+        .iter()
+        .find(|account| sysvar_instructions::check_id(account.key))
+        .ok_or_else(|| ErrorReport::at(ProgramError::NotEnoughAccountKeys, NO_ACCOUNT))?;
+    let current_index = sysvar_instructions::load_current_index_checked(instructions_sysvar)?;
+    let caller =
+        sysvar_instructions::load_instruction_at_checked(current_index as usize, instructions_sysvar)?.program_id;
+    Ok(Some(caller))
+}
+
+/// Checks that the position is neither locked as collateral nor, at `now`,
+/// backing a veto.
+pub(super) fn check_unlocked(position: &Position, now: i64) -> Result<(), ProgramError> {
+    if position.locked_by().is_some() || now < position.veto_locked_until() {
+        return Err(FriendtechError::PositionLocked.into());
+    }
+    Ok(())
+}
+
+/// Checks that `vault` is the market's vault.
+pub(super) fn check_vault(market: &Market, vault: &AccountInfo) -> Result<(), ProgramError> {
+    if *vault.key != market.vault {
+        return Err(FriendtechError::AccountMismatch.into());
+    }
+    Ok(())
+}
+
+/// Checks that the position at `position_index` has been held for the
+/// market's minimum hold duration at unix timestamp `now`.
+pub(super) fn check_hold_period(
+    market: &Market,
+    position: &Position,
+    now: i64,
+    position_index: u8,
+) -> Result<(), ErrorReport> {
+    let unlocks_at = position.acquired_at().saturating_add(market.min_hold_duration);
+    if now < unlocks_at {
+        return Err(
+            ErrorReport::at(FriendtechError::HoldPeriodActive, position_index).values(unlocks_at as u64, now as u64)
+        );
+    }
+    Ok(())
+}
+
+/// Loads `owner`'s position in `market` after checking ownership and address.
+/// A `LegacyShareAccount` found there is migrated on this first touch; see
+/// `migrate_legacy_position` for what `rent_payer` covers.
+pub(super) fn load_position<'a>(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    market: &Pubkey,
+    position_account: &AccountInfo<'a>,
+    rent_payer: Option<(&AccountInfo<'a>, &AccountInfo<'a>)>,
+) -> Result<Position, ProgramError> {
+    if position_account.owner != program_id {
+        return Err(FriendtechError::IncorrectOwner.into());
+    }
+    if position_account.data_len() == LEGACY_SHARE_ACCOUNT_LEN {
+        return migrate_legacy_position(program_id, owner, market, position_account, rent_payer);
+    }
+    let position = Position::unpack(&position_account.data.borrow())?;
+    if position.owner != *owner {
+        return Err(FriendtechError::AccountMismatch.into());
+    }
+    check_position_address(program_id, market, &position, position_account)?;
+    Ok(position)
+}
+
+/// Reads the `LegacyShareAccount` at `owner`'s position PDA in `market` as
+/// the position holding its balance. A writable account is rewritten in
+/// place as that position, growing to `Position::LEN`; if its lamports no
+/// longer cover rent, `rent_payer`, a payer and the system program, tops
+/// them up, and without one the load fails with `LegacyAccount`. A
+/// read-only account is only decoded.
+fn migrate_legacy_position<'a>(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    market: &Pubkey,
+    position_account: &AccountInfo<'a>,
+    rent_payer: Option<(&AccountInfo<'a>, &AccountInfo<'a>)>,
+) -> Result<Position, ProgramError> {
+    let legacy = LegacyShareAccount::try_from_slice(&position_account.data.borrow())?;
+    let (position_key, bump) = find_position_address(program_id, market, owner);
+    if legacy.owner != *owner || position_key != *position_account.key {
+        return Err(FriendtechError::AccountMismatch.into());
+    }
+    let position = Position { balance: legacy.balance, ..Position::new(*owner, bump) };
+    if !position_account.is_writable {
+        return Ok(position);
+    }
+
+    let shortfall = Rent::get()?.minimum_balance(Position::LEN).saturating_sub(position_account.lamports());
+    if shortfall > 0 {
+        let Some((payer, system_program_account)) = rent_payer else {
+            return Err(FriendtechError::LegacyAccount.into());
+        };
+        if *system_program_account.key != system_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let ix = system_instruction::transfer(payer.key, position_account.key, shortfall);
+        invoke(&ix, &[payer.clone(), position_account.clone(), system_program_account.clone()])?;
+    }
+    position_account.realloc(Position::LEN, true)?;
+    Position::pack(position.clone(), &mut position_account.data.borrow_mut())?;
+    Ok(position)
+}
+
+/// Checks that the position is stored at its PDA in `market`, which it does
+/// not record itself.
+pub(super) fn check_position_address(
+    program_id: &Pubkey,
+    market: &Pubkey,
+    position: &Position,
+    position_account: &AccountInfo,
+) -> Result<(), ProgramError> {
+    let seeds = [POSITION_SEED, market.as_ref(), position.owner.as_ref(), &[position.bump]];
+    match Pubkey::create_program_address(&seeds, program_id) {
+        Ok(key) if key == *position_account.key => Ok(()),
+        _ => Err(FriendtechError::AccountMismatch.into()),
+    }
+}
+
+/// Loads `owner`'s position in `market`, creating it at `payer`'s expense if
+/// it does not exist yet.
+pub(super) fn load_or_create_position<'a>(
+    program_id: &Pubkey,
+    payer: &AccountInfo<'a>,
+    owner: &Pubkey,
+    market: &Pubkey,
+    position_account: &AccountInfo<'a>,
+    system_program_account: &AccountInfo<'a>,
+) -> Result<Position, ProgramError> {
+    if position_account.owner == program_id {
+        return load_position(program_id, owner, market, position_account, Some((payer, system_program_account)));
+    }
+    let (position_key, bump) = find_position_address(program_id, market, owner);
+    if position_key != *position_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    create_pda_account(
+        payer,
+        position_account,
+        system_program_account,
+        program_id,
+        Position::LEN,
+        &[POSITION_SEED, market.as_ref(), owner.as_ref(), &[bump]],
+    )?;
+    Ok(Position::new(*owner, bump))
+}
+
+/// Loads `holder`'s holding of `subject` after checking ownership and
+/// address, or `None` if the holding PDA has not been created yet.
+pub(super) fn load_holding(
+    program_id: &Pubkey,
+    holder: &Pubkey,
+    subject: &Pubkey,
+    holding_account: &AccountInfo,
+) -> Result<Option<Holding>, ProgramError> {
+    if holding_account.owner != program_id {
+        let (holding_key, _) = find_holding_address(program_id, holder, subject);
+        if holding_key != *holding_account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        return Ok(None);
+    }
+    let holding = Holding::unpack(&holding_account.data.borrow())?;
+    if holding.holder != *holder || holding.subject != *subject {
+        return Err(FriendtechError::AccountMismatch.into());
+    }
+    Ok(Some(holding))
+}
+
+/// Loads `holder`'s holding of `subject`, creating it at `payer`'s expense if
+/// it does not exist yet.
+pub(super) fn load_or_create_holding<'a>(
+    program_id: &Pubkey,
+    payer: &AccountInfo<'a>,
+    holder: &Pubkey,
+    subject: &Pubkey,
+    holding_account: &AccountInfo<'a>,
+    system_program_account: &AccountInfo<'a>,
+) -> Result<Holding, ProgramError> {
+    if let Some(holding) = load_holding(program_id, holder, subject, holding_account)? {
+        return Ok(holding);
+    }
+    let (_, bump) = find_holding_address(program_id, holder, subject);
+    create_pda_account(
+        payer,
+        holding_account,
+        system_program_account,
+        program_id,
+        Holding::LEN,
+        &[HOLDING_SEED, holder.as_ref(), subject.as_ref(), &[bump]],
+    )?;
+    Ok(Holding { account_type: AccountType::Holding, holder: *holder, subject: *subject, markets: 0, bump })
+}
+
+/// Loads the global config after checking its owner and address.
+pub(super) fn load_config(program_id: &Pubkey, config_account: &AccountInfo) -> Result<Config, ProgramError> {
+    if config_account.owner != program_id {
+        return Err(FriendtechError::IncorrectOwner.into());
+    }
+    let config = Config::unpack(&config_account.data.borrow())?;
+    let config_key = Pubkey::create_program_address(&[CONFIG_SEED, &[config.bump]], program_id)?;
+    if config_key != *config_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(config)
+}
+
+/// Checks an interface fee against the config's cap and that `recipient`,
+/// at `recipient_index`, is the account it names.
+pub(super) fn check_interface_fee<'a, 'b>(
+    config: &Config,
+    fee: &InterfaceFee,
+    recipient: &'a AccountInfo<'b>,
+    recipient_index: u8,
+) -> Result<(u16, &'a AccountInfo<'b>), ErrorReport> {
+    if fee.fee_bps > config.max_interface_fee_bps {
+        return Err(ErrorReport::at(FriendtechError::InvalidFee, NO_ACCOUNT)
+            .values(config.max_interface_fee_bps.into(), fee.fee_bps.into()));
+    }
+    if *recipient.key != fee.recipient {
+        return Err(ErrorReport::at(FriendtechError::AccountMismatch, recipient_index));
+    }
+    Ok((fee.fee_bps, recipient))
+}
+
+/// Checks that no two of `accounts` are the same account, so a trade
+/// cannot pay a fee into its own source or the vault it settles against.
+/// Fails at the index of the later of the first aliased pair found.
+pub(super) fn check_distinct(accounts: &[(&AccountInfo, u8)]) -> Result<(), ErrorReport> {
+    for (i, (account, index)) in accounts.iter().enumerate() {
+        if accounts[..i].iter().any(|(other, _)| other.key == account.key) {
+            return Err(ErrorReport::at(FriendtechError::DuplicateAccount, *index));
+        }
+    }
+    Ok(())
+}
+
+pub(super) fn check_fee_bps(fee_bps: u16) -> Result<(), ErrorReport> {
+    if fee_bps > MAX_FEE_BPS {
+        return Err(ErrorReport::at(FriendtechError::InvalidFee, NO_ACCOUNT).values(MAX_FEE_BPS.into(), fee_bps.into()));
+    }
+    Ok(())
+}
+
+/// Checks a sell tax rate and period against their caps.
+pub(super) fn check_sell_tax(sell_tax_bps: u16, sell_tax_period: i64) -> Result<(), ErrorReport> {
+    if sell_tax_bps > MAX_SELL_TAX_BPS {
+        return Err(ErrorReport::at(FriendtechError::InvalidFee, NO_ACCOUNT)
+            .values(MAX_SELL_TAX_BPS.into(), sell_tax_bps.into()));
+    }
+    if !(0..=MAX_SELL_TAX_PERIOD).contains(&sell_tax_period) {
+        return Err(ErrorReport::at(ProgramError::InvalidArgument, NO_ACCOUNT)
+            .values(MAX_SELL_TAX_PERIOD as u64, sell_tax_period as u64));
+    }
+    Ok(())
+}
+
+pub(super) fn check_feature(config: &Config, feature: u64) -> Result<(), ErrorReport> {
+    if config.features & feature == 0 {
+        return Err(ErrorReport::at(FriendtechError::FeatureDisabled, NO_ACCOUNT).values(feature, config.features));
+    }
+    Ok(())
+}
+
+/// Checks that the program is not winding down, for instructions that
+/// take quote tokens in or add shares; see `StartWindDown`.
+pub(super) fn check_not_winding_down(config: &Config) -> Result<(), ErrorReport> {
+    if config.wind_down_deadline.is_some() {
+        return Err(ErrorReport::at(FriendtechError::WindingDown, NO_ACCOUNT));
+    }
+    Ok(())
+}
+
+pub(super) fn check_insurance_fee_bps(insurance_fee_bps: u16) -> Result<(), ErrorReport> {
+    if u64::from(insurance_fee_bps) > BPS_DENOMINATOR {
+        return Err(
+            ErrorReport::at(FriendtechError::InvalidFee, NO_ACCOUNT).values(BPS_DENOMINATOR, insurance_fee_bps.into())
+        );
+    }
+    Ok(())
+}
+
+/// Loads the protocol stats after checking their owner and address.
+pub(super) fn load_stats(program_id: &Pubkey, stats_account: &AccountInfo) -> Result<Stats, ProgramError> {
+    if stats_account.owner != program_id {
+        return Err(FriendtechError::IncorrectOwner.into());
+    }
+    let stats = Stats::unpack(&stats_account.data.borrow())?;
+    let stats_key = Pubkey::create_program_address(&[STATS_SEED, &[stats.bump]], program_id)?;
+    if stats_key != *stats_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(stats)
+}
+
+/// Creates a rent-exempt account at a PDA, owned by `owner`. Tolerates the
+/// address having been pre-funded, which would make `create_account` fail.
+pub(super) fn create_pda_account<'a>(
+    payer: &AccountInfo<'a>,
+    new_account: &AccountInfo<'a>,
+    system_program_account: &AccountInfo<'a>,
+    owner: &Pubkey,
+    space: usize,
+    seeds: &[&[u8]],
+) -> Result<(), ProgramError> {
+    if *system_program_account.key != system_program::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let required = Rent::get()?.minimum_balance(space);
+    let current = new_account.lamports();
+    if current == 0 {
+        let ix = system_instruction::create_account(payer.key, new_account.key, required, space as u64, owner);
+        return invoke_signed(&ix, &[payer.clone(), new_account.clone(), system_program_account.clone()], &[seeds]);
+    }
+
+    if current < required {
+        let ix = system_instruction::transfer(payer.key, new_account.key, required - current);
+        invoke(&ix, &[payer.clone(), new_account.clone(), system_program_account.clone()])?;
+    }
+    let accounts = [new_account.clone(), system_program_account.clone()];
+    invoke_signed(&system_instruction::allocate(new_account.key, space as u64), &accounts, &[seeds])?;
+    invoke_signed(&system_instruction::assign(new_account.key, owner), &accounts, &[seeds])
+}
+
+/// Creates `market`'s quote token vault at its `[VAULT_SEED, market]` PDA,
+/// with the market as token authority.
+pub(super) fn create_vault<'a>(
+    payer: &AccountInfo<'a>,
+    vault: &AccountInfo<'a>,
+    quote_mint: &AccountInfo<'a>,
+    token_program: &AccountInfo<'a>,
+    system_program_account: &AccountInfo<'a>,
+    market: &Pubkey,
+    vault_bump: u8,
+) -> Result<(), ProgramError> {
+    let seeds: &[&[u8]] = &[VAULT_SEED, market.as_ref(), &[vault_bump]];
+    create_token_pda(payer, vault, quote_mint, token_program, system_program_account, market, seeds)
+}
+
+/// Creates a quote token account at the PDA for `seeds`, with `authority`
+/// as its token owner.
+pub(super) fn create_token_pda<'a>(
+    payer: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    quote_mint: &AccountInfo<'a>,
+    token_program: &AccountInfo<'a>,
+    system_program_account: &AccountInfo<'a>,
+    authority: &Pubkey,
+    seeds: &[&[u8]],
+) -> Result<(), ProgramError> {
+    create_pda_account(payer, account, system_program_account, &spl_token::id(), SplTokenAccount::LEN, seeds)?;
+    let ix = spl_token_instruction::initialize_account3(token_program.key, account.key, quote_mint.key, authority)?;
+    invoke(&ix, &[account.clone(), quote_mint.clone(), token_program.clone()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{install_test_runtime, sample_config, sample_market, set_test_stack_height};
+    use crate::{MutualHolding, FEATURE_DCA, FEATURE_LENDING, FEATURE_PRESALES};
+    use borsh::BorshSerialize;
+
+    #[test]
+    fn test_check_router() {
+        install_test_runtime();
+        let mut market = sample_market(Pubkey::new_unique());
+        for policy in [RouterPolicy::Open, RouterPolicy::DirectOnly, RouterPolicy::Allowlist] {
+            market.router_policy = policy;
+            assert!(check_router(&market, &[], 1).is_ok());
+        }
+
+        // Under CPI, turning every caller away needs no instructions sysvar,
+        // while checking the caller against the allowlist does.
+        set_test_stack_height(TRANSACTION_LEVEL_STACK_HEIGHT + 1);
+        market.router_policy = RouterPolicy::DirectOnly;
+        let report = check_router(&market, &[], 1).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::RouterNotAllowed.into(), 1));
+        market.router_policy = RouterPolicy::Allowlist;
+        assert_eq!(check_router(&market, &[], 1).unwrap_err().error, ProgramError::NotEnoughAccountKeys);
+        set_test_stack_height(TRANSACTION_LEVEL_STACK_HEIGHT);
+    }
+
+    #[test]
+    fn test_check_payer() {
+        let key = Pubkey::new_unique();
+        let owner = system_program::id();
+        let (mut lamports, mut data) = (1_000_000, vec![]);
+        let mut payer = AccountInfo::new(&key, true, true, &mut lamports, &mut data, &owner, false, 0);
+        assert!(check_payer(&payer).is_ok());
+
+        payer.is_writable = false;
+        assert_eq!(check_payer(&payer), Err(ProgramError::InvalidArgument));
+        payer.is_signer = false;
+        assert_eq!(check_payer(&payer), Err(ProgramError::MissingRequiredSignature));
+    }
+
+    #[test]
+    fn test_load_holding() {
+        let (program_id, a, b) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let (key, bump) = find_holding_address(&program_id, &a, &b);
+        let owner = system_program::id();
+        let (mut lamports, mut data) = (0, vec![]);
+        let account = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &owner, false, 0);
+        assert_eq!(load_holding(&program_id, &a, &b, &account), Ok(None));
+        assert_eq!(load_holding(&program_id, &b, &a, &account), Err(ProgramError::InvalidSeeds));
+
+        let holding = Holding { account_type: AccountType::Holding, holder: a, subject: b, markets: 2, bump };
+        let mut data = vec![0u8; Holding::LEN];
+        Holding::pack(holding.clone(), &mut data).unwrap();
+        let mut lamports = 0;
+        let account = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &program_id, false, 0);
+        assert_eq!(load_holding(&program_id, &a, &b, &account), Ok(Some(holding)));
+        assert_eq!(load_holding(&program_id, &b, &a, &account), Err(FriendtechError::AccountMismatch.into()));
+
+        assert!(MutualHolding { a_holds_b: true, b_holds_a: true }.is_mutual());
+        assert!(!MutualHolding { a_holds_b: true, b_holds_a: false }.is_mutual());
+    }
+
+    #[test]
+    fn test_load_position_layouts() {
+        let (program_id, owner, market) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let (key, bump) = find_position_address(&program_id, &market, &owner);
+        let position = Position { balance: 7, ..Position::new(owner, bump) };
+        let elsewhere = Pubkey::new_unique();
+
+        // A legacy share account reads as the position it migrates into, and
+        // a writable one short of rent cannot migrate without a payer.
+        install_test_runtime();
+        let mut legacy = LegacyShareAccount { owner, balance: 7 }.try_to_vec().unwrap();
+        let mut lamports = 0;
+        let mut account = AccountInfo::new(&key, false, false, &mut lamports, &mut legacy, &program_id, false, 0);
+        assert_eq!(load_position(&program_id, &owner, &market, &account, None), Ok(position.clone()));
+        assert_eq!(
+            load_position(&program_id, &owner, &elsewhere, &account, None),
+            Err(FriendtechError::AccountMismatch.into())
+        );
+        account.is_writable = true;
+        assert_eq!(
+            load_position(&program_id, &owner, &market, &account, None),
+            Err(FriendtechError::LegacyAccount.into())
+        );
+        assert_eq!(account.data_len(), LEGACY_SHARE_ACCOUNT_LEN);
+
+        let mut data = vec![0u8; Position::LEN];
+        Position::pack(position.clone(), &mut data).unwrap();
+        let mut lamports = 0;
+        let account = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &program_id, false, 0);
+        assert_eq!(load_position(&program_id, &owner, &market, &account, None), Ok(position));
+        assert_eq!(
+            load_position(&program_id, &owner, &elsewhere, &account, None),
+            Err(FriendtechError::AccountMismatch.into())
+        );
+    }
+
+    #[test]
+    fn test_check_hold_period() {
+        let mut market = sample_market(Pubkey::new_unique());
+        let owner = Pubkey::new_unique();
+        let mut position = Position { balance: 5, ..Position::new(owner, 255) };
+        position.set_acquired_at(1_000);
+        assert!(check_hold_period(&market, &position, 1_000, 2).is_ok());
+
+        market.min_hold_duration = 600;
+        let report = check_hold_period(&market, &position, 1_599, 2).unwrap_err();
+        assert_eq!(report.error, FriendtechError::HoldPeriodActive.into());
+        assert_eq!((report.log.account_index, report.log.expected, report.log.actual), (2, 1_600, 1_599));
+        assert!(check_hold_period(&market, &position, 1_600, 2).is_ok());
+    }
+
+    #[test]
+    fn test_feature_flags() {
+        let mut config = sample_config(Pubkey::new_unique());
+        assert!(check_feature(&config, FEATURE_PRESALES).is_ok());
+        assert!(check_feature(&config, FEATURE_LENDING).is_err());
+        config.features &= !FEATURE_PRESALES;
+        assert!(check_feature(&config, FEATURE_PRESALES).is_err());
+        assert!(check_feature(&config, FEATURE_DCA).is_ok());
+    }
+
+    #[test]
+    fn test_check_vault_balance() {
+        let (key, owner) = (Pubkey::new_unique(), spl_token::id());
+        let vault = SplTokenAccount {
+            mint: Pubkey::new_unique(),
+            owner: key,
+            amount: 500,
+            state: spl_token::state::AccountState::Initialized,
+            ..SplTokenAccount::default()
+        };
+        let mut data = vec![0u8; SplTokenAccount::LEN];
+        SplTokenAccount::pack(vault, &mut data).unwrap();
+        let mut lamports = 0;
+        let account = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &owner, false, 0);
+        assert_eq!(token_balance(&account), Ok(500));
+        assert!(check_vault_balance(&account, 500).is_ok());
+
+        // A transfer that moved less than accounted for, e.g. to the vault itself.
+        let report = check_vault_balance(&account, 600).unwrap_err();
+        assert_eq!(report.error, FriendtechError::VaultBalanceMismatch.into());
+        assert_eq!((report.log.expected, report.log.actual), (600, 500));
+    }
+
+    #[test]
+    fn test_close_account() {
+        let (key, recipient_key, program_id) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let (mut lamports, mut data) = (1_500, vec![7u8; Position::LEN]);
+        let account = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &program_id, false, 0);
+        let (mut recipient_lamports, mut recipient_data) = (500, vec![]);
+        let owner = system_program::id();
+        let recipient = AccountInfo::new(
+            &recipient_key,
+            false,
+            true,
+            &mut recipient_lamports,
+            &mut recipient_data,
+            &owner,
+            false,
+            0,
+        );
+        assert_eq!(close_account(&account, &account), Err(ProgramError::InvalidArgument));
+
+        close_account(&account, &recipient).unwrap();
+        assert_eq!((account.lamports(), recipient.lamports()), (0, 2_000));
+        assert!(account.data.borrow().iter().all(|&byte| byte == 0));
+        assert_eq!(Position::unpack(&account.data.borrow()), Err(FriendtechError::UninitializedAccount.into()));
+    }
+
+    #[test]
+    fn test_account_subject() {
+        let (key, program_id, subject) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let market = sample_market(subject);
+        let mut data = vec![0u8; Market::LEN];
+        Market::pack(market, &mut data).unwrap();
+        let mut lamports = 0;
+        let account = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &program_id, false, 0);
+        assert_eq!(account_subject(&account).unwrap(), Some(subject));
+
+        let mut config_data = vec![0u8; Config::LEN];
+        let config = sample_config(subject);
+        Config::pack(config, &mut config_data).unwrap();
+        let mut lamports = 0;
+        let account = AccountInfo::new(&key, false, true, &mut lamports, &mut config_data, &program_id, false, 0);
+        assert_eq!(account_subject(&account).unwrap(), None);
+    }
+
+    #[test]
+    fn test_check_insurance_fee_bps() {
+        assert!(check_insurance_fee_bps(BPS_DENOMINATOR as u16).is_ok());
+        assert!(check_insurance_fee_bps(BPS_DENOMINATOR as u16 + 1).is_err());
+    }
+
+    #[test]
+    fn test_check_interface_fee() {
+        let config = sample_config(Pubkey::new_unique());
+        let (key, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (mut lamports, mut data) = (0, vec![]);
+        let recipient = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &owner, false, 0);
+        let fee = InterfaceFee { fee_bps: 100, recipient: key };
+        assert_eq!(check_interface_fee(&config, &fee, &recipient, 11).unwrap().0, 100);
+        let over_cap = InterfaceFee { fee_bps: 101, recipient: key };
+        let report = check_interface_fee(&config, &over_cap, &recipient, 11).unwrap_err();
+        assert_eq!(
+            (report.error, report.log.expected, report.log.actual),
+            (FriendtechError::InvalidFee.into(), 100, 101)
+        );
+        let elsewhere = InterfaceFee { fee_bps: 100, recipient: Pubkey::new_unique() };
+        let report = check_interface_fee(&config, &elsewhere, &recipient, 11).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::AccountMismatch.into(), 11));
+    }
+
+    #[test]
+    fn test_check_distinct() {
+        let (vault_key, token_key, owner) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let (mut lamports, mut data) = ([0; 3], [vec![], vec![], vec![]]);
+        let [l0, l1, l2] = &mut lamports;
+        let [d0, d1, d2] = &mut data;
+        let token_account = AccountInfo::new(&token_key, false, true, l0, d0, &owner, false, 0);
+        let vault = AccountInfo::new(&vault_key, false, true, l1, d1, &owner, false, 0);
+        let alias = AccountInfo::new(&token_key, false, true, l2, d2, &owner, false, 0);
+        assert!(check_distinct(&[(&token_account, 4), (&vault, 5)]).is_ok());
+        assert!(check_distinct(&[]).is_ok());
+
+        // The trader naming their own token account as the fee recipient.
+        let report = check_distinct(&[(&token_account, 4), (&vault, 5), (&alias, 11)]).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::DuplicateAccount.into(), 11));
+        let report = check_distinct(&[(&token_account, 4), (&vault, 5), (&vault, 11)]).unwrap_err();
+        assert_eq!(report.log.account_index, 11);
+    }
+}
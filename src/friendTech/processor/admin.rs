@@ -0,0 +1,331 @@
+//! Config creation and the admin's settings: fee switch, features, admin
+//! handover and lamport sweeps.
+
+use super::accounts::{
+    account_subject, check_fee_bps, check_insurance_fee_bps, check_signer, create_pda_account, load_config, load_market,
+};
+use super::log_event;
+use crate::{
+    find_config_address, find_stats_address, AccountType, AtAccount, Config, ErrorReport, FeeSwitchEvent,
+    FriendtechError, Market, Stats, BPS_DENOMINATOR, CONFIG_SEED, FEE_SWITCH_LOG_TAG, NO_ACCOUNT, STATS_SEED,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    clock::Clock,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    sysvar::Sysvar,
+};
+
+#[allow(clippy::too_many_arguments)]
+pub(super) fn process_initialize_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    attestor: Pubkey,
+    protocol_fee_bps: u16,
+    subject_fee_bps: u16,
+    proposal_delay: i64,
+    veto_threshold_bps: u16,
+    max_interface_fee_bps: u16,
+    insurance_fee_bps: u16,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let admin = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+    let stats_account = next_account_info(accounts_iter)?;
+    let program_data = next_account_info(accounts_iter)?;
+    let upgrade_authority = next_account_info(accounts_iter)?;
+
+    check_signer(admin).at_account(0)?;
+    for fee_bps in [protocol_fee_bps, subject_fee_bps, max_interface_fee_bps] {
+        check_fee_bps(fee_bps)?;
+    }
+    if proposal_delay < 0 {
+        return Err(ErrorReport::at(ProgramError::InvalidArgument, NO_ACCOUNT));
+    }
+    if veto_threshold_bps == 0 || u64::from(veto_threshold_bps) > BPS_DENOMINATOR {
+        return Err(ErrorReport::at(ProgramError::InvalidArgument, NO_ACCOUNT)
+            .values(BPS_DENOMINATOR, veto_threshold_bps.into()));
+    }
+    check_insurance_fee_bps(insurance_fee_bps)?;
+    let (config_key, bump) = find_config_address(program_id);
+    if config_key != *config_account.key {
+        return Err(ErrorReport::at(ProgramError::InvalidSeeds, 1));
+    }
+    if config_account.owner == program_id {
+        return Err(ErrorReport::at(ProgramError::AccountAlreadyInitialized, 1));
+    }
+    let (stats_key, stats_bump) = find_stats_address(program_id);
+    if stats_key != *stats_account.key {
+        return Err(ErrorReport::at(ProgramError::InvalidSeeds, 3));
+    }
+    // Only whoever deployed the program may claim its config, so a
+    // front-running signer cannot become admin.
+    check_signer(upgrade_authority).at_account(5)?;
+    if load_upgrade_authority(program_id, program_data)? != Some(*upgrade_authority.key) {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 5));
+    }
+
+    create_pda_account(
+        admin,
+        config_account,
+        system_program_account,
+        program_id,
+        Config::LEN,
+        &[CONFIG_SEED, &[bump]],
+    )?;
+    let config = Config {
+        attestor,
+        protocol_fee_bps,
+        subject_fee_bps,
+        proposal_delay,
+        veto_threshold_bps,
+        max_interface_fee_bps,
+        insurance_fee_bps,
+        ..Config::new(*admin.key, bump)
+    };
+    Config::pack(config, &mut config_account.data.borrow_mut()).at_account(1)?;
+
+    create_pda_account(
+        admin,
+        stats_account,
+        system_program_account,
+        program_id,
+        Stats::LEN,
+        &[STATS_SEED, &[stats_bump]],
+    )?;
+    let stats = Stats {
+        account_type: AccountType::Stats,
+        total_markets: 0,
+        total_volume: 0,
+        total_fees: 0,
+        epoch: Clock::get()?.epoch,
+        epoch_active_markets: 0,
+        last_epoch_active_markets: 0,
+        bump: stats_bump,
+    };
+    Stats::pack(stats, &mut stats_account.data.borrow_mut()).at_account(3)
+}
+
+/// Reads the upgrade authority of `program_id` from its `ProgramData`
+/// account, the upgradeable loader's `[program_id]` PDA; `None` once the
+/// program is immutable.
+fn load_upgrade_authority(program_id: &Pubkey, program_data: &AccountInfo) -> Result<Option<Pubkey>, ErrorReport> {
+    if *program_data.owner != bpf_loader_upgradeable::id() {
+        return Err(ErrorReport::at(FriendtechError::IncorrectOwner, 4));
+    }
+    let (program_data_key, _) = Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+    if program_data_key != *program_data.key {
+        return Err(ErrorReport::at(ProgramError::InvalidSeeds, 4));
+    }
+    // Bincode layout: a u32 variant, the u64 slot of the last deployment,
+    // then the optional authority.
+    let data = program_data.data.borrow();
+    match data.get(..UpgradeableLoaderState::size_of_programdata_metadata()) {
+        Some([3, 0, 0, 0, _, _, _, _, _, _, _, _, 0, ..]) => Ok(None),
+        Some([3, 0, 0, 0, _, _, _, _, _, _, _, _, 1, authority @ ..]) => {
+            Ok(Some(Pubkey::try_from(authority).map_err(|_| ProgramError::InvalidAccountData)?))
+        }
+        _ => Err(ErrorReport::at(ProgramError::InvalidAccountData, 4)),
+    }
+}
+
+pub(super) fn process_sweep_lamports(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let authority = next_account_info(accounts_iter)?;
+    let target = next_account_info(accounts_iter)?;
+    let recipient = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+
+    check_signer(authority).at_account(0)?;
+    if target.owner != program_id {
+        return Err(ErrorReport::at(FriendtechError::IncorrectOwner, 1));
+    }
+    if target.key == recipient.key {
+        return Err(ErrorReport::at(ProgramError::InvalidArgument, 2));
+    }
+    let config = load_config(program_id, config_account).at_account(3)?;
+    if *authority.key != config.admin && account_subject(target).at_account(1)? != Some(*authority.key) {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+
+    let minimum = Rent::get()?.minimum_balance(target.data_len());
+    let surplus = target.lamports().saturating_sub(minimum);
+    if surplus == 0 {
+        return Ok(());
+    }
+    let recipient_lamports = recipient.lamports().checked_add(surplus).ok_or(ProgramError::ArithmeticOverflow)?;
+    **target.try_borrow_mut_lamports()? -= surplus;
+    **recipient.try_borrow_mut_lamports()? = recipient_lamports;
+    Ok(())
+}
+
+pub(super) fn process_set_fee_switch(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    protocol_fees_enabled: bool,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let admin = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let market_account = accounts_iter.next();
+
+    check_signer(admin).at_account(0)?;
+    let mut config = load_config(program_id, config_account).at_account(1)?;
+    if config.admin != *admin.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+
+    let event = if let Some(market_account) = market_account {
+        let mut market = load_market(program_id, market_account).at_account(2)?;
+        market.protocol_fees_enabled = protocol_fees_enabled;
+        Market::pack(market, &mut market_account.data.borrow_mut()).at_account(2)?;
+        FeeSwitchEvent { market: Some(*market_account.key), protocol_fees_enabled }
+    } else {
+        config.protocol_fees_enabled = protocol_fees_enabled;
+        Config::pack(config, &mut config_account.data.borrow_mut()).at_account(1)?;
+        FeeSwitchEvent { market: None, protocol_fees_enabled }
+    };
+    log_event(FEE_SWITCH_LOG_TAG, &event)?;
+    Ok(())
+}
+
+pub(super) fn process_transfer_admin(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    new_admin: Option<Pubkey>,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let admin = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+
+    check_signer(admin).at_account(0)?;
+    let mut config = load_config(program_id, config_account).at_account(1)?;
+    if config.admin != *admin.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    config.pending_admin = new_admin;
+    Config::pack(config, &mut config_account.data.borrow_mut()).at_account(1)
+}
+
+pub(super) fn process_set_features(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    features: u64,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let admin = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+
+    check_signer(admin).at_account(0)?;
+    let mut config = load_config(program_id, config_account).at_account(1)?;
+    if config.admin != *admin.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    config.features = features;
+    Config::pack(config, &mut config_account.data.borrow_mut()).at_account(1)
+}
+
+pub(super) fn process_accept_admin(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let new_admin = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+
+    check_signer(new_admin).at_account(0)?;
+    let mut config = load_config(program_id, config_account).at_account(1)?;
+    if config.pending_admin != Some(*new_admin.key) {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    config.admin = *new_admin.key;
+    config.pending_admin = None;
+    Config::pack(config, &mut config_account.data.borrow_mut()).at_account(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{install_test_runtime, test_accounts};
+    use crate::MAX_FEE_BPS;
+    use solana_program::system_program;
+
+    #[test]
+    fn test_initialize_config_checks_parameters() {
+        let program_id = Pubkey::new_unique();
+        let keys: Vec<Pubkey> = (0..6).map(|_| Pubkey::new_unique()).collect();
+        let owner = Pubkey::new_unique();
+        let mut lamports = [0u64; 6];
+        let mut data = vec![vec![]; 6];
+        let mut accounts = test_accounts(&keys, &mut lamports, &mut data, &owner, false);
+        let attestor = Pubkey::new_unique();
+        let initialize = |accounts: &[AccountInfo], fee_bps: u16, veto_threshold_bps: u16| {
+            process_initialize_config(&program_id, accounts, attestor, fee_bps, fee_bps, 0, veto_threshold_bps, 0, 0)
+                .unwrap_err()
+        };
+
+        let report = initialize(&accounts, 500, 5_000);
+        assert_eq!((report.error, report.log.account_index), (ProgramError::MissingRequiredSignature, 0));
+        accounts[0].is_signer = true;
+        let report = initialize(&accounts, MAX_FEE_BPS + 1, 5_000);
+        assert_eq!(
+            (report.error, report.log.expected, report.log.actual),
+            (FriendtechError::InvalidFee.into(), MAX_FEE_BPS.into(), (MAX_FEE_BPS + 1).into())
+        );
+        let report = initialize(&accounts, 500, 0);
+        assert_eq!(report.error, ProgramError::InvalidArgument);
+        let report = initialize(&accounts, 500, BPS_DENOMINATOR as u16 + 1);
+        assert_eq!(report.error, ProgramError::InvalidArgument);
+        let report = initialize(&accounts, 500, 5_000);
+        assert_eq!((report.error, report.log.account_index), (ProgramError::InvalidSeeds, 1));
+    }
+
+    #[test]
+    fn test_initialize_config_and_hand_over_admin() {
+        install_test_runtime();
+        let program_id = Pubkey::new_unique();
+        let (admin, successor, deployer) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let loader = bpf_loader_upgradeable::id();
+        let keys = [
+            admin,
+            find_config_address(&program_id).0,
+            system_program::id(),
+            find_stats_address(&program_id).0,
+            Pubkey::find_program_address(&[program_id.as_ref()], &loader).0,
+            admin,
+        ];
+        let mut lamports = [1_000_000_000, 0, 0, 0, 0, 0];
+        let mut program_data = [3, 0, 0, 0].to_vec();
+        program_data.extend_from_slice(&7u64.to_le_bytes());
+        program_data.push(1);
+        program_data.extend_from_slice(deployer.as_ref());
+        let mut data = vec![vec![], vec![0u8; Config::LEN], vec![], vec![0u8; Stats::LEN], program_data, vec![]];
+        let system = system_program::id();
+        let mut accounts = test_accounts(&keys, &mut lamports, &mut data, &system, true);
+        accounts[4].owner = &loader;
+
+        // A signer other than the upgrade authority cannot claim the config.
+        let attestor = Pubkey::new_unique();
+        let initialize = |accounts: &[AccountInfo]| {
+            process_initialize_config(&program_id, accounts, attestor, 500, 300, 86_400, 3_000, 100, 2_000)
+        };
+        let report = initialize(&accounts).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::Unauthorized.into(), 5));
+        accounts[5].key = &deployer;
+        initialize(&accounts).unwrap();
+        accounts[1].owner = &program_id;
+        let config = load_config(&program_id, &accounts[1]).unwrap();
+        assert_eq!((config.admin, config.attestor, config.subject_fee_bps), (admin, attestor, 300));
+        assert_eq!(Stats::unpack(&accounts[3].data.borrow()).unwrap().epoch, 10);
+        assert!(accounts[1].lamports() > 0 && accounts[3].lamports() > 0);
+
+        // The admin role passes only once the nominee accepts it.
+        process_transfer_admin(&program_id, &accounts[..2], Some(successor)).unwrap();
+        accounts[0].key = &successor;
+        process_accept_admin(&program_id, &accounts[..2]).unwrap();
+        let config = load_config(&program_id, &accounts[1]).unwrap();
+        assert_eq!((config.admin, config.pending_admin), (successor, None));
+    }
+}
@@ -0,0 +1,337 @@
+//! Merkle airdrops of shares or quote tokens to a market's holders.
+
+use super::accounts::{
+    check_feature, check_payer, check_signer, check_token_program, check_unlocked, create_pda_account,
+    create_token_pda, load_config, load_holding, load_market, load_or_create_holding, load_or_create_position,
+    load_position,
+};
+use crate::{
+    accrue_rewards, airdrop_leaf, find_airdrop_address, find_airdrop_escrow_address, merkle, update_reward_index,
+    weighted_tax_basis, AccountType, Airdrop, AirdropKind, AtAccount, ErrorReport, FriendtechError, Holding, Market,
+    Position, AIRDROP_BITMAP_BYTES, AIRDROP_ESCROW_SEED, AIRDROP_SEED, FEATURE_AIRDROPS, MAX_AIRDROP_CLAIMS,
+    NO_ACCOUNT,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+use spl_token::{instruction as spl_token_instruction, state::Account as SplTokenAccount};
+
+pub(super) fn process_set_airdrop_root(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    airdrop_id: u64,
+    root: [u8; 32],
+    kind: AirdropKind,
+    total: u64,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let payer = next_account_info(accounts_iter)?;
+    let subject = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+    let airdrop_account = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+
+    check_payer(payer).at_account(0)?;
+    check_signer(subject).at_account(1)?;
+    check_feature(&load_config(program_id, config_account).at_account(5)?, FEATURE_AIRDROPS)?;
+    let market = load_market(program_id, market_account).at_account(2)?;
+    if market.authority != *subject.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 1));
+    }
+    if total == 0 {
+        return Err(ErrorReport::at(ProgramError::InvalidArgument, NO_ACCOUNT));
+    }
+    let id_bytes = airdrop_id.to_le_bytes();
+    let (airdrop_key, bump) = find_airdrop_address(program_id, market_account.key, airdrop_id);
+    if airdrop_key != *airdrop_account.key {
+        return Err(ErrorReport::at(ProgramError::InvalidSeeds, 3));
+    }
+    if airdrop_account.owner == program_id {
+        return Err(ErrorReport::at(ProgramError::AccountAlreadyInitialized, 3));
+    }
+
+    match kind {
+        AirdropKind::Shares => {
+            let position_account = next_account_info(accounts_iter)?;
+            let holding_account = next_account_info(accounts_iter)?;
+            let mut position = load_position(
+                program_id,
+                subject.key,
+                market_account.key,
+                position_account,
+                Some((payer, system_program_account)),
+            )
+            .at_account(6)?;
+            let now = Clock::get()?.unix_timestamp;
+            check_unlocked(&position, now).at_account(6)?;
+            if position.balance < total {
+                return Err(ErrorReport::at(FriendtechError::InsufficientFunds, 6).values(total, position.balance));
+            }
+            // Escrowing shares leaves the supply unchanged, so the market
+            // need not store its updated index, only a holder leaving.
+            let mut market = market.clone();
+            update_reward_index(&mut market, now)?;
+            accrue_rewards(&market, &mut position)?;
+            position.balance -= total;
+            if position.balance == 0 {
+                if let Some(mut holding) =
+                    load_holding(program_id, subject.key, subject.key, holding_account).at_account(7)?
+                {
+                    holding.markets = holding.markets.saturating_sub(1);
+                    Holding::pack(holding, &mut holding_account.data.borrow_mut()).at_account(7)?;
+                }
+                market.holders = market.holders.saturating_sub(1);
+                Market::pack(market, &mut market_account.data.borrow_mut()).at_account(2)?;
+            }
+            Position::pack(position, &mut position_account.data.borrow_mut()).at_account(6)?;
+        }
+        AirdropKind::QuoteTokens => {
+            let escrow = next_account_info(accounts_iter)?;
+            let token_account = next_account_info(accounts_iter)?;
+            let quote_mint = next_account_info(accounts_iter)?;
+            let token_program = next_account_info(accounts_iter)?;
+            check_token_program(token_program).at_account(9)?;
+            if market.quote_mint != *quote_mint.key {
+                return Err(ErrorReport::at(FriendtechError::AccountMismatch, 8));
+            }
+            let (escrow_key, escrow_bump) = find_airdrop_escrow_address(program_id, &airdrop_key);
+            if escrow_key != *escrow.key {
+                return Err(ErrorReport::at(ProgramError::InvalidSeeds, 6));
+            }
+            create_token_pda(
+                payer,
+                escrow,
+                quote_mint,
+                token_program,
+                system_program_account,
+                &airdrop_key,
+                &[AIRDROP_ESCROW_SEED, airdrop_key.as_ref(), &[escrow_bump]],
+            )?;
+            let ix = spl_token_instruction::transfer(
+                token_program.key,
+                token_account.key,
+                escrow.key,
+                subject.key,
+                &[],
+                total,
+            )?;
+            invoke(&ix, &[token_account.clone(), escrow.clone(), subject.clone(), token_program.clone()])?;
+        }
+    }
+
+    create_pda_account(
+        payer,
+        airdrop_account,
+        system_program_account,
+        program_id,
+        Airdrop::LEN,
+        &[AIRDROP_SEED, market_account.key.as_ref(), &id_bytes, &[bump]],
+    )?;
+    let airdrop = Airdrop {
+        account_type: AccountType::Airdrop,
+        market: *market_account.key,
+        id: airdrop_id,
+        root,
+        kind,
+        remaining: total,
+        claimed: [0; AIRDROP_BITMAP_BYTES],
+        bump,
+    };
+    Airdrop::pack(airdrop, &mut airdrop_account.data.borrow_mut()).at_account(3)
+}
+
+pub(super) fn process_claim_airdrop(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    index: u32,
+    amount: u64,
+    proof: Vec<[u8; 32]>,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let payer = next_account_info(accounts_iter)?;
+    let claimant = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+    let airdrop_account = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+
+    check_payer(payer).at_account(0)?;
+    check_feature(&load_config(program_id, config_account).at_account(5)?, FEATURE_AIRDROPS)?;
+    let market = load_market(program_id, market_account).at_account(2)?;
+    let mut airdrop = load_airdrop(program_id, market_account.key, airdrop_account).at_account(3)?;
+    if index >= MAX_AIRDROP_CLAIMS || !merkle::verify(&airdrop.root, airdrop_leaf(index, claimant.key, amount), &proof)
+    {
+        return Err(ErrorReport::at(FriendtechError::InvalidProof, 3));
+    }
+    if airdrop.is_claimed(index) {
+        return Err(ErrorReport::at(FriendtechError::AlreadyClaimed, 3));
+    }
+    if amount > airdrop.remaining {
+        return Err(ErrorReport::at(FriendtechError::InsufficientFunds, 3).values(amount, airdrop.remaining));
+    }
+    airdrop.set_claimed(index);
+    airdrop.remaining -= amount;
+
+    match airdrop.kind {
+        AirdropKind::Shares => {
+            let position_account = next_account_info(accounts_iter)?;
+            let holding_account = next_account_info(accounts_iter)?;
+            let mut position = load_or_create_position(
+                program_id,
+                payer,
+                claimant.key,
+                market_account.key,
+                position_account,
+                system_program_account,
+            )
+            .at_account(6)?;
+            let now = Clock::get()?.unix_timestamp;
+            // Releasing escrowed shares leaves the supply unchanged, so the
+            // market need not store its updated index, only a new holder.
+            let mut market = market.clone();
+            update_reward_index(&mut market, now)?;
+            accrue_rewards(&market, &mut position)?;
+            position.set_tax_basis_at(weighted_tax_basis(&position, amount, now));
+            if position.balance == 0 && amount > 0 {
+                position.set_acquired_at(now);
+                let mut holding = load_or_create_holding(
+                    program_id,
+                    payer,
+                    claimant.key,
+                    &market.subject,
+                    holding_account,
+                    system_program_account,
+                )
+                .at_account(7)?;
+                holding.markets = holding.markets.checked_add(1).ok_or(ProgramError::ArithmeticOverflow)?;
+                Holding::pack(holding, &mut holding_account.data.borrow_mut()).at_account(7)?;
+                market.holders = market.holders.checked_add(1).ok_or(ProgramError::ArithmeticOverflow)?;
+                Market::pack(market, &mut market_account.data.borrow_mut()).at_account(2)?;
+            }
+            position.balance = position.balance.checked_add(amount).ok_or(ProgramError::ArithmeticOverflow)?;
+            Position::pack(position, &mut position_account.data.borrow_mut()).at_account(6)?;
+        }
+        AirdropKind::QuoteTokens => {
+            let escrow = next_account_info(accounts_iter)?;
+            let token_account = next_account_info(accounts_iter)?;
+            let token_program = next_account_info(accounts_iter)?;
+            check_token_program(token_program).at_account(8)?;
+            let destination = SplTokenAccount::unpack(&token_account.data.borrow()).at_account(7)?;
+            if destination.owner != *claimant.key {
+                return Err(ErrorReport::at(FriendtechError::AccountMismatch, 7));
+            }
+            let id_bytes = airdrop.id.to_le_bytes();
+            let seeds: &[&[u8]] = &[AIRDROP_SEED, airdrop.market.as_ref(), &id_bytes, &[airdrop.bump]];
+            let ix = spl_token_instruction::transfer(
+                token_program.key,
+                escrow.key,
+                token_account.key,
+                airdrop_account.key,
+                &[],
+                amount,
+            )?;
+            invoke_signed(
+                &ix,
+                &[escrow.clone(), token_account.clone(), airdrop_account.clone(), token_program.clone()],
+                &[seeds],
+            )?;
+        }
+    }
+    Airdrop::pack(airdrop, &mut airdrop_account.data.borrow_mut()).at_account(3)
+}
+
+/// Loads one of `market`'s airdrops after checking its owner and address.
+pub(super) fn load_airdrop(
+    program_id: &Pubkey,
+    market: &Pubkey,
+    airdrop_account: &AccountInfo,
+) -> Result<Airdrop, ProgramError> {
+    if airdrop_account.owner != program_id {
+        return Err(FriendtechError::IncorrectOwner.into());
+    }
+    let airdrop = Airdrop::unpack(&airdrop_account.data.borrow())?;
+    if airdrop.market != *market {
+        return Err(FriendtechError::AccountMismatch.into());
+    }
+    let airdrop_key = Pubkey::create_program_address(
+        &[AIRDROP_SEED, market.as_ref(), &airdrop.id.to_le_bytes(), &[airdrop.bump]],
+        program_id,
+    )?;
+    if airdrop_key != *airdrop_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(airdrop)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{install_test_runtime, sample_config, sample_market, test_accounts};
+    use crate::{find_config_address, find_holding_address, find_position_address, Config};
+    use solana_program::system_program;
+
+    #[test]
+    fn test_share_airdrop_claim() {
+        install_test_runtime();
+        let program_id = Pubkey::new_unique();
+        let (subject, claimant, market_key) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let market = sample_market(subject);
+        let (config_key, config_bump) = find_config_address(&program_id);
+        let (subject_position, subject_bump) = find_position_address(&program_id, &market_key, &subject);
+        let leaves = [airdrop_leaf(0, &claimant, 3), airdrop_leaf(1, &Pubkey::new_unique(), 2)];
+        let keys = [
+            subject,
+            subject,
+            market_key,
+            find_airdrop_address(&program_id, &market_key, 7).0,
+            system_program::id(),
+            config_key,
+            subject_position,
+            find_holding_address(&program_id, &subject, &subject).0,
+            find_position_address(&program_id, &market_key, &claimant).0,
+            find_holding_address(&program_id, &claimant, &subject).0,
+        ];
+        let mut lamports = [1_000_000_000, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let mut data = vec![vec![]; 10];
+        data[2] = vec![0u8; Market::LEN];
+        Market::pack(market, &mut data[2]).unwrap();
+        data[3] = vec![0u8; Airdrop::LEN];
+        data[5] = vec![0u8; Config::LEN];
+        Config::pack(Config { bump: config_bump, ..sample_config(Pubkey::new_unique()) }, &mut data[5]).unwrap();
+        data[6] = vec![0u8; Position::LEN];
+        Position::pack(Position { balance: 12, ..Position::new(subject, subject_bump) }, &mut data[6]).unwrap();
+        data[8] = vec![0u8; Position::LEN];
+        data[9] = vec![0u8; Holding::LEN];
+        let mut accounts = test_accounts(&keys, &mut lamports, &mut data, &program_id, true);
+        let system = system_program::id();
+        for index in [3, 4, 7, 8, 9] {
+            accounts[index].owner = &system;
+        }
+
+        // The subject escrows 5 of their shares for the airdrop's leaves.
+        let root = merkle::root(&leaves);
+        process_set_airdrop_root(&program_id, &accounts[..8], 7, root, AirdropKind::Shares, 5).unwrap();
+        assert_eq!(Position::unpack(&accounts[6].data.borrow()).unwrap().balance, 7);
+        accounts[3].owner = &program_id;
+
+        // The claimant's leaf moves their shares into a new position.
+        accounts[1].key = &claimant;
+        let claim = [&accounts[..6], &accounts[8..]].concat();
+        let proof = merkle::proof(&leaves, 0).unwrap();
+        process_claim_airdrop(&program_id, &claim, 0, 3, proof).unwrap();
+        let airdrop = Airdrop::unpack(&accounts[3].data.borrow()).unwrap();
+        assert!(airdrop.is_claimed(0) && !airdrop.is_claimed(1));
+        assert_eq!(airdrop.remaining, 2);
+        let position = Position::unpack(&accounts[8].data.borrow()).unwrap();
+        assert_eq!((position.owner, position.balance), (claimant, 3));
+        assert_eq!(Holding::unpack(&accounts[9].data.borrow()).unwrap().markets, 1);
+        assert_eq!(Market::unpack(&accounts[2].data.borrow()).unwrap().holders, 1);
+    }
+}
@@ -0,0 +1,558 @@
+//! `BuyShares`, `BuyMaxForBudget` and `BuyBasket`, and the buy execution
+//! shared with DCA schedules.
+
+use super::accounts::{
+    check_distinct, check_feature, check_interface_fee, check_not_winding_down, check_payer, check_router,
+    check_signer, check_token_program, check_vault, check_vault_balance, load_config, load_market,
+    load_or_create_holding, load_or_create_position, load_stats, token_balance,
+};
+use super::candles::record_trade_candle;
+use super::log_trade;
+use super::receipt::{write_receipt, ReceiptAccounts};
+use crate::{
+    accrue_fees, accrue_rewards, max_shares_for_budget, price_impact, quote_buy, record_volume, roll_volume_window,
+    update_reward_index, weighted_tax_basis, AtAccount, Config, ErrorReport, FriendtechError, Holding, InterfaceFee,
+    Market, MarketPause, Position, Stats, TradeSettlement, BASKET_FIXED_ACCOUNTS, BASKET_MARKET_ACCOUNTS,
+    BPS_DENOMINATOR, FEATURE_BASKETS, MAX_BASKET_MARKETS, NO_ACCOUNT,
+};
+use borsh::BorshSerialize;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    program::{invoke_signed, set_return_data},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar::{instructions as sysvar_instructions, Sysvar},
+};
+use spl_token::{instruction as spl_token_instruction, state::Account as SplTokenAccount};
+
+/// How much a single-market buy takes.
+pub(super) enum BuySize {
+    Shares(u64),
+    /// The most shares `budget` affords, at least `min_shares`.
+    Budget {
+        budget: u64,
+        min_shares: u64,
+    },
+    /// `amount` shares, if their per-share curve price is within `min..=max`.
+    PriceBand {
+        amount: u64,
+        min: u64,
+        max: u64,
+    },
+}
+
+pub(super) fn process_buy_shares(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    size: BuySize,
+    client_id: Option<[u8; 8]>,
+    interface_fee: Option<InterfaceFee>,
+    receipt_id: Option<[u8; 32]>,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let payer = next_account_info(accounts_iter)?;
+    let buyer = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+    let position_account = next_account_info(accounts_iter)?;
+    let token_account = next_account_info(accounts_iter)?;
+    let vault = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+    let stats_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let holding_account = next_account_info(accounts_iter)?;
+
+    check_payer(payer).at_account(0)?;
+    check_signer(buyer).at_account(1)?;
+    check_token_program(token_program).at_account(6)?;
+    let mut market = load_market(program_id, market_account).at_account(2)?;
+    check_router(&market, accounts, 2)?;
+    check_vault(&market, vault).at_account(5)?;
+    let mut stats = load_stats(program_id, stats_account).at_account(8)?;
+    let config = load_config(program_id, config_account).at_account(9)?;
+    let interface_fee = match interface_fee {
+        Some(fee) => Some(check_interface_fee(&config, &fee, next_account_info(accounts_iter)?, 11)?),
+        None => None,
+    };
+    let recipient = interface_fee.map_or(vault, |(_, recipient)| recipient);
+    let fee_accounts = [(token_account, 4), (vault, 5), (recipient, 11)];
+    check_distinct(&fee_accounts[..2 + interface_fee.is_some() as usize])?;
+    let receipt = match receipt_id {
+        Some(receipt_id) => {
+            let receipt = (next_account_info(accounts_iter)?, 11 + interface_fee.is_some() as u8);
+            Some((receipt_id, ReceiptAccounts { payer: (payer, 0), receipt, system_program: system_program_account }))
+        }
+        None => None,
+    };
+    let candles = if market.candles {
+        Some((next_account_info(accounts_iter)?, 11 + interface_fee.is_some() as u8 + receipt.is_some() as u8))
+    } else {
+        None
+    };
+    let clock = Clock::get()?;
+    roll_volume_window(&mut market, clock.unix_timestamp);
+    let amount = match size {
+        BuySize::Shares(amount) => amount,
+        BuySize::Budget { budget, min_shares } => {
+            let amount = max_shares_for_budget(&config, &market, budget);
+            if amount == 0 || amount < min_shares {
+                return Err(ErrorReport::at(FriendtechError::SlippageExceeded, NO_ACCOUNT).values(min_shares, amount));
+            }
+            amount
+        }
+        BuySize::PriceBand { amount, min, max } => {
+            check_price_band(&market, amount, min, max)?;
+            amount
+        }
+    };
+
+    let trade = BuyAccounts {
+        payer,
+        buyer,
+        authority: buyer,
+        authority_seeds: None,
+        token_account: (token_account, 4),
+        token_program,
+        system_program: system_program_account,
+        market: (market_account, 2),
+        position: (position_account, 3),
+        vault,
+        holding: (holding_account, 10),
+        interface_fee,
+    };
+    let settlement = execute_buy(program_id, &trade, &config, market, amount, &mut stats, &clock, client_id)?;
+    Stats::pack(stats, &mut stats_account.data.borrow_mut()).at_account(8)?;
+    record_trade_candle(program_id, candles, market_account.key, amount, settlement.gross_price, clock.unix_timestamp)?;
+    if let Some((receipt_id, receipt_accounts)) = receipt {
+        write_receipt(
+            program_id,
+            &receipt_accounts,
+            &receipt_id,
+            market_account.key,
+            buyer.key,
+            true,
+            amount,
+            &settlement,
+            clock.slot,
+        )?;
+    }
+    set_return_data(&settlement.try_to_vec()?);
+    Ok(())
+}
+
+/// Checks that the per-share curve price of a buy of `amount` shares is
+/// within `min..=max`.
+fn check_price_band(market: &Market, amount: u64, min: u64, max: u64) -> Result<(), ErrorReport> {
+    let price = price_impact(market, amount, true)?.pre_trade_price;
+    if price < min {
+        return Err(ErrorReport::at(FriendtechError::PriceOutOfBand, NO_ACCOUNT).values(min, price));
+    }
+    if price > max {
+        return Err(ErrorReport::at(FriendtechError::PriceOutOfBand, NO_ACCOUNT).values(max, price));
+    }
+    Ok(())
+}
+
+pub(super) fn process_buy_basket(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    budget: u64,
+    weights_bps: Vec<u16>,
+    client_id: Option<[u8; 8]>,
+) -> Result<(), ErrorReport> {
+    if weights_bps.is_empty() || weights_bps.len() > MAX_BASKET_MARKETS {
+        return Err(ErrorReport::at(FriendtechError::InvalidBasket, NO_ACCOUNT)
+            .values(MAX_BASKET_MARKETS as u64, weights_bps.len() as u64));
+    }
+    let total_weight: u64 = weights_bps.iter().map(|&weight| u64::from(weight)).sum();
+    if total_weight != BPS_DENOMINATOR {
+        return Err(ErrorReport::at(FriendtechError::InvalidBasket, NO_ACCOUNT).values(BPS_DENOMINATOR, total_weight));
+    }
+    // A trailing instructions sysvar, for `check_router`, is not part of the
+    // basket.
+    let basket_accounts = match accounts.last() {
+        Some(last) if sysvar_instructions::check_id(last.key) => accounts.len() - 1,
+        _ => accounts.len(),
+    };
+    let expected_accounts = BASKET_FIXED_ACCOUNTS + BASKET_MARKET_ACCOUNTS * weights_bps.len();
+    if basket_accounts != expected_accounts {
+        return Err(ErrorReport::at(ProgramError::NotEnoughAccountKeys, NO_ACCOUNT)
+            .values(expected_accounts as u64, basket_accounts as u64));
+    }
+
+    let accounts_iter = &mut accounts.iter();
+    let payer = next_account_info(accounts_iter)?;
+    let buyer = next_account_info(accounts_iter)?;
+    let token_account = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+    let stats_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+
+    check_payer(payer).at_account(0)?;
+    check_signer(buyer).at_account(1)?;
+    check_token_program(token_program).at_account(3)?;
+    let mut stats = load_stats(program_id, stats_account).at_account(5)?;
+    let config = load_config(program_id, config_account).at_account(6)?;
+    check_feature(&config, FEATURE_BASKETS)?;
+    let clock = Clock::get()?;
+
+    let mut settlements = Vec::with_capacity(weights_bps.len());
+    for (i, weight) in weights_bps.into_iter().enumerate() {
+        let index = (BASKET_FIXED_ACCOUNTS + BASKET_MARKET_ACCOUNTS * i) as u8;
+        let market_account = next_account_info(accounts_iter)?;
+        let position_account = next_account_info(accounts_iter)?;
+        let vault = next_account_info(accounts_iter)?;
+        let holding_account = next_account_info(accounts_iter)?;
+
+        let mut market = load_market(program_id, market_account).at_account(index)?;
+        // Legs have no room for a candles account.
+        if market.candles {
+            return Err(ErrorReport::at(FriendtechError::InvalidBasket, index));
+        }
+        check_router(&market, accounts, index)?;
+        check_vault(&market, vault).at_account(index + 2)?;
+        check_distinct(&[(token_account, 2), (vault, index + 2)])?;
+        roll_volume_window(&mut market, clock.unix_timestamp);
+
+        let allocation = (budget as u128 * weight as u128 / BPS_DENOMINATOR as u128) as u64;
+        let amount = max_shares_for_budget(&config, &market, allocation);
+        if amount == 0 {
+            settlements.push(TradeSettlement {
+                gross_price: 0,
+                protocol_fee: 0,
+                subject_fee: 0,
+                interface_fee: 0,
+                net_amount: 0,
+                new_supply: market.supply,
+            });
+            continue;
+        }
+        let trade = BuyAccounts {
+            payer,
+            buyer,
+            authority: buyer,
+            authority_seeds: None,
+            token_account: (token_account, 2),
+            token_program,
+            system_program: system_program_account,
+            market: (market_account, index),
+            position: (position_account, index + 1),
+            vault,
+            holding: (holding_account, index + 3),
+            interface_fee: None,
+        };
+        settlements.push(execute_buy(program_id, &trade, &config, market, amount, &mut stats, &clock, client_id)?);
+    }
+    Stats::pack(stats, &mut stats_account.data.borrow_mut()).at_account(5)?;
+    set_return_data(&settlements.try_to_vec()?);
+    Ok(())
+}
+
+/// Accounts taking part in one curve buy, with the instruction indices used
+/// in error reports.
+pub(super) struct BuyAccounts<'a, 'b> {
+    pub(super) payer: &'a AccountInfo<'b>,
+    /// Owner of the position; its key is all that is used.
+    pub(super) buyer: &'a AccountInfo<'b>,
+    /// Authority of `token_account`, signing with `authority_seeds` if it is
+    /// a PDA of this program.
+    pub(super) authority: &'a AccountInfo<'b>,
+    pub(super) authority_seeds: Option<&'a [&'a [u8]]>,
+    pub(super) token_account: (&'a AccountInfo<'b>, u8),
+    pub(super) token_program: &'a AccountInfo<'b>,
+    pub(super) system_program: &'a AccountInfo<'b>,
+    pub(super) market: (&'a AccountInfo<'b>, u8),
+    pub(super) position: (&'a AccountInfo<'b>, u8),
+    pub(super) vault: &'a AccountInfo<'b>,
+    pub(super) holding: (&'a AccountInfo<'b>, u8),
+    /// Validated interface fee rate and recipient.
+    pub(super) interface_fee: Option<(u16, &'a AccountInfo<'b>)>,
+}
+
+/// Buys `amount` shares of an already validated market, creating the
+/// buyer's position and holding if needed. The trade is added to `stats`,
+/// which the caller stores, and logged as a `TradeEvent`.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn execute_buy(
+    program_id: &Pubkey,
+    trade: &BuyAccounts,
+    config: &Config,
+    mut market: Market,
+    amount: u64,
+    stats: &mut Stats,
+    clock: &Clock,
+    client_id: Option<[u8; 8]>,
+) -> Result<TradeSettlement, ErrorReport> {
+    let ((market_account, market_index), (position_account, position_index)) = (trade.market, trade.position);
+    let (token_account, token_index) = trade.token_account;
+    // An empty buy would still count the buyer as a holder.
+    if amount == 0 {
+        return Err(ErrorReport::at(ProgramError::InvalidArgument, NO_ACCOUNT).values(1, 0));
+    }
+    check_not_winding_down(config)?;
+    if market.pause != MarketPause::Unpaused {
+        return Err(ErrorReport::at(FriendtechError::MarketPaused, market_index));
+    }
+    if clock.unix_timestamp < market.opens_at {
+        return Err(ErrorReport::at(FriendtechError::MarketNotOpen, market_index)
+            .values(market.opens_at as u64, clock.unix_timestamp as u64));
+    }
+    let mut position = load_or_create_position(
+        program_id,
+        trade.payer,
+        trade.buyer.key,
+        market_account.key,
+        position_account,
+        trade.system_program,
+    )
+    .at_account(position_index)?;
+
+    let mut settlement = quote_buy(config, &market, amount)?;
+    if let Some((fee_bps, _)) = trade.interface_fee {
+        settlement = settlement.with_interface_fee(fee_bps, true)?;
+    }
+    let user_spl_token_account = SplTokenAccount::unpack(&token_account.data.borrow()).at_account(token_index)?;
+    if user_spl_token_account.amount < settlement.net_amount {
+        return Err(ErrorReport::at(FriendtechError::InsufficientFunds, token_index)
+            .values(settlement.net_amount, user_spl_token_account.amount));
+    }
+
+    let vault_amount = settlement.net_amount - settlement.interface_fee;
+    let vault_balance =
+        token_balance(trade.vault)?.checked_add(vault_amount).ok_or(ProgramError::ArithmeticOverflow)?;
+    let ix = spl_token_instruction::transfer(
+        trade.token_program.key,
+        token_account.key,
+        trade.vault.key,
+        trade.authority.key,
+        &[],
+        vault_amount,
+    )?;
+    invoke_signed(
+        &ix,
+        &[token_account.clone(), trade.vault.clone(), trade.authority.clone(), trade.token_program.clone()],
+        trade.authority_seeds.as_slice(),
+    )?;
+    if let Some((_, recipient)) = trade.interface_fee {
+        let ix = spl_token_instruction::transfer(
+            trade.token_program.key,
+            token_account.key,
+            recipient.key,
+            trade.authority.key,
+            &[],
+            settlement.interface_fee,
+        )?;
+        invoke_signed(
+            &ix,
+            &[token_account.clone(), recipient.clone(), trade.authority.clone(), trade.token_program.clone()],
+            trade.authority_seeds.as_slice(),
+        )?;
+    }
+    check_vault_balance(trade.vault, vault_balance)?;
+
+    update_reward_index(&mut market, clock.unix_timestamp)?;
+    accrue_rewards(&market, &mut position)?;
+    position.set_tax_basis_at(weighted_tax_basis(&position, amount, clock.unix_timestamp));
+    if position.balance == 0 {
+        position.set_acquired_at(clock.unix_timestamp);
+        let (holding_account, holding_index) = trade.holding;
+        let mut holding = load_or_create_holding(
+            program_id,
+            trade.payer,
+            trade.buyer.key,
+            &market.subject,
+            holding_account,
+            trade.system_program,
+        )
+        .at_account(holding_index)?;
+        holding.markets = holding.markets.checked_add(1).ok_or(ProgramError::ArithmeticOverflow)?;
+        Holding::pack(holding, &mut holding_account.data.borrow_mut()).at_account(holding_index)?;
+        market.holders = market.holders.checked_add(1).ok_or(ProgramError::ArithmeticOverflow)?;
+    }
+    position.balance = position.balance.checked_add(amount).ok_or(ProgramError::ArithmeticOverflow)?;
+    market.supply = settlement.new_supply;
+    accrue_fees(&mut market, settlement.protocol_fee, settlement.subject_fee)?;
+    stats.record_trade(&mut market, &settlement, clock.epoch);
+    record_volume(&mut market, settlement.gross_price, clock.unix_timestamp);
+    Position::pack(position, &mut position_account.data.borrow_mut()).at_account(position_index)?;
+    Market::pack(market, &mut market_account.data.borrow_mut()).at_account(market_index)?;
+    let recipient = trade.interface_fee.map(|(_, recipient)| recipient.key);
+    log_trade(market_account.key, trade.buyer.key, true, amount, &settlement, client_id, recipient)?;
+    Ok(settlement)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{install_test_runtime, sample_config, sample_market, test_accounts};
+    use crate::{find_config_address, find_holding_address, find_position_address, find_stats_address, AccountType};
+    use solana_program::system_program;
+    use spl_token::state::AccountState;
+
+    fn sample_stats(bump: u8) -> Stats {
+        Stats {
+            account_type: AccountType::Stats,
+            total_markets: 1,
+            total_volume: 0,
+            total_fees: 0,
+            epoch: 0,
+            epoch_active_markets: 0,
+            last_epoch_active_markets: 0,
+            bump,
+        }
+    }
+
+    #[test]
+    fn test_check_price_band() {
+        let mut market = sample_market(Pubkey::new_unique());
+        market.supply = 40;
+        let price = price_impact(&market, 3, true).unwrap().pre_trade_price;
+        assert!(price > 0);
+        assert!(check_price_band(&market, 3, price, price).is_ok());
+        assert!(check_price_band(&market, 3, 0, u64::MAX).is_ok());
+        let report = check_price_band(&market, 3, price + 1, u64::MAX).unwrap_err();
+        assert_eq!(
+            (report.error, report.log.expected, report.log.actual),
+            (FriendtechError::PriceOutOfBand.into(), price + 1, price)
+        );
+        let report = check_price_band(&market, 3, 0, price - 1).unwrap_err();
+        assert_eq!((report.log.expected, report.log.actual), (price - 1, price));
+    }
+
+    #[test]
+    fn test_buy_basket_checks_weights_and_accounts() {
+        let program_id = Pubkey::new_unique();
+        let report = process_buy_basket(&program_id, &[], 100, vec![], None).unwrap_err();
+        assert_eq!(
+            (report.error, report.log.expected, report.log.actual),
+            (FriendtechError::InvalidBasket.into(), MAX_BASKET_MARKETS as u64, 0)
+        );
+        let report = process_buy_basket(&program_id, &[], 100, vec![1_000; MAX_BASKET_MARKETS + 1], None).unwrap_err();
+        assert_eq!(report.error, FriendtechError::InvalidBasket.into());
+
+        let report = process_buy_basket(&program_id, &[], 100, vec![5_000, 4_999], None).unwrap_err();
+        assert_eq!(
+            (report.error, report.log.expected, report.log.actual),
+            (FriendtechError::InvalidBasket.into(), BPS_DENOMINATOR, 9_999)
+        );
+
+        let report = process_buy_basket(&program_id, &[], 100, vec![5_000, 5_000], None).unwrap_err();
+        let expected_accounts = (BASKET_FIXED_ACCOUNTS + 2 * BASKET_MARKET_ACCOUNTS) as u64;
+        assert_eq!(
+            (report.error, report.log.expected, report.log.actual),
+            (ProgramError::NotEnoughAccountKeys, expected_accounts, 0)
+        );
+    }
+
+    #[test]
+    fn test_zero_share_buy_is_not_a_holding() {
+        install_test_runtime();
+        let program_id = Pubkey::new_unique();
+        let (buyer, market_key) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let market = sample_market(Pubkey::new_unique());
+        let (stats_key, stats_bump) = find_stats_address(&program_id);
+        let (config_key, config_bump) = find_config_address(&program_id);
+        let keys = [
+            buyer,
+            buyer,
+            market_key,
+            find_position_address(&program_id, &market_key, &buyer).0,
+            Pubkey::new_unique(),
+            market.vault,
+            spl_token::id(),
+            system_program::id(),
+            stats_key,
+            config_key,
+            find_holding_address(&program_id, &buyer, &market.subject).0,
+        ];
+        let mut lamports = [1_000_000_000, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let mut data = vec![vec![]; 11];
+        data[2] = vec![0u8; Market::LEN];
+        Market::pack(market.clone(), &mut data[2]).unwrap();
+        data[3] = vec![0u8; Position::LEN];
+        for (index, owner, amount) in [(4, buyer, 1_000_000_000), (5, market_key, 0)] {
+            data[index] = vec![0u8; SplTokenAccount::LEN];
+            let token = SplTokenAccount {
+                mint: market.quote_mint,
+                owner,
+                amount,
+                state: AccountState::Initialized,
+                ..SplTokenAccount::default()
+            };
+            SplTokenAccount::pack(token, &mut data[index]).unwrap();
+        }
+        data[8] = vec![0u8; Stats::LEN];
+        Stats::pack(sample_stats(stats_bump), &mut data[8]).unwrap();
+        data[9] = vec![0u8; Config::LEN];
+        Config::pack(Config { bump: config_bump, ..sample_config(Pubkey::new_unique()) }, &mut data[9]).unwrap();
+        data[10] = vec![0u8; Holding::LEN];
+        let (token, system) = (spl_token::id(), system_program::id());
+        let mut accounts = test_accounts(&keys, &mut lamports, &mut data, &token, true);
+        for index in [0, 1, 3, 10] {
+            accounts[index].owner = &system;
+        }
+        for index in [2, 8, 9] {
+            accounts[index].owner = &program_id;
+        }
+
+        // Buying nothing creates neither the position nor the holding, and
+        // does not count the buyer among the market's holders.
+        let report = process_buy_shares(&program_id, &accounts, BuySize::Shares(0), None, None, None).unwrap_err();
+        assert_eq!((report.error, report.log.expected, report.log.actual), (ProgramError::InvalidArgument, 1, 0));
+        assert_eq!(Market::unpack(&accounts[2].data.borrow()).unwrap().holders, 0);
+        assert_eq!(accounts[10].data.borrow().as_ref(), vec![0u8; Holding::LEN].as_slice());
+
+        process_buy_shares(&program_id, &accounts, BuySize::Shares(2), None, None, None).unwrap();
+        assert_eq!(Market::unpack(&accounts[2].data.borrow()).unwrap().holders, 1);
+        assert_eq!(Holding::unpack(&accounts[10].data.borrow()).unwrap().markets, 1);
+    }
+
+    #[test]
+    fn test_buy_basket_rejects_candle_markets() {
+        install_test_runtime();
+        let program_id = Pubkey::new_unique();
+        let (buyer, market_key) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let market = Market { candles: true, ..sample_market(Pubkey::new_unique()) };
+        let (stats_key, stats_bump) = find_stats_address(&program_id);
+        let (config_key, config_bump) = find_config_address(&program_id);
+        let keys = [
+            buyer,
+            buyer,
+            Pubkey::new_unique(),
+            spl_token::id(),
+            system_program::id(),
+            stats_key,
+            config_key,
+            market_key,
+            find_position_address(&program_id, &market_key, &buyer).0,
+            market.vault,
+            find_holding_address(&program_id, &buyer, &market.subject).0,
+        ];
+        let mut lamports = [1_000_000_000, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let mut data = vec![vec![]; 11];
+        data[5] = vec![0u8; Stats::LEN];
+        Stats::pack(sample_stats(stats_bump), &mut data[5]).unwrap();
+        data[6] = vec![0u8; Config::LEN];
+        Config::pack(Config { bump: config_bump, ..sample_config(Pubkey::new_unique()) }, &mut data[6]).unwrap();
+        data[7] = vec![0u8; Market::LEN];
+        Market::pack(market, &mut data[7]).unwrap();
+        let (token, system) = (spl_token::id(), system_program::id());
+        let mut accounts = test_accounts(&keys, &mut lamports, &mut data, &token, true);
+        for index in [0, 1, 8, 10] {
+            accounts[index].owner = &system;
+        }
+        for index in [5, 6, 7] {
+            accounts[index].owner = &program_id;
+        }
+
+        // The leg has no candles account to record the trade in.
+        let report = process_buy_basket(&program_id, &accounts, 1_000_000, vec![10_000], None).unwrap_err();
+        assert_eq!(
+            (report.error, report.log.account_index),
+            (FriendtechError::InvalidBasket.into(), BASKET_FIXED_ACCOUNTS as u8)
+        );
+    }
+}
@@ -0,0 +1,141 @@
+//! `CreateCandles` and the recording of trades into a market's `Candles`.
+
+use super::accounts::{check_payer, check_signer, create_pda_account, load_market};
+use crate::{
+    find_candles_address, record_candle, AccountType, AtAccount, Candle, Candles, ErrorReport, FriendtechError, Market,
+    CANDLES_SEED, CANDLE_BUCKETS,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+
+pub(super) fn process_create_candles(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let payer = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+    let candles_account = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+
+    check_payer(payer).at_account(0)?;
+    check_signer(authority).at_account(1)?;
+    let mut market = load_market(program_id, market_account).at_account(2)?;
+    if market.authority != *authority.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 1));
+    }
+    let (candles_key, bump) = find_candles_address(program_id, market_account.key);
+    if candles_key != *candles_account.key {
+        return Err(ErrorReport::at(ProgramError::InvalidSeeds, 3));
+    }
+    if market.candles || candles_account.owner == program_id {
+        return Err(ErrorReport::at(ProgramError::AccountAlreadyInitialized, 3));
+    }
+    create_pda_account(
+        payer,
+        candles_account,
+        system_program_account,
+        program_id,
+        Candles::LEN,
+        &[CANDLES_SEED, market_account.key.as_ref(), &[bump]],
+    )
+    .at_account(3)?;
+    let candles = Candles {
+        account_type: AccountType::Candles,
+        market: *market_account.key,
+        candles: [Candle::default(); CANDLE_BUCKETS],
+        hour: 0,
+        bump,
+    };
+    Candles::pack(candles, &mut candles_account.data.borrow_mut()).at_account(3)?;
+    market.candles = true;
+    Market::pack(market, &mut market_account.data.borrow_mut()).at_account(2)
+}
+
+/// Records a trade into `market`'s `Candles`, the account a trade passes
+/// with its index once the market records candles.
+pub(super) fn record_trade_candle(
+    program_id: &Pubkey,
+    candles_account: Option<(&AccountInfo, u8)>,
+    market: &Pubkey,
+    amount: u64,
+    gross_price: u64,
+    now: i64,
+) -> Result<(), ErrorReport> {
+    let Some((candles_account, index)) = candles_account else {
+        return Ok(());
+    };
+    let mut candles = load_candles(program_id, market, candles_account).at_account(index)?;
+    if !candles_account.is_writable {
+        return Err(ErrorReport::at(ProgramError::InvalidArgument, index));
+    }
+    record_candle(&mut candles, amount, gross_price, now);
+    Candles::pack(candles, &mut candles_account.data.borrow_mut()).at_account(index)
+}
+
+/// Loads `market`'s candles after checking their owner and address.
+fn load_candles(program_id: &Pubkey, market: &Pubkey, candles_account: &AccountInfo) -> Result<Candles, ProgramError> {
+    if candles_account.owner != program_id {
+        return Err(FriendtechError::IncorrectOwner.into());
+    }
+    let candles = Candles::unpack(&candles_account.data.borrow())?;
+    let candles_key = Pubkey::create_program_address(&[CANDLES_SEED, market.as_ref(), &[candles.bump]], program_id)?;
+    if candles.market != *market || candles_key != *candles_account.key {
+        return Err(FriendtechError::AccountMismatch.into());
+    }
+    Ok(candles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recent_candles;
+    use crate::tests::{install_test_runtime, sample_market, test_accounts, TEST_NOW};
+    use solana_program::system_program;
+
+    #[test]
+    fn test_create_candles_and_record_trade() {
+        install_test_runtime();
+        let (program_id, subject, market_key) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let keys =
+            [subject, subject, market_key, find_candles_address(&program_id, &market_key).0, system_program::id()];
+        let mut lamports = [1_000_000_000, 0, 0, 0, 0];
+        let mut data = vec![vec![], vec![], vec![0u8; Market::LEN], vec![0u8; Candles::LEN], vec![]];
+        Market::pack(sample_market(subject), &mut data[2]).unwrap();
+        let mut accounts = test_accounts(&keys, &mut lamports, &mut data, &program_id, true);
+        let system = system_program::id();
+        accounts[3].owner = &system;
+
+        // Only the market authority opts the market in.
+        let impostor = Pubkey::new_unique();
+        accounts[1].key = &impostor;
+        let report = process_create_candles(&program_id, &accounts).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::Unauthorized.into(), 1));
+        accounts[1].key = &subject;
+        process_create_candles(&program_id, &accounts).unwrap();
+        assert!(load_market(&program_id, &accounts[2]).unwrap().candles);
+        accounts[3].owner = &program_id;
+        let report = process_create_candles(&program_id, &accounts).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (ProgramError::AccountAlreadyInitialized, 3));
+
+        // The candles must be the market's own, and writable. The other
+        // market is one whose seeds with the candles' bump make an address.
+        let bump = Candles::unpack(&accounts[3].data.borrow()).unwrap().bump;
+        let other_market = std::iter::repeat_with(Pubkey::new_unique)
+            .find(|key| Pubkey::create_program_address(&[CANDLES_SEED, key.as_ref(), &[bump]], &program_id).is_ok())
+            .unwrap();
+        let report =
+            record_trade_candle(&program_id, Some((&accounts[3], 12)), &other_market, 2, 300, TEST_NOW).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::AccountMismatch.into(), 12));
+        accounts[3].is_writable = false;
+        let report = record_trade_candle(&program_id, Some((&accounts[3], 12)), &market_key, 2, 300, TEST_NOW);
+        assert_eq!(report.unwrap_err().error, ProgramError::InvalidArgument);
+        accounts[3].is_writable = true;
+        record_trade_candle(&program_id, Some((&accounts[3], 12)), &market_key, 2, 300, TEST_NOW).unwrap();
+        let recorded = Candles::unpack(&accounts[3].data.borrow()).unwrap();
+        let candle = Candle { open: 150, high: 150, low: 150, close: 150, volume: 300, trades: 1 };
+        assert_eq!(recent_candles(&recorded, TEST_NOW), vec![(TEST_NOW / 3_600, candle)]);
+    }
+}
@@ -0,0 +1,198 @@
+//! Locking positions as collateral for approved lending programs, which
+//! hold the lock instead of taking custody of the shares. A program proves
+//! itself by signing with its collateral authority, a PDA only it can sign
+//! for.
+
+use super::accounts::{check_feature, check_signer, load_config};
+use crate::{
+    AtAccount, Config, ErrorReport, FriendtechError, Position, COLLATERAL_SEED, FEATURE_LENDING,
+    MAX_COLLATERAL_PROGRAMS, NO_ACCOUNT,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+
+pub(super) fn process_set_collateral_programs(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    programs: Vec<Pubkey>,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let admin = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+
+    check_signer(admin).at_account(0)?;
+    let mut config = load_config(program_id, config_account).at_account(1)?;
+    if config.admin != *admin.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    if programs.len() > MAX_COLLATERAL_PROGRAMS {
+        return Err(ErrorReport::at(ProgramError::InvalidArgument, NO_ACCOUNT)
+            .values(MAX_COLLATERAL_PROGRAMS as u64, programs.len() as u64));
+    }
+    config.collateral_programs = [Pubkey::default(); MAX_COLLATERAL_PROGRAMS];
+    config.collateral_programs[..programs.len()].copy_from_slice(&programs);
+    Config::pack(config, &mut config_account.data.borrow_mut()).at_account(1)
+}
+
+pub(super) fn process_lock_position(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    bump: u8,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let position_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let lending_program = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+
+    check_signer(owner).at_account(0)?;
+    let config = load_config(program_id, config_account).at_account(2)?;
+    check_feature(&config, FEATURE_LENDING)?;
+    if position_account.owner != program_id {
+        return Err(ErrorReport::at(FriendtechError::IncorrectOwner, 1));
+    }
+    let mut position = Position::unpack(&position_account.data.borrow()).at_account(1)?;
+    if position.owner != *owner.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    if position.locked_by().is_some() {
+        return Err(ErrorReport::at(FriendtechError::PositionLocked, 1));
+    }
+    let lender = *lending_program.key;
+    if lender == Pubkey::default() || !config.collateral_programs.contains(&lender) {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 3));
+    }
+    check_collateral_authority(&lender, authority, bump).at_account(4)?;
+    position.set_locked_by(Some(lender));
+    Position::pack(position, &mut position_account.data.borrow_mut()).at_account(1)
+}
+
+pub(super) fn process_unlock_position(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    bump: u8,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let position_account = next_account_info(accounts_iter)?;
+    let authority = next_account_info(accounts_iter)?;
+
+    // Not feature-gated, so locks taken before lending was turned off can
+    // still be released.
+    if position_account.owner != program_id {
+        return Err(ErrorReport::at(FriendtechError::IncorrectOwner, 0));
+    }
+    let mut position = Position::unpack(&position_account.data.borrow()).at_account(0)?;
+    let Some(lender) = position.locked_by() else {
+        return Ok(());
+    };
+    check_collateral_authority(&lender, authority, bump).at_account(1)?;
+    position.set_locked_by(None);
+    Position::pack(position, &mut position_account.data.borrow_mut()).at_account(0)
+}
+
+/// Checks that `authority` is `lender`'s collateral authority, which only
+/// `lender` can sign for, and that it signed.
+fn check_collateral_authority(lender: &Pubkey, authority: &AccountInfo, bump: u8) -> Result<(), ProgramError> {
+    check_signer(authority)?;
+    match Pubkey::create_program_address(&[COLLATERAL_SEED, &[bump]], lender) {
+        Ok(expected) if expected == *authority.key => Ok(()),
+        _ => Err(FriendtechError::Unauthorized.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::sample_config;
+    use crate::{find_collateral_authority_address, find_config_address, DEFAULT_FEATURES};
+
+    #[test]
+    fn test_position_lock_requires_collateral_authority() {
+        let program_id = Pubkey::new_unique();
+        let (owner, position_key) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (config_key, bump) = find_config_address(&program_id);
+        let (lender, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (lender_authority, lender_bump) = find_collateral_authority_address(&lender);
+        let (other_authority, other_bump) = find_collateral_authority_address(&other);
+        let position = Position { balance: 5, ..Position::new(owner, 255) };
+        let mut config =
+            Config { bump, features: DEFAULT_FEATURES | FEATURE_LENDING, ..sample_config(Pubkey::new_unique()) };
+        config.collateral_programs[0] = lender;
+        let mut position_data = vec![0u8; Position::LEN];
+        Position::pack(position, &mut position_data).unwrap();
+        let mut config_data = vec![0u8; Config::LEN];
+        Config::pack(config, &mut config_data).unwrap();
+        let (mut owner_lamports, mut position_lamports, mut config_lamports) = (0, 0, 0);
+        let (mut program_lamports, mut authority_lamports) = (0, 0);
+        let (mut owner_data, mut program_data, mut authority_data) = (vec![], vec![], vec![]);
+        let system = Pubkey::default();
+        let mut accounts = [
+            AccountInfo::new(&owner, false, false, &mut owner_lamports, &mut owner_data, &system, false, 0),
+            AccountInfo::new(
+                &position_key,
+                false,
+                true,
+                &mut position_lamports,
+                &mut position_data,
+                &program_id,
+                false,
+                0,
+            ),
+            AccountInfo::new(&config_key, false, false, &mut config_lamports, &mut config_data, &program_id, false, 0),
+            AccountInfo::new(&other, false, false, &mut program_lamports, &mut program_data, &system, true, 0),
+            AccountInfo::new(
+                &other_authority,
+                true,
+                false,
+                &mut authority_lamports,
+                &mut authority_data,
+                &system,
+                false,
+                0,
+            ),
+        ];
+
+        let report = process_lock_position(&program_id, &accounts, other_bump).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (ProgramError::MissingRequiredSignature, 0));
+        // A program that is not approved cannot lock, even signing with its
+        // own authority, nor pass off its authority as an approved lender's.
+        accounts[0].is_signer = true;
+        let report = process_lock_position(&program_id, &accounts, other_bump).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::Unauthorized.into(), 3));
+        accounts[3].key = &lender;
+        let report = process_lock_position(&program_id, &accounts, other_bump).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::Unauthorized.into(), 4));
+
+        // The approved lender's authority must sign.
+        accounts[4].key = &lender_authority;
+        accounts[4].is_signer = false;
+        let report = process_lock_position(&program_id, &accounts, lender_bump).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (ProgramError::MissingRequiredSignature, 4));
+        accounts[4].is_signer = true;
+        process_lock_position(&program_id, &accounts, lender_bump).unwrap();
+        assert_eq!(Position::unpack(&accounts[1].data.borrow()).unwrap().locked_by(), Some(lender));
+        let report = process_lock_position(&program_id, &accounts, lender_bump).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::PositionLocked.into(), 1));
+
+        // Only the lender's authority can unlock the position.
+        let mut unlock_accounts = [accounts[1].clone(), accounts[4].clone()];
+        unlock_accounts[1].key = &other_authority;
+        let report = process_unlock_position(&program_id, &unlock_accounts, other_bump).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::Unauthorized.into(), 1));
+
+        // Turning lending off stops new locks but still releases existing ones.
+        let mut config = Config::unpack(&accounts[2].data.borrow()).unwrap();
+        config.features = DEFAULT_FEATURES;
+        Config::pack(config, &mut accounts[2].data.borrow_mut()).unwrap();
+        unlock_accounts[1].key = &lender_authority;
+        process_unlock_position(&program_id, &unlock_accounts, lender_bump).unwrap();
+        assert_eq!(Position::unpack(&accounts[1].data.borrow()).unwrap().locked_by(), None);
+        let report = process_lock_position(&program_id, &accounts, lender_bump).unwrap_err();
+        assert_eq!((report.error, report.log.expected), (FriendtechError::FeatureDisabled.into(), FEATURE_LENDING));
+    }
+}
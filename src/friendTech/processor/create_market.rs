@@ -0,0 +1,109 @@
+//! `CreateMarket` and `CreateGroupMarket`.
+
+use super::accounts::{
+    check_not_winding_down, check_payer, check_signer, check_token_program, create_pda_account, create_vault,
+    load_config, load_stats,
+};
+use super::fees::check_fee_split;
+use crate::{
+    find_market_address, find_vault_address, AtAccount, ErrorReport, FeeRecipient, Market, Stats, MARKET_SEED,
+    MAX_GROUP_MEMBERS, NO_ACCOUNT,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+
+/// Creates a market; with `members`, a group market splitting its subject
+/// fees across them.
+pub(super) fn process_create_market(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    members: &[FeeRecipient],
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let payer = next_account_info(accounts_iter)?;
+    let subject = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+    let vault = next_account_info(accounts_iter)?;
+    let quote_mint = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let stats_account = next_account_info(accounts_iter)?;
+
+    check_payer(payer).at_account(0)?;
+    check_signer(subject).at_account(1)?;
+    check_token_program(token_program).at_account(5)?;
+    if members.len() > MAX_GROUP_MEMBERS {
+        return Err(ErrorReport::at(ProgramError::InvalidArgument, NO_ACCOUNT)
+            .values(MAX_GROUP_MEMBERS as u64, members.len() as u64));
+    }
+    check_fee_split(members)?;
+    let config = load_config(program_id, config_account).at_account(7)?;
+    check_not_winding_down(&config)?;
+    let mut stats = load_stats(program_id, stats_account).at_account(8)?;
+    let (market_key, bump) = find_market_address(program_id, subject.key, quote_mint.key);
+    if market_key != *market_account.key {
+        return Err(ErrorReport::at(ProgramError::InvalidSeeds, 2));
+    }
+    if market_account.owner == program_id {
+        return Err(ErrorReport::at(ProgramError::AccountAlreadyInitialized, 2));
+    }
+    let (vault_key, vault_bump) = find_vault_address(program_id, &market_key);
+    if vault_key != *vault.key {
+        return Err(ErrorReport::at(ProgramError::InvalidSeeds, 3));
+    }
+
+    create_vault(payer, vault, quote_mint, token_program, system_program_account, &market_key, vault_bump)?;
+
+    create_pda_account(
+        payer,
+        market_account,
+        system_program_account,
+        program_id,
+        Market::LEN,
+        &[MARKET_SEED, subject.key.as_ref(), quote_mint.key.as_ref(), &[bump]],
+    )?;
+    let mut group = [FeeRecipient::default(); MAX_GROUP_MEMBERS];
+    group[..members.len()].copy_from_slice(members);
+    let market = Market { group, ..Market::new(*subject.key, *quote_mint.key, vault_key, &config, bump) };
+    Market::pack(market, &mut market_account.data.borrow_mut()).at_account(2)?;
+
+    stats.total_markets = stats.total_markets.saturating_add(1);
+    Stats::pack(stats, &mut stats_account.data.borrow_mut()).at_account(8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_accounts;
+
+    #[test]
+    fn test_create_market_checks_signers_and_token_program() {
+        let program_id = Pubkey::new_unique();
+        let mut keys: Vec<Pubkey> = (0..9).map(|_| Pubkey::new_unique()).collect();
+        keys[5] = spl_token::id();
+        let mut lamports = [0u64; 9];
+        let mut data = vec![vec![]; 9];
+        let mut accounts = test_accounts(&keys, &mut lamports, &mut data, &program_id, true);
+
+        accounts[0].is_writable = false;
+        let report = process_create_market(&program_id, &accounts, &[]).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (ProgramError::InvalidArgument, 0));
+        accounts[0].is_writable = true;
+        accounts[1].is_signer = false;
+        let report = process_create_market(&program_id, &accounts, &[]).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (ProgramError::MissingRequiredSignature, 1));
+        accounts[1].is_signer = true;
+        // An uninitialized config account past the token program check.
+        let report = process_create_market(&program_id, &accounts, &[]).unwrap_err();
+        assert_eq!(report.log.account_index, 7);
+        // A group's weights must add up to all of the subject fees.
+        let members = [FeeRecipient { destination: Pubkey::new_unique(), weight_bps: 6_000 }; 2];
+        let report = process_create_market(&program_id, &accounts, &members).unwrap_err();
+        assert_eq!(report.error, crate::FriendtechError::InvalidFeeSplit.into());
+    }
+}
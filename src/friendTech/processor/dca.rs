@@ -0,0 +1,309 @@
+//! Dollar-cost-averaging schedules, executed by anyone once an interval
+//! has passed.
+
+use super::accounts::{
+    check_feature, check_payer, check_router, check_signer, check_token_program, check_vault, close_account,
+    create_pda_account, create_token_pda, load_config, load_market, load_stats,
+};
+use super::buy::{execute_buy, BuyAccounts};
+use super::candles::record_trade_candle;
+use crate::{
+    find_dca_escrow_address, find_dca_schedule_address, max_shares_for_budget, roll_volume_window, AccountType,
+    AtAccount, DcaSchedule, ErrorReport, FriendtechError, Stats, DCA_ESCROW_SEED, DCA_SEED, FEATURE_DCA, NO_ACCOUNT,
+};
+use borsh::BorshSerialize;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    program::{invoke, invoke_signed, set_return_data},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+use spl_token::{instruction as spl_token_instruction, state::Account as SplTokenAccount};
+
+pub(super) fn process_create_dca_schedule(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    market_key: Pubkey,
+    amount_per_interval: u64,
+    interval: i64,
+    total: u64,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let payer = next_account_info(accounts_iter)?;
+    let owner = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+    let schedule_account = next_account_info(accounts_iter)?;
+    let escrow = next_account_info(accounts_iter)?;
+    let token_account = next_account_info(accounts_iter)?;
+    let quote_mint = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+
+    check_payer(payer).at_account(0)?;
+    check_signer(owner).at_account(1)?;
+    check_token_program(token_program).at_account(7)?;
+    check_feature(&load_config(program_id, config_account).at_account(9)?, FEATURE_DCA)?;
+    if *market_account.key != market_key {
+        return Err(ErrorReport::at(FriendtechError::AccountMismatch, 2));
+    }
+    let market = load_market(program_id, market_account).at_account(2)?;
+    if market.quote_mint != *quote_mint.key {
+        return Err(ErrorReport::at(FriendtechError::AccountMismatch, 6));
+    }
+    if amount_per_interval == 0 || interval <= 0 || total < amount_per_interval {
+        return Err(ErrorReport::at(ProgramError::InvalidArgument, NO_ACCOUNT).values(amount_per_interval, total));
+    }
+
+    let (schedule_key, bump) = find_dca_schedule_address(program_id, &market_key, owner.key);
+    if schedule_key != *schedule_account.key {
+        return Err(ErrorReport::at(ProgramError::InvalidSeeds, 3));
+    }
+    if schedule_account.owner == program_id {
+        return Err(ErrorReport::at(ProgramError::AccountAlreadyInitialized, 3));
+    }
+    let (escrow_key, escrow_bump) = find_dca_escrow_address(program_id, &schedule_key);
+    if escrow_key != *escrow.key {
+        return Err(ErrorReport::at(ProgramError::InvalidSeeds, 4));
+    }
+
+    create_pda_account(
+        payer,
+        schedule_account,
+        system_program_account,
+        program_id,
+        DcaSchedule::LEN,
+        &[DCA_SEED, market_key.as_ref(), owner.key.as_ref(), &[bump]],
+    )?;
+    create_token_pda(
+        payer,
+        escrow,
+        quote_mint,
+        token_program,
+        system_program_account,
+        &schedule_key,
+        &[DCA_ESCROW_SEED, schedule_key.as_ref(), &[escrow_bump]],
+    )?;
+    let ix = spl_token_instruction::transfer(token_program.key, token_account.key, escrow.key, owner.key, &[], total)?;
+    invoke(&ix, &[token_account.clone(), escrow.clone(), owner.clone(), token_program.clone()])?;
+
+    let schedule = DcaSchedule {
+        account_type: AccountType::DcaSchedule,
+        owner: *owner.key,
+        market: market_key,
+        escrow: escrow_key,
+        amount_per_interval,
+        interval,
+        remaining: total,
+        next_execution_at: Clock::get()?.unix_timestamp,
+        bump,
+    };
+    DcaSchedule::pack(schedule, &mut schedule_account.data.borrow_mut()).at_account(3)
+}
+
+pub(super) fn process_execute_dca(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let keeper = next_account_info(accounts_iter)?;
+    let schedule_account = next_account_info(accounts_iter)?;
+    let escrow = next_account_info(accounts_iter)?;
+    let owner = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+    let position_account = next_account_info(accounts_iter)?;
+    let vault = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+    let stats_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let holding_account = next_account_info(accounts_iter)?;
+
+    check_payer(keeper).at_account(0)?;
+    check_token_program(token_program).at_account(7)?;
+    let mut schedule = load_dca_schedule(program_id, schedule_account).at_account(1)?;
+    if schedule.escrow != *escrow.key {
+        return Err(ErrorReport::at(FriendtechError::AccountMismatch, 2));
+    }
+    if schedule.owner != *owner.key {
+        return Err(ErrorReport::at(FriendtechError::AccountMismatch, 3));
+    }
+    if schedule.market != *market_account.key {
+        return Err(ErrorReport::at(FriendtechError::AccountMismatch, 4));
+    }
+    let mut market = load_market(program_id, market_account).at_account(4)?;
+    check_router(&market, accounts, 4)?;
+    check_vault(&market, vault).at_account(6)?;
+    let mut stats = load_stats(program_id, stats_account).at_account(9)?;
+    let config = load_config(program_id, config_account).at_account(10)?;
+    check_feature(&config, FEATURE_DCA)?;
+    let clock = Clock::get()?;
+    if clock.unix_timestamp < schedule.next_execution_at {
+        return Err(ErrorReport::at(FriendtechError::DcaNotDue, 1)
+            .values(schedule.next_execution_at as u64, clock.unix_timestamp as u64));
+    }
+    roll_volume_window(&mut market, clock.unix_timestamp);
+    let candles = if market.candles { Some((next_account_info(accounts_iter)?, 12)) } else { None };
+
+    let budget = schedule.amount_per_interval.min(schedule.remaining);
+    let amount = max_shares_for_budget(&config, &market, budget);
+    if amount == 0 {
+        return Err(ErrorReport::at(FriendtechError::InsufficientFunds, 2).values(budget, 0));
+    }
+    let seeds: &[&[u8]] = &[DCA_SEED, schedule.market.as_ref(), schedule.owner.as_ref(), &[schedule.bump]];
+    let trade = BuyAccounts {
+        payer: keeper,
+        buyer: owner,
+        authority: schedule_account,
+        authority_seeds: Some(seeds),
+        token_account: (escrow, 2),
+        token_program,
+        system_program: system_program_account,
+        market: (market_account, 4),
+        position: (position_account, 5),
+        vault,
+        holding: (holding_account, 11),
+        interface_fee: None,
+    };
+    let settlement = execute_buy(program_id, &trade, &config, market, amount, &mut stats, &clock, None)?;
+    record_trade_candle(program_id, candles, market_account.key, amount, settlement.gross_price, clock.unix_timestamp)?;
+
+    schedule.remaining -= settlement.net_amount;
+    schedule.next_execution_at = clock.unix_timestamp.saturating_add(schedule.interval);
+    DcaSchedule::pack(schedule, &mut schedule_account.data.borrow_mut()).at_account(1)?;
+    Stats::pack(stats, &mut stats_account.data.borrow_mut()).at_account(9)?;
+    set_return_data(&settlement.try_to_vec()?);
+    Ok(())
+}
+
+pub(super) fn process_cancel_dca_schedule(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let schedule_account = next_account_info(accounts_iter)?;
+    let escrow = next_account_info(accounts_iter)?;
+    let destination = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+
+    check_signer(owner).at_account(0)?;
+    check_token_program(token_program).at_account(4)?;
+    let schedule = load_dca_schedule(program_id, schedule_account).at_account(1)?;
+    if schedule.owner != *owner.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    if schedule.escrow != *escrow.key {
+        return Err(ErrorReport::at(FriendtechError::AccountMismatch, 2));
+    }
+
+    let seeds: &[&[u8]] = &[DCA_SEED, schedule.market.as_ref(), schedule.owner.as_ref(), &[schedule.bump]];
+    let balance = SplTokenAccount::unpack(&escrow.data.borrow()).at_account(2)?.amount;
+    let ix = spl_token_instruction::transfer(
+        token_program.key,
+        escrow.key,
+        destination.key,
+        schedule_account.key,
+        &[],
+        balance,
+    )?;
+    invoke_signed(
+        &ix,
+        &[escrow.clone(), destination.clone(), schedule_account.clone(), token_program.clone()],
+        &[seeds],
+    )?;
+    let ix = spl_token_instruction::close_account(token_program.key, escrow.key, owner.key, schedule_account.key, &[])?;
+    invoke_signed(&ix, &[escrow.clone(), owner.clone(), schedule_account.clone(), token_program.clone()], &[seeds])?;
+    close_account(schedule_account, owner).at_account(1)?;
+    Ok(())
+}
+
+/// Loads a DCA schedule after checking its owner and address.
+pub(super) fn load_dca_schedule(
+    program_id: &Pubkey,
+    schedule_account: &AccountInfo,
+) -> Result<DcaSchedule, ProgramError> {
+    if schedule_account.owner != program_id {
+        return Err(FriendtechError::IncorrectOwner.into());
+    }
+    let schedule = DcaSchedule::unpack(&schedule_account.data.borrow())?;
+    let schedule_key = Pubkey::create_program_address(
+        &[DCA_SEED, schedule.market.as_ref(), schedule.owner.as_ref(), &[schedule.bump]],
+        program_id,
+    )?;
+    if schedule_key != *schedule_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(schedule)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{install_test_runtime, sample_config, sample_market, test_accounts, TEST_NOW};
+    use crate::{find_config_address, Config, Market};
+    use solana_program::{program_option::COption, system_program};
+    use spl_token::state::{AccountState, Mint as SplMint};
+
+    #[test]
+    fn test_create_dca_schedule() {
+        install_test_runtime();
+        let program_id = Pubkey::new_unique();
+        let (owner, market_key, token_key) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let market = sample_market(Pubkey::new_unique());
+        let (config_key, bump) = find_config_address(&program_id);
+        let schedule_key = find_dca_schedule_address(&program_id, &market_key, &owner).0;
+        let keys = [
+            owner,
+            owner,
+            market_key,
+            schedule_key,
+            find_dca_escrow_address(&program_id, &schedule_key).0,
+            token_key,
+            market.quote_mint,
+            spl_token::id(),
+            system_program::id(),
+            config_key,
+        ];
+        let mut lamports = [1_000_000_000, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let mut data = vec![vec![]; 10];
+        data[2] = vec![0u8; Market::LEN];
+        Market::pack(market.clone(), &mut data[2]).unwrap();
+        data[3] = vec![0u8; DcaSchedule::LEN];
+        data[4] = vec![0u8; SplTokenAccount::LEN];
+        data[5] = vec![0u8; SplTokenAccount::LEN];
+        let funds = SplTokenAccount {
+            mint: market.quote_mint,
+            owner,
+            amount: 10_000,
+            state: AccountState::Initialized,
+            ..SplTokenAccount::default()
+        };
+        SplTokenAccount::pack(funds, &mut data[5]).unwrap();
+        data[6] = vec![0u8; SplMint::LEN];
+        let mint = SplMint {
+            mint_authority: COption::None,
+            supply: 10_000,
+            decimals: 6,
+            is_initialized: true,
+            ..SplMint::default()
+        };
+        SplMint::pack(mint, &mut data[6]).unwrap();
+        data[9] = vec![0u8; Config::LEN];
+        Config::pack(Config { bump, ..sample_config(Pubkey::new_unique()) }, &mut data[9]).unwrap();
+        let (token, system) = (spl_token::id(), system_program::id());
+        let mut accounts = test_accounts(&keys, &mut lamports, &mut data, &token, true);
+        for index in [0, 1, 3, 8] {
+            accounts[index].owner = &system;
+        }
+        for index in [2, 9] {
+            accounts[index].owner = &program_id;
+        }
+
+        // The whole budget moves into the schedule's escrow up front.
+        process_create_dca_schedule(&program_id, &accounts, market_key, 1_000, 3_600, 6_000).unwrap();
+        accounts[3].owner = &program_id;
+        let schedule = load_dca_schedule(&program_id, &accounts[3]).unwrap();
+        assert_eq!((schedule.owner, schedule.remaining, schedule.next_execution_at), (owner, 6_000, TEST_NOW));
+        let escrow = SplTokenAccount::unpack(&accounts[4].data.borrow()).unwrap();
+        assert_eq!((escrow.owner, escrow.amount), (schedule_key, 6_000));
+        assert_eq!(SplTokenAccount::unpack(&accounts[5].data.borrow()).unwrap().amount, 4_000);
+    }
+}
@@ -0,0 +1,186 @@
+//! Devnet-only faucet of quote tokens and fast-forwarding of market time, so
+//! QA can exercise time-dependent logic on test clusters without waiting.
+
+use super::accounts::{
+    check_payer, check_position_address, check_signer, check_token_program, create_pda_account, load_config,
+    load_market,
+};
+use crate::{
+    find_faucet_mint_address, AtAccount, ErrorReport, FriendtechError, Market, Position, CONFIG_SEED, FAUCET_MINT_SEED,
+    NO_ACCOUNT, VOLUME_BUCKETS, VOLUME_BUCKET_SECS,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use spl_token::{instruction as spl_token_instruction, state::Mint as SplMint};
+
+pub(super) fn process_create_faucet_mint(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    decimals: u8,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let payer = next_account_info(accounts_iter)?;
+    let admin = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let mint_account = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+
+    check_payer(payer).at_account(0)?;
+    check_signer(admin).at_account(1)?;
+    let config = load_config(program_id, config_account).at_account(2)?;
+    if config.admin != *admin.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 1));
+    }
+    check_token_program(token_program).at_account(4)?;
+    let (mint_key, bump) = find_faucet_mint_address(program_id);
+    if mint_key != *mint_account.key {
+        return Err(ErrorReport::at(ProgramError::InvalidSeeds, 3));
+    }
+
+    create_pda_account(
+        payer,
+        mint_account,
+        system_program_account,
+        &spl_token::id(),
+        SplMint::LEN,
+        &[FAUCET_MINT_SEED, &[bump]],
+    )?;
+    let ix = spl_token_instruction::initialize_mint2(
+        token_program.key,
+        mint_account.key,
+        config_account.key,
+        None,
+        decimals,
+    )?;
+    invoke(&ix, &[mint_account.clone(), token_program.clone()])?;
+    Ok(())
+}
+
+pub(super) fn process_faucet_mint(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let config_account = next_account_info(accounts_iter)?;
+    let mint_account = next_account_info(accounts_iter)?;
+    let token_account = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+
+    let config = load_config(program_id, config_account).at_account(0)?;
+    // The config PDA is the rewards mint's authority too, so only the
+    // faucet mint may be minted here.
+    if *mint_account.key != find_faucet_mint_address(program_id).0 {
+        return Err(ErrorReport::at(ProgramError::InvalidSeeds, 1));
+    }
+    check_token_program(token_program).at_account(3)?;
+    let ix = spl_token_instruction::mint_to(
+        token_program.key,
+        mint_account.key,
+        token_account.key,
+        config_account.key,
+        &[],
+        amount,
+    )?;
+    invoke_signed(
+        &ix,
+        &[mint_account.clone(), token_account.clone(), config_account.clone(), token_program.clone()],
+        &[&[CONFIG_SEED, &[config.bump]]],
+    )?;
+    Ok(())
+}
+
+pub(super) fn process_fast_forward_market(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    seconds: i64,
+    epochs: u64,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let admin = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+
+    check_signer(admin).at_account(0)?;
+    let config = load_config(program_id, config_account).at_account(1)?;
+    if config.admin != *admin.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    if seconds < 0 {
+        return Err(ErrorReport::at(ProgramError::InvalidArgument, NO_ACCOUNT).values(0, seconds as u64));
+    }
+    let mut market = load_market(program_id, market_account).at_account(2)?;
+    fast_forward_market(&mut market, seconds, epochs);
+    Market::pack(market, &mut market_account.data.borrow_mut()).at_account(2)?;
+
+    for (i, position_account) in accounts_iter.enumerate() {
+        let index = (i + 3) as u8;
+        if position_account.owner != program_id {
+            return Err(ErrorReport::at(FriendtechError::IncorrectOwner, index));
+        }
+        let mut position = Position::unpack(&position_account.data.borrow()).at_account(index)?;
+        check_position_address(program_id, market_account.key, &position, position_account).at_account(index)?;
+        position.set_acquired_at(position.acquired_at().saturating_sub(seconds));
+        position.set_tax_basis_at(position.tax_basis_at().saturating_sub(seconds));
+        Position::pack(position, &mut position_account.data.borrow_mut()).at_account(index)?;
+    }
+    Ok(())
+}
+
+/// Moves `market`'s timestamps `seconds` and its epoch `epochs` back.
+fn fast_forward_market(market: &mut Market, seconds: i64, epochs: u64) {
+    for timestamp in [
+        &mut market.dump_window_start,
+        &mut market.opens_at,
+        &mut market.rewards_updated_at,
+        &mut market.succession_after,
+        &mut market.fee_streamed_at,
+    ] {
+        *timestamp = timestamp.saturating_sub(seconds);
+    }
+    market.last_trade_epoch = market.last_trade_epoch.saturating_sub(epochs);
+    // Buckets are indexed by hour, so moving the newest hour back moves
+    // every bucket with it.
+    let hours = seconds / VOLUME_BUCKET_SECS;
+    market.volume_hour = market.volume_hour.saturating_sub(hours);
+    market.volume_buckets.rotate_left((hours % VOLUME_BUCKETS as i64) as usize);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::sample_market;
+    use crate::{record_volume, rolling_volume};
+
+    #[test]
+    fn test_fast_forward_market() {
+        let mut market = sample_market(Pubkey::new_unique());
+        let hour = 1_000 * VOLUME_BUCKET_SECS;
+        record_volume(&mut market, 100, hour);
+        record_volume(&mut market, 30, hour + 5 * VOLUME_BUCKET_SECS);
+        (market.dump_window_start, market.last_trade_epoch) = (hour, 10);
+
+        // Five hours forward, the window looks as it would five hours later.
+        let mut forwarded = market.clone();
+        fast_forward_market(&mut forwarded, 5 * VOLUME_BUCKET_SECS, 2);
+        for later in [0, 5, 18, 19, 23, 24] {
+            let now = hour + 5 * VOLUME_BUCKET_SECS + later * VOLUME_BUCKET_SECS;
+            assert_eq!(rolling_volume(&forwarded, now), rolling_volume(&market, now + 5 * VOLUME_BUCKET_SECS));
+        }
+        record_volume(&mut forwarded, 1, hour + 5 * VOLUME_BUCKET_SECS);
+        assert_eq!(rolling_volume(&forwarded, hour + 5 * VOLUME_BUCKET_SECS), 131);
+        assert_eq!(forwarded.dump_window_start, hour - 5 * VOLUME_BUCKET_SECS);
+        assert_eq!(forwarded.last_trade_epoch, 8);
+
+        // A day forward, the whole window has passed.
+        fast_forward_market(&mut market, 24 * VOLUME_BUCKET_SECS + 1, 20);
+        assert_eq!(rolling_volume(&market, hour + 5 * VOLUME_BUCKET_SECS), 0);
+        assert_eq!(market.last_trade_epoch, 0);
+    }
+}
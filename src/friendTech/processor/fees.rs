@@ -0,0 +1,532 @@
+//! Fee claims, subject fee streams and the per-mint insurance vaults funded
+//! from protocol fees.
+
+use super::accounts::{
+    check_insurance_fee_bps, check_payer, check_signer, check_token_program, check_vault, create_token_pda,
+    load_config, load_market, transfer_from_vault,
+};
+use super::log_event;
+use crate::{
+    fee_recipients, find_insurance_vault_address, group_members, insurance_share, split_group_fees,
+    split_protocol_fees, streamed_subject_fees, AtAccount, Config, ErrorReport, FeeRecipient, FriendtechError,
+    InsurancePayoutEvent, Market, BPS_DENOMINATOR, CONFIG_SEED, INSURANCE_LOG_TAG, INSURANCE_SEED, MAX_FEE_RECIPIENTS,
+    NO_ACCOUNT,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    program::invoke_signed,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+use spl_token::{instruction as spl_token_instruction, state::Account as SplTokenAccount};
+
+pub(super) fn process_claim_subject_fees(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let subject = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+    let vault = next_account_info(accounts_iter)?;
+    let destination = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+
+    check_signer(subject).at_account(0)?;
+    let mut market = load_market(program_id, market_account).at_account(1)?;
+    if market.authority != *subject.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    if market.fee_beneficiary.is_some() {
+        return Err(ErrorReport::at(FriendtechError::FeesStreaming, 1));
+    }
+    check_vault(&market, vault).at_account(2)?;
+    check_token_program(token_program).at_account(4)?;
+
+    let amount = market.subject_fees;
+    let members = group_members(&market);
+    if members.is_empty() {
+        transfer_from_vault(&market, market_account, vault, destination, token_program, amount)?;
+    } else {
+        for (i, (member, share)) in members.iter().zip(split_group_fees(&market, amount)).enumerate() {
+            let (index, member_account) =
+                if i == 0 { (3, destination) } else { (4 + i as u8, next_account_info(accounts_iter)?) };
+            if *member_account.key != member.destination {
+                return Err(ErrorReport::at(FriendtechError::AccountMismatch, index));
+            }
+            transfer_from_vault(&market, market_account, vault, member_account, token_program, share)?;
+        }
+    }
+    market.subject_fees = 0;
+    Market::pack(market, &mut market_account.data.borrow_mut()).at_account(1)
+}
+
+pub(super) fn process_set_fee_stream(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    beneficiary: Option<Pubkey>,
+    rate_per_second: u64,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let subject = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+
+    check_signer(subject).at_account(0)?;
+    let mut market = load_market(program_id, market_account).at_account(1)?;
+    if market.authority != *subject.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    // A stream at rate zero would lock the fees in until it is replaced.
+    if beneficiary.is_some() && rate_per_second == 0 {
+        return Err(ErrorReport::at(ProgramError::InvalidArgument, NO_ACCOUNT));
+    }
+    // A group market's fees are split across its members, which a single
+    // beneficiary would bypass.
+    if beneficiary.is_some() && !group_members(&market).is_empty() {
+        return Err(ErrorReport::at(FriendtechError::InvalidFeeSplit, 1));
+    }
+    let now = Clock::get()?.unix_timestamp;
+    if let Some(current) = market.fee_beneficiary {
+        let vault = next_account_info(accounts_iter)?;
+        let current_account = next_account_info(accounts_iter)?;
+        let token_program = next_account_info(accounts_iter)?;
+        check_vault(&market, vault).at_account(2)?;
+        if *current_account.key != current {
+            return Err(ErrorReport::at(FriendtechError::AccountMismatch, 3));
+        }
+        check_token_program(token_program).at_account(4)?;
+        pay_streamed_fees(&mut market, market_account, vault, current_account, token_program, now)?;
+    }
+    market.fee_beneficiary = beneficiary;
+    market.fee_stream_rate = rate_per_second;
+    market.fee_streamed_at = now;
+    Market::pack(market, &mut market_account.data.borrow_mut()).at_account(1)
+}
+
+pub(super) fn process_claim_streamed_fees(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let market_account = next_account_info(accounts_iter)?;
+    let vault = next_account_info(accounts_iter)?;
+    let beneficiary = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+
+    let mut market = load_market(program_id, market_account).at_account(0)?;
+    check_vault(&market, vault).at_account(1)?;
+    if market.fee_beneficiary != Some(*beneficiary.key) {
+        return Err(ErrorReport::at(FriendtechError::AccountMismatch, 2));
+    }
+    check_token_program(token_program).at_account(3)?;
+
+    pay_streamed_fees(&mut market, market_account, vault, beneficiary, token_program, Clock::get()?.unix_timestamp)?;
+    Market::pack(market, &mut market_account.data.borrow_mut()).at_account(0)
+}
+
+/// Pays the fees streamed by unix timestamp `now` from the market's vault to
+/// its beneficiary and restarts the stream from `now`.
+fn pay_streamed_fees<'a>(
+    market: &mut Market,
+    market_account: &AccountInfo<'a>,
+    vault: &AccountInfo<'a>,
+    beneficiary: &AccountInfo<'a>,
+    token_program: &AccountInfo<'a>,
+    now: i64,
+) -> Result<(), ErrorReport> {
+    let amount = streamed_subject_fees(market, now);
+    transfer_from_vault(market, market_account, vault, beneficiary, token_program, amount)?;
+    market.subject_fees -= amount;
+    market.fee_streamed_at = now;
+    Ok(())
+}
+
+pub(super) fn process_claim_protocol_fees(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let admin = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+    let vault = next_account_info(accounts_iter)?;
+    let destination = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+
+    check_signer(admin).at_account(0)?;
+    let config = load_config(program_id, config_account).at_account(1)?;
+    if config.admin != *admin.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    let mut market = load_market(program_id, market_account).at_account(2)?;
+    check_vault(&market, vault).at_account(3)?;
+    check_token_program(token_program).at_account(5)?;
+
+    // With insurance off, the insurance vault is left out and the remaining
+    // recipients follow the token program.
+    let insurance = insurance_share(&config, market.protocol_fees);
+    let remaining_at = if config.insurance_fee_bps == 0 {
+        6
+    } else {
+        let insurance_vault = next_account_info(accounts_iter)?;
+        check_insurance_vault(program_id, &market.quote_mint, insurance_vault).at_account(6)?;
+        transfer_from_vault(&market, market_account, vault, insurance_vault, token_program, insurance)?;
+        7
+    };
+    let amount = market.protocol_fees - insurance;
+    let recipients = fee_recipients(&config);
+    if recipients.is_empty() {
+        transfer_from_vault(&market, market_account, vault, destination, token_program, amount)?;
+    } else {
+        for (i, (recipient, share)) in recipients.iter().zip(split_protocol_fees(&config, amount)).enumerate() {
+            let (index, recipient_account) =
+                if i == 0 { (4, destination) } else { (remaining_at + i as u8 - 1, next_account_info(accounts_iter)?) };
+            if *recipient_account.key != recipient.destination {
+                return Err(ErrorReport::at(FriendtechError::AccountMismatch, index));
+            }
+            transfer_from_vault(&market, market_account, vault, recipient_account, token_program, share)?;
+        }
+    }
+    market.protocol_fees = 0;
+    Market::pack(market, &mut market_account.data.borrow_mut()).at_account(2)
+}
+
+pub(super) fn process_create_insurance_vault(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let payer = next_account_info(accounts_iter)?;
+    let insurance_vault = next_account_info(accounts_iter)?;
+    let quote_mint = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+
+    check_payer(payer).at_account(0)?;
+    check_token_program(token_program).at_account(3)?;
+    load_config(program_id, config_account).at_account(5)?;
+    let (vault_key, vault_bump) = find_insurance_vault_address(program_id, quote_mint.key);
+    if vault_key != *insurance_vault.key {
+        return Err(ErrorReport::at(ProgramError::InvalidSeeds, 1));
+    }
+    create_token_pda(
+        payer,
+        insurance_vault,
+        quote_mint,
+        token_program,
+        system_program_account,
+        config_account.key,
+        &[INSURANCE_SEED, quote_mint.key.as_ref(), &[vault_bump]],
+    )?;
+    Ok(())
+}
+
+pub(super) fn process_set_insurance_fee_bps(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    insurance_fee_bps: u16,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let admin = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+
+    check_signer(admin).at_account(0)?;
+    let mut config = load_config(program_id, config_account).at_account(1)?;
+    if config.admin != *admin.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    check_insurance_fee_bps(insurance_fee_bps)?;
+    config.insurance_fee_bps = insurance_fee_bps;
+    Config::pack(config, &mut config_account.data.borrow_mut()).at_account(1)
+}
+
+pub(super) fn process_set_fee_split(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    recipients: Vec<FeeRecipient>,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let admin = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+
+    check_signer(admin).at_account(0)?;
+    let mut config = load_config(program_id, config_account).at_account(1)?;
+    if config.admin != *admin.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    if recipients.len() > MAX_FEE_RECIPIENTS {
+        return Err(ErrorReport::at(ProgramError::InvalidArgument, NO_ACCOUNT)
+            .values(MAX_FEE_RECIPIENTS as u64, recipients.len() as u64));
+    }
+    check_fee_split(&recipients)?;
+    config.fee_split = [FeeRecipient::default(); MAX_FEE_RECIPIENTS];
+    config.fee_split[..recipients.len()].copy_from_slice(&recipients);
+    Config::pack(config, &mut config_account.data.borrow_mut()).at_account(1)
+}
+
+/// Checks that a non-empty fee split has no unused slots and weights summing
+/// to 100%.
+pub(super) fn check_fee_split(recipients: &[FeeRecipient]) -> Result<(), ErrorReport> {
+    if recipients.is_empty() {
+        return Ok(());
+    }
+    let total: u64 = recipients.iter().map(|recipient| u64::from(recipient.weight_bps)).sum();
+    if recipients.iter().any(|recipient| recipient.weight_bps == 0) || total != BPS_DENOMINATOR {
+        return Err(ErrorReport::at(FriendtechError::InvalidFeeSplit, NO_ACCOUNT).values(BPS_DENOMINATOR, total));
+    }
+    Ok(())
+}
+
+pub(super) fn process_compensate_from_insurance(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let admin = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+    let insurance_vault = next_account_info(accounts_iter)?;
+    let destination = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+
+    check_signer(admin).at_account(0)?;
+    let config = load_config(program_id, config_account).at_account(1)?;
+    if config.admin != *admin.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    let market = load_market(program_id, market_account).at_account(2)?;
+    check_insurance_vault(program_id, &market.quote_mint, insurance_vault).at_account(3)?;
+    check_token_program(token_program).at_account(5)?;
+    let balance = SplTokenAccount::unpack(&insurance_vault.data.borrow()).at_account(3)?.amount;
+    if amount == 0 || amount > balance {
+        return Err(ErrorReport::at(FriendtechError::InsufficientFunds, 3).values(amount, balance));
+    }
+
+    let ix = spl_token_instruction::transfer(
+        token_program.key,
+        insurance_vault.key,
+        destination.key,
+        config_account.key,
+        &[],
+        amount,
+    )?;
+    invoke_signed(
+        &ix,
+        &[insurance_vault.clone(), destination.clone(), config_account.clone(), token_program.clone()],
+        &[&[CONFIG_SEED, &[config.bump]]],
+    )?;
+    let event = InsurancePayoutEvent { market: *market_account.key, destination: *destination.key, amount };
+    log_event(INSURANCE_LOG_TAG, &event)?;
+    Ok(())
+}
+
+/// Checks that `insurance_vault` is the insurance vault PDA of `quote_mint`.
+pub(super) fn check_insurance_vault(
+    program_id: &Pubkey,
+    quote_mint: &Pubkey,
+    insurance_vault: &AccountInfo,
+) -> Result<(), ProgramError> {
+    let (vault_key, _) = find_insurance_vault_address(program_id, quote_mint);
+    if vault_key != *insurance_vault.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{install_test_runtime, sample_config, sample_market, test_accounts, TEST_NOW};
+    use spl_token::state::AccountState;
+
+    #[test]
+    fn test_claim_subject_fees_checks_subject_and_vault() {
+        let program_id = Pubkey::new_unique();
+        let subject = Pubkey::new_unique();
+        let market = sample_market(subject);
+        let keys = [subject, Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), spl_token::id()];
+        let mut lamports = [0u64; 5];
+        let mut data = vec![vec![]; 5];
+        data[1] = vec![0u8; Market::LEN];
+        Market::pack(market.clone(), &mut data[1]).unwrap();
+        let mut accounts = test_accounts(&keys, &mut lamports, &mut data, &program_id, true);
+
+        let report = process_claim_subject_fees(&program_id, &accounts).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::AccountMismatch.into(), 2));
+        let impostor = Pubkey::new_unique();
+        accounts[0].key = &impostor;
+        let report = process_claim_subject_fees(&program_id, &accounts).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::Unauthorized.into(), 0));
+    }
+
+    #[test]
+    fn test_streamed_fees_replace_lump_sum_claims() {
+        let program_id = Pubkey::new_unique();
+        let subject = Pubkey::new_unique();
+        let beneficiary = Pubkey::new_unique();
+        let market = Market { fee_beneficiary: Some(beneficiary), fee_stream_rate: 1, ..sample_market(subject) };
+        let keys = [subject, Pubkey::new_unique(), market.vault, Pubkey::new_unique(), spl_token::id()];
+        let mut lamports = [0u64; 5];
+        let mut data = vec![vec![]; 5];
+        data[1] = vec![0u8; Market::LEN];
+        Market::pack(market, &mut data[1]).unwrap();
+        let accounts = test_accounts(&keys, &mut lamports, &mut data, &program_id, true);
+
+        let report = process_claim_subject_fees(&program_id, &accounts).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::FeesStreaming.into(), 1));
+        // Streamed fees only go to the beneficiary.
+        let report = process_claim_streamed_fees(&program_id, &accounts[1..]).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::AccountMismatch.into(), 2));
+    }
+
+    #[test]
+    fn test_ending_stream_pays_beneficiary() {
+        install_test_runtime();
+        let program_id = Pubkey::new_unique();
+        let (subject, market_key, beneficiary) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let market = Market {
+            fee_beneficiary: Some(beneficiary),
+            fee_stream_rate: 5,
+            fee_streamed_at: TEST_NOW - 10,
+            subject_fees: 100,
+            ..sample_market(subject)
+        };
+        let keys = [subject, market_key, market.vault, beneficiary, spl_token::id()];
+        let mut lamports = [0u64; 5];
+        let mut data = vec![vec![]; 5];
+        data[1] = vec![0u8; Market::LEN];
+        Market::pack(market.clone(), &mut data[1]).unwrap();
+        for (index, owner, amount) in [(2, market_key, 100), (3, Pubkey::new_unique(), 0)] {
+            data[index] = vec![0u8; SplTokenAccount::LEN];
+            let token = SplTokenAccount {
+                mint: market.quote_mint,
+                owner,
+                amount,
+                state: AccountState::Initialized,
+                ..SplTokenAccount::default()
+            };
+            SplTokenAccount::pack(token, &mut data[index]).unwrap();
+        }
+        let token = spl_token::id();
+        let mut accounts = test_accounts(&keys, &mut lamports, &mut data, &token, true);
+        accounts[1].owner = &program_id;
+
+        // The stream cannot end without the beneficiary's account to pay.
+        let report = process_set_fee_stream(&program_id, &accounts[..2], None, 0).unwrap_err();
+        assert_eq!(report.error, ProgramError::NotEnoughAccountKeys);
+
+        // Ending the stream pays the 50 streamed in the last 10 seconds, and
+        // leaves the rest to lump-sum claims.
+        process_set_fee_stream(&program_id, &accounts, None, 0).unwrap();
+        let market = Market::unpack(&accounts[1].data.borrow()).unwrap();
+        assert_eq!((market.fee_beneficiary, market.subject_fees, market.fee_streamed_at), (None, 50, TEST_NOW));
+        assert_eq!(SplTokenAccount::unpack(&accounts[3].data.borrow()).unwrap().amount, 50);
+        assert_eq!(SplTokenAccount::unpack(&accounts[2].data.borrow()).unwrap().amount, 50);
+    }
+
+    #[test]
+    fn test_claim_protocol_fees_without_insurance() {
+        install_test_runtime();
+        let program_id = Pubkey::new_unique();
+        let (admin, market_key, destination) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let (config_key, bump) = crate::find_config_address(&program_id);
+        let market = Market { protocol_fees: 100, ..sample_market(Pubkey::new_unique()) };
+        let keys = [admin, config_key, market_key, market.vault, destination, spl_token::id()];
+        let mut lamports = [0u64; 6];
+        let mut data = vec![vec![]; 6];
+        data[1] = vec![0u8; Config::LEN];
+        Config::pack(Config { insurance_fee_bps: 0, bump, ..sample_config(admin) }, &mut data[1]).unwrap();
+        data[2] = vec![0u8; Market::LEN];
+        Market::pack(market.clone(), &mut data[2]).unwrap();
+        for (index, owner, amount) in [(3, market_key, 100), (4, Pubkey::new_unique(), 0)] {
+            data[index] = vec![0u8; SplTokenAccount::LEN];
+            let token = SplTokenAccount {
+                mint: market.quote_mint,
+                owner,
+                amount,
+                state: AccountState::Initialized,
+                ..SplTokenAccount::default()
+            };
+            SplTokenAccount::pack(token, &mut data[index]).unwrap();
+        }
+        let token = spl_token::id();
+        let mut accounts = test_accounts(&keys, &mut lamports, &mut data, &token, true);
+        accounts[1].owner = &program_id;
+        accounts[2].owner = &program_id;
+
+        // With insurance off, no insurance vault is needed and all the fees
+        // go to the destination.
+        process_claim_protocol_fees(&program_id, &accounts).unwrap();
+        assert_eq!(Market::unpack(&accounts[2].data.borrow()).unwrap().protocol_fees, 0);
+        assert_eq!(SplTokenAccount::unpack(&accounts[4].data.borrow()).unwrap().amount, 100);
+    }
+
+    #[test]
+    fn test_claim_group_fees_checks_members() {
+        let program_id = Pubkey::new_unique();
+        let subject = Pubkey::new_unique();
+        let mut market = sample_market(subject);
+        let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+        market.group[0] = FeeRecipient { destination: first, weight_bps: 7_000 };
+        market.group[1] = FeeRecipient { destination: second, weight_bps: 3_000 };
+        let keys = [subject, Pubkey::new_unique(), market.vault, Pubkey::new_unique(), spl_token::id(), second];
+        let mut lamports = [0u64; 6];
+        let mut data = vec![vec![]; 6];
+        data[1] = vec![0u8; Market::LEN];
+        Market::pack(market, &mut data[1]).unwrap();
+        let mut accounts = test_accounts(&keys, &mut lamports, &mut data, &program_id, true);
+
+        // The first member's account stands in for the destination.
+        let report = process_claim_subject_fees(&program_id, &accounts).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::AccountMismatch.into(), 3));
+        accounts[3].key = &first;
+        // With the first member's account, the claim moves on to paying its share.
+        let report = process_claim_subject_fees(&program_id, &accounts).unwrap_err();
+        assert_eq!(report.error, ProgramError::InvalidAccountData);
+    }
+
+    #[test]
+    fn test_group_market_cannot_stream_fees() {
+        let program_id = Pubkey::new_unique();
+        let subject = Pubkey::new_unique();
+        let mut market = sample_market(subject);
+        market.group[0] = FeeRecipient { destination: Pubkey::new_unique(), weight_bps: 5_000 };
+        market.group[1] = FeeRecipient { destination: Pubkey::new_unique(), weight_bps: 5_000 };
+        let market_key = Pubkey::new_unique();
+        let (mut subject_lamports, mut market_lamports) = (0, 0);
+        let (mut subject_data, mut market_data) = (vec![], vec![0u8; Market::LEN]);
+        Market::pack(market, &mut market_data).unwrap();
+        let accounts = [
+            AccountInfo::new(&subject, true, false, &mut subject_lamports, &mut subject_data, &program_id, false, 0),
+            AccountInfo::new(&market_key, false, true, &mut market_lamports, &mut market_data, &program_id, false, 0),
+        ];
+
+        // The subject cannot stream the members' fees to themselves.
+        let report = process_set_fee_stream(&program_id, &accounts, Some(subject), 1_000).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::InvalidFeeSplit.into(), 1));
+    }
+
+    #[test]
+    fn test_set_fee_split() {
+        let program_id = Pubkey::new_unique();
+        let admin = Pubkey::new_unique();
+        let (config_key, bump) = crate::find_config_address(&program_id);
+        let (mut admin_lamports, mut config_lamports) = (0, 0);
+        let (mut admin_data, mut config_data) = (vec![], vec![0u8; Config::LEN]);
+        Config::pack(Config { bump, ..sample_config(admin) }, &mut config_data).unwrap();
+        let accounts = [
+            AccountInfo::new(&admin, true, false, &mut admin_lamports, &mut admin_data, &program_id, false, 0),
+            AccountInfo::new(&config_key, false, true, &mut config_lamports, &mut config_data, &program_id, false, 0),
+        ];
+        let recipient = |weight_bps| FeeRecipient { destination: Pubkey::new_unique(), weight_bps };
+
+        let split = vec![recipient(6_000), recipient(3_000), recipient(1_000)];
+        process_set_fee_split(&program_id, &accounts, split.clone()).unwrap();
+        let config = Config::unpack(&accounts[1].data.borrow()).unwrap();
+        assert_eq!(fee_recipients(&config), &split[..]);
+
+        let report =
+            process_set_fee_split(&program_id, &accounts, vec![recipient(6_000), recipient(3_000)]).unwrap_err();
+        assert_eq!((report.error, report.log.actual), (FriendtechError::InvalidFeeSplit.into(), 9_000));
+        let report = process_set_fee_split(&program_id, &accounts, vec![recipient(10_000), recipient(0)]).unwrap_err();
+        assert_eq!(report.error, FriendtechError::InvalidFeeSplit.into());
+        let report =
+            process_set_fee_split(&program_id, &accounts, vec![recipient(2_000); MAX_FEE_RECIPIENTS + 1]).unwrap_err();
+        assert_eq!(report.error, ProgramError::InvalidArgument);
+
+        process_set_fee_split(&program_id, &accounts, vec![]).unwrap();
+        assert!(fee_recipients(&Config::unpack(&accounts[1].data.borrow()).unwrap()).is_empty());
+    }
+}
@@ -0,0 +1,265 @@
+//! Subject parameter proposals, holder vetoes and their execution.
+
+use super::accounts::{
+    check_fee_bps, check_payer, check_sell_tax, check_signer, create_pda_account, load_config, load_market,
+    load_position,
+};
+use crate::{
+    find_proposal_address, find_veto_address, AccountType, AtAccount, ErrorReport, FriendtechError, Market, Position,
+    Proposal, ProposalStatus, VetoRecord, PROPOSAL_SEED, VETO_SEED,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+pub(super) fn process_propose_parameters(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    subject_fee_bps: u16,
+    sell_tax_bps: u16,
+    sell_tax_period: i64,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let payer = next_account_info(accounts_iter)?;
+    let subject = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+    let proposal_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+
+    check_payer(payer).at_account(0)?;
+    check_signer(subject).at_account(1)?;
+    let market = load_market(program_id, market_account).at_account(2)?;
+    if market.authority != *subject.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 1));
+    }
+    let config = load_config(program_id, config_account).at_account(4)?;
+    check_fee_bps(subject_fee_bps)?;
+    check_sell_tax(sell_tax_bps, sell_tax_period)?;
+
+    let (proposal_key, bump) = find_proposal_address(program_id, market_account.key);
+    if proposal_key != *proposal_account.key {
+        return Err(ErrorReport::at(ProgramError::InvalidSeeds, 3));
+    }
+    let id = if proposal_account.owner == program_id {
+        let previous = Proposal::unpack(&proposal_account.data.borrow()).at_account(3)?;
+        previous.id.checked_add(1).ok_or(ProgramError::ArithmeticOverflow)?
+    } else {
+        create_pda_account(
+            payer,
+            proposal_account,
+            system_program_account,
+            program_id,
+            Proposal::LEN,
+            &[PROPOSAL_SEED, market_account.key.as_ref(), &[bump]],
+        )?;
+        0
+    };
+
+    let now = Clock::get()?.unix_timestamp;
+    let proposal = Proposal {
+        account_type: AccountType::Proposal,
+        market: *market_account.key,
+        id,
+        subject_fee_bps,
+        sell_tax_bps,
+        sell_tax_period,
+        executable_at: now.checked_add(config.proposal_delay).ok_or(ProgramError::ArithmeticOverflow)?,
+        supply: market.supply,
+        veto_weight: 0,
+        status: ProposalStatus::Pending,
+        bump,
+    };
+    Proposal::pack(proposal, &mut proposal_account.data.borrow_mut()).at_account(3)
+}
+
+pub(super) fn process_veto_proposal(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let payer = next_account_info(accounts_iter)?;
+    let holder = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+    let position_account = next_account_info(accounts_iter)?;
+    let proposal_account = next_account_info(accounts_iter)?;
+    let veto_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+
+    check_payer(payer).at_account(0)?;
+    check_signer(holder).at_account(1)?;
+    load_market(program_id, market_account).at_account(2)?;
+    let mut position = load_position(
+        program_id,
+        holder.key,
+        market_account.key,
+        position_account,
+        Some((payer, system_program_account)),
+    )
+    .at_account(3)?;
+    if position.balance == 0 {
+        return Err(ErrorReport::at(FriendtechError::InsufficientFunds, 3).values(1, 0));
+    }
+    let mut proposal = load_proposal(program_id, market_account.key, proposal_account).at_account(4)?;
+    let config = load_config(program_id, config_account).at_account(6)?;
+    let now = Clock::get()?.unix_timestamp;
+    if proposal.status != ProposalStatus::Pending || now >= proposal.executable_at {
+        return Err(ErrorReport::at(FriendtechError::ProposalClosed, 4));
+    }
+
+    let (veto_key, bump) = find_veto_address(program_id, proposal_account.key, holder.key);
+    if veto_key != *veto_account.key {
+        return Err(ErrorReport::at(ProgramError::InvalidSeeds, 5));
+    }
+    if veto_account.owner == program_id {
+        let record = VetoRecord::unpack(&veto_account.data.borrow()).at_account(5)?;
+        if record.proposal_id == proposal.id {
+            return Err(ErrorReport::at(FriendtechError::AlreadyVoted, 5));
+        }
+    } else {
+        create_pda_account(
+            payer,
+            veto_account,
+            system_program_account,
+            program_id,
+            VetoRecord::LEN,
+            &[VETO_SEED, proposal_account.key.as_ref(), holder.key.as_ref(), &[bump]],
+        )?;
+    }
+    let record = VetoRecord {
+        account_type: AccountType::VetoRecord,
+        proposal: *proposal_account.key,
+        voter: *holder.key,
+        proposal_id: proposal.id,
+        bump,
+    };
+    VetoRecord::pack(record, &mut veto_account.data.borrow_mut()).at_account(5)?;
+
+    proposal.veto_weight =
+        proposal.veto_weight.checked_add(position.balance).ok_or(ProgramError::ArithmeticOverflow)?;
+    if proposal.veto_reached(config.veto_threshold_bps) {
+        proposal.status = ProposalStatus::Vetoed;
+    }
+    // Holding the shares until the proposal could execute keeps them from
+    // being sold on to another holder and vetoing again.
+    position.set_veto_locked_until(position.veto_locked_until().max(proposal.executable_at));
+    Position::pack(position, &mut position_account.data.borrow_mut()).at_account(3)?;
+    Proposal::pack(proposal, &mut proposal_account.data.borrow_mut()).at_account(4)
+}
+
+pub(super) fn process_execute_proposal(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let market_account = next_account_info(accounts_iter)?;
+    let proposal_account = next_account_info(accounts_iter)?;
+
+    let mut market = load_market(program_id, market_account).at_account(0)?;
+    let mut proposal = load_proposal(program_id, market_account.key, proposal_account).at_account(1)?;
+    if proposal.status != ProposalStatus::Pending {
+        return Err(ErrorReport::at(FriendtechError::ProposalClosed, 1));
+    }
+    let now = Clock::get()?.unix_timestamp;
+    if now < proposal.executable_at {
+        return Err(
+            ErrorReport::at(FriendtechError::ProposalNotReady, 1).values(proposal.executable_at as u64, now as u64)
+        );
+    }
+
+    market.subject_fee_bps = proposal.subject_fee_bps;
+    market.sell_tax_bps = proposal.sell_tax_bps;
+    market.sell_tax_period = proposal.sell_tax_period;
+    proposal.status = ProposalStatus::Executed;
+    Market::pack(market, &mut market_account.data.borrow_mut()).at_account(0)?;
+    Proposal::pack(proposal, &mut proposal_account.data.borrow_mut()).at_account(1)
+}
+
+/// Loads `market`'s proposal after checking its owner and address.
+pub(super) fn load_proposal(
+    program_id: &Pubkey,
+    market: &Pubkey,
+    proposal_account: &AccountInfo,
+) -> Result<Proposal, ProgramError> {
+    if proposal_account.owner != program_id {
+        return Err(FriendtechError::IncorrectOwner.into());
+    }
+    let proposal = Proposal::unpack(&proposal_account.data.borrow())?;
+    let proposal_key = Pubkey::create_program_address(&[PROPOSAL_SEED, market.as_ref(), &[proposal.bump]], program_id)?;
+    if proposal.market != *market || proposal_key != *proposal_account.key {
+        return Err(FriendtechError::AccountMismatch.into());
+    }
+    Ok(proposal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{install_test_runtime, sample_config, sample_market, test_accounts, TEST_NOW};
+    use crate::{find_config_address, find_position_address, Config};
+    use solana_program::system_program;
+
+    #[test]
+    fn test_propose_veto_and_execute() {
+        install_test_runtime();
+        let program_id = Pubkey::new_unique();
+        let (subject, holder, market_key) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let (config_key, config_bump) = find_config_address(&program_id);
+        let proposal_key = find_proposal_address(&program_id, &market_key).0;
+        let (position_key, position_bump) = find_position_address(&program_id, &market_key, &holder);
+        let keys = [
+            subject,
+            subject,
+            market_key,
+            proposal_key,
+            config_key,
+            system_program::id(),
+            holder,
+            position_key,
+            find_veto_address(&program_id, &proposal_key, &holder).0,
+        ];
+        let mut lamports = [1_000_000_000, 0, 0, 0, 0, 0, 0, 0, 0];
+        let mut data = vec![vec![]; 9];
+        data[2] = vec![0u8; Market::LEN];
+        Market::pack(sample_market(subject), &mut data[2]).unwrap();
+        data[3] = vec![0u8; Proposal::LEN];
+        data[4] = vec![0u8; Config::LEN];
+        Config::pack(Config { bump: config_bump, ..sample_config(Pubkey::new_unique()) }, &mut data[4]).unwrap();
+        data[7] = vec![0u8; Position::LEN];
+        Position::pack(Position { balance: 2, ..Position::new(holder, position_bump) }, &mut data[7]).unwrap();
+        data[8] = vec![0u8; VetoRecord::LEN];
+        let mut accounts = test_accounts(&keys, &mut lamports, &mut data, &program_id, true);
+        let system = system_program::id();
+        for index in [3, 8] {
+            accounts[index].owner = &system;
+        }
+
+        process_propose_parameters(&program_id, &accounts[..6], 700, 1_000, 3_600).unwrap();
+        accounts[3].owner = &program_id;
+        let proposal = load_proposal(&program_id, &market_key, &accounts[3]).unwrap();
+        assert_eq!((proposal.id, proposal.executable_at, proposal.supply), (0, TEST_NOW + 86_400, 12));
+
+        // The threshold is measured against the supply when the proposal was
+        // made, so holders selling out after it do not lower it, and a veto
+        // below it is recorded without closing the proposal.
+        Market::pack(Market { supply: 4, ..sample_market(subject) }, &mut accounts[2].data.borrow_mut()).unwrap();
+        let veto = [0, 6, 2, 7, 3, 8, 4, 5].map(|index| accounts[index].clone());
+        process_veto_proposal(&program_id, &veto).unwrap();
+        let proposal = load_proposal(&program_id, &market_key, &accounts[3]).unwrap();
+        assert_eq!((proposal.veto_weight, proposal.status), (2, ProposalStatus::Pending));
+
+        // The vetoing shares are held until the proposal could execute.
+        let position = Position::unpack(&accounts[7].data.borrow()).unwrap();
+        assert_eq!(position.veto_locked_until(), proposal.executable_at);
+
+        let report = process_execute_proposal(&program_id, &[accounts[2].clone(), accounts[3].clone()]).unwrap_err();
+        assert_eq!(report.error, FriendtechError::ProposalNotReady.into());
+        let due = Proposal { executable_at: TEST_NOW, ..proposal };
+        Proposal::pack(due, &mut accounts[3].data.borrow_mut()).unwrap();
+        process_execute_proposal(&program_id, &[accounts[2].clone(), accounts[3].clone()]).unwrap();
+        let market = Market::unpack(&accounts[2].data.borrow()).unwrap();
+        assert_eq!((market.subject_fee_bps, market.sell_tax_bps, market.sell_tax_period), (700, 1_000, 3_600));
+        let proposal = load_proposal(&program_id, &market_key, &accounts[3]).unwrap();
+        assert_eq!(proposal.status, ProposalStatus::Executed);
+    }
+}
@@ -0,0 +1,125 @@
+//! Paid messages to a market's subject, with the fee accrued in the vault
+//! like trading fees.
+
+use super::accounts::{
+    check_payer, check_token_program, check_vault, check_vault_balance, create_pda_account, load_config, load_market,
+    token_balance,
+};
+use crate::{
+    accrue_fees, find_access_address, split_message_fee, AccountType, AtAccount, ErrorReport, FriendtechError, Market,
+    MessageAccess, ACCESS_SEED, NO_ACCOUNT,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    program::invoke,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use spl_token::instruction as spl_token_instruction;
+
+pub(super) fn process_pay_message_fee(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    count: u32,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let sender = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+    let access_account = next_account_info(accounts_iter)?;
+    let token_account = next_account_info(accounts_iter)?;
+    let vault = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+
+    check_payer(sender).at_account(0)?;
+    check_token_program(token_program).at_account(5)?;
+    let mut market = load_market(program_id, market_account).at_account(1)?;
+    check_vault(&market, vault).at_account(4)?;
+    let config = load_config(program_id, config_account).at_account(7)?;
+    if count == 0 {
+        return Err(ErrorReport::at(ProgramError::InvalidArgument, NO_ACCOUNT));
+    }
+
+    let (access_key, bump) = find_access_address(program_id, market_account.key, sender.key);
+    if access_key != *access_account.key {
+        return Err(ErrorReport::at(ProgramError::InvalidSeeds, 2));
+    }
+    let mut access = if access_account.owner == program_id {
+        MessageAccess::unpack(&access_account.data.borrow()).at_account(2)?
+    } else {
+        create_pda_account(
+            sender,
+            access_account,
+            system_program_account,
+            program_id,
+            MessageAccess::LEN,
+            &[ACCESS_SEED, market_account.key.as_ref(), sender.key.as_ref(), &[bump]],
+        )
+        .at_account(2)?;
+        MessageAccess {
+            account_type: AccountType::MessageAccess,
+            market: *market_account.key,
+            sender: *sender.key,
+            messages: 0,
+            fees_paid: 0,
+            bump,
+        }
+    };
+
+    let amount = market.message_fee.checked_mul(count as u64).ok_or(ProgramError::ArithmeticOverflow)?;
+    if amount > 0 {
+        let balance = token_balance(token_account).at_account(3)?;
+        if balance < amount {
+            return Err(ErrorReport::at(FriendtechError::InsufficientFunds, 3).values(amount, balance));
+        }
+        let vault_balance = token_balance(vault)?.checked_add(amount).ok_or(ProgramError::ArithmeticOverflow)?;
+        let ix =
+            spl_token_instruction::transfer(token_program.key, token_account.key, vault.key, sender.key, &[], amount)?;
+        invoke(&ix, &[token_account.clone(), vault.clone(), sender.clone(), token_program.clone()])?;
+        check_vault_balance(vault, vault_balance)?;
+        let (protocol_fee, subject_fee) = split_message_fee(&config, &market, amount);
+        accrue_fees(&mut market, protocol_fee, subject_fee)?;
+        Market::pack(market, &mut market_account.data.borrow_mut()).at_account(1)?;
+    }
+
+    access.messages = access.messages.checked_add(count as u64).ok_or(ProgramError::ArithmeticOverflow)?;
+    access.fees_paid = access.fees_paid.checked_add(amount).ok_or(ProgramError::ArithmeticOverflow)?;
+    MessageAccess::pack(access, &mut access_account.data.borrow_mut()).at_account(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{sample_config, sample_market, test_accounts};
+    use crate::{find_config_address, Config};
+
+    #[test]
+    fn test_pay_message_fee_checks_access_address() {
+        let program_id = Pubkey::new_unique();
+        let market = sample_market(Pubkey::new_unique());
+        let (config_key, bump) = find_config_address(&program_id);
+        let keys: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let (vault_key, token_program) = (market.vault, spl_token::id());
+        let mut data: Vec<Vec<u8>> = vec![vec![]; 8];
+        data[1] = vec![0u8; Market::LEN];
+        Market::pack(market, &mut data[1]).unwrap();
+        data[7] = vec![0u8; Config::LEN];
+        Config::pack(Config { bump, ..sample_config(Pubkey::new_unique()) }, &mut data[7]).unwrap();
+        let mut lamports = [0u64; 8];
+        let system = Pubkey::default();
+        let account_keys = [keys[0], keys[1], keys[2], keys[3], vault_key, token_program, system, config_key];
+        let mut accounts = test_accounts(&account_keys, &mut lamports, &mut data, &program_id, true);
+
+        let report = process_pay_message_fee(&program_id, &accounts, 0).unwrap_err();
+        assert_eq!(report.error, ProgramError::InvalidArgument);
+        let report = process_pay_message_fee(&program_id, &accounts, 1).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (ProgramError::InvalidSeeds, 2));
+        // The sender's own access PDA passes the address check.
+        let access_key = find_access_address(&program_id, &keys[1], &keys[0]).0;
+        accounts[2].key = &access_key;
+        let report = process_pay_message_fee(&program_id, &accounts, 1).unwrap_err();
+        assert_ne!(report.error, ProgramError::InvalidSeeds);
+    }
+}
@@ -0,0 +1,258 @@
+//! Allowlisted presales at a fixed price before a market opens.
+
+use super::accounts::{
+    check_feature, check_not_winding_down, check_payer, check_router, check_signer, check_token_program, check_vault,
+    check_vault_balance, create_pda_account, load_config, load_market, load_or_create_holding, load_or_create_position,
+    load_stats, token_balance,
+};
+use super::log_trade;
+use crate::{
+    accrue_rewards, find_presale_address, merkle, presale_leaf, presale_price_floor, record_volume,
+    update_reward_index, AccountType, AtAccount, ErrorReport, FriendtechError, Holding, Market, Position, Presale,
+    Stats, TradeSettlement, FEATURE_PRESALES, NO_ACCOUNT, PRESALE_SEED,
+};
+use borsh::BorshSerialize;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    program::{invoke, set_return_data},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+use spl_token::instruction as spl_token_instruction;
+
+pub(super) fn process_start_presale(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    root: [u8; 32],
+    price: u64,
+    allocation: u64,
+    opens_at: i64,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let payer = next_account_info(accounts_iter)?;
+    let subject = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+    let presale_account = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+
+    check_payer(payer).at_account(0)?;
+    check_signer(subject).at_account(1)?;
+    check_feature(&load_config(program_id, config_account).at_account(5)?, FEATURE_PRESALES)?;
+    let mut market = load_market(program_id, market_account).at_account(2)?;
+    if market.authority != *subject.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 1));
+    }
+    if market.supply != 0 || market.last_trade_epoch != u64::MAX {
+        return Err(ErrorReport::at(ProgramError::InvalidAccountData, 2).values(0, market.supply));
+    }
+    let now = Clock::get()?.unix_timestamp;
+    if allocation == 0 || opens_at <= now {
+        return Err(ErrorReport::at(ProgramError::InvalidArgument, NO_ACCOUNT).values(now as u64, opens_at as u64));
+    }
+    let floor = presale_price_floor(allocation)?;
+    if price < floor {
+        return Err(ErrorReport::at(ProgramError::InvalidArgument, NO_ACCOUNT).values(floor, price));
+    }
+    let (presale_key, bump) = find_presale_address(program_id, market_account.key);
+    if presale_key != *presale_account.key {
+        return Err(ErrorReport::at(ProgramError::InvalidSeeds, 3));
+    }
+    if presale_account.owner == program_id {
+        return Err(ErrorReport::at(ProgramError::AccountAlreadyInitialized, 3));
+    }
+
+    create_pda_account(
+        payer,
+        presale_account,
+        system_program_account,
+        program_id,
+        Presale::LEN,
+        &[PRESALE_SEED, market_account.key.as_ref(), &[bump]],
+    )?;
+    let presale = Presale {
+        account_type: AccountType::Presale,
+        market: *market_account.key,
+        root,
+        price,
+        allocation,
+        sold: 0,
+        bump,
+    };
+    Presale::pack(presale, &mut presale_account.data.borrow_mut()).at_account(3)?;
+    market.opens_at = opens_at;
+    Market::pack(market, &mut market_account.data.borrow_mut()).at_account(2)
+}
+
+pub(super) fn process_buy_presale(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+    cap: u64,
+    proof: Vec<[u8; 32]>,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let payer = next_account_info(accounts_iter)?;
+    let buyer = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+    let position_account = next_account_info(accounts_iter)?;
+    let token_account = next_account_info(accounts_iter)?;
+    let vault = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+    let stats_account = next_account_info(accounts_iter)?;
+    let holding_account = next_account_info(accounts_iter)?;
+    let presale_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+
+    check_payer(payer).at_account(0)?;
+    check_signer(buyer).at_account(1)?;
+    let config = load_config(program_id, config_account).at_account(11)?;
+    check_feature(&config, FEATURE_PRESALES)?;
+    check_not_winding_down(&config)?;
+    let mut market = load_market(program_id, market_account).at_account(2)?;
+    check_router(&market, accounts, 2)?;
+    check_vault(&market, vault).at_account(5)?;
+    check_token_program(token_program).at_account(6)?;
+    let mut stats = load_stats(program_id, stats_account).at_account(8)?;
+    let mut presale = load_presale(program_id, market_account.key, presale_account).at_account(10)?;
+    let clock = Clock::get()?;
+    if clock.unix_timestamp >= market.opens_at {
+        return Err(ErrorReport::at(FriendtechError::PresaleClosed, 10)
+            .values(market.opens_at as u64, clock.unix_timestamp as u64));
+    }
+    if !merkle::verify(&presale.root, presale_leaf(buyer.key, cap), &proof) {
+        return Err(ErrorReport::at(FriendtechError::InvalidProof, 10));
+    }
+    let unsold = presale.allocation - presale.sold;
+    if amount == 0 || amount > unsold {
+        return Err(ErrorReport::at(FriendtechError::InsufficientFunds, 10).values(amount, unsold));
+    }
+
+    let mut position = load_or_create_position(
+        program_id,
+        payer,
+        buyer.key,
+        market_account.key,
+        position_account,
+        system_program_account,
+    )
+    .at_account(3)?;
+    // Nobody trades before the market opens, so the balance is all presale.
+    let bought = position.balance.saturating_add(amount);
+    if bought > cap {
+        return Err(ErrorReport::at(FriendtechError::InsufficientFunds, 3).values(cap, bought));
+    }
+    let cost = presale.price.checked_mul(amount).ok_or(ProgramError::ArithmeticOverflow)?;
+    let vault_balance =
+        token_balance(vault).at_account(5)?.checked_add(cost).ok_or(ProgramError::ArithmeticOverflow)?;
+    let ix = spl_token_instruction::transfer(token_program.key, token_account.key, vault.key, buyer.key, &[], cost)?;
+    invoke(&ix, &[token_account.clone(), vault.clone(), buyer.clone(), token_program.clone()])?;
+    check_vault_balance(vault, vault_balance)?;
+
+    if position.balance == 0 {
+        position.set_acquired_at(clock.unix_timestamp);
+        let mut holding = load_or_create_holding(
+            program_id,
+            payer,
+            buyer.key,
+            &market.subject,
+            holding_account,
+            system_program_account,
+        )
+        .at_account(9)?;
+        holding.markets = holding.markets.checked_add(1).ok_or(ProgramError::ArithmeticOverflow)?;
+        Holding::pack(holding, &mut holding_account.data.borrow_mut()).at_account(9)?;
+        market.holders = market.holders.checked_add(1).ok_or(ProgramError::ArithmeticOverflow)?;
+    }
+    update_reward_index(&mut market, clock.unix_timestamp)?;
+    accrue_rewards(&market, &mut position)?;
+    // Presale shares' sell tax decays from the public open.
+    position.set_tax_basis_at(market.opens_at);
+    position.balance = bought;
+    presale.sold += amount;
+    let settlement = TradeSettlement {
+        gross_price: cost,
+        protocol_fee: 0,
+        subject_fee: 0,
+        interface_fee: 0,
+        net_amount: cost,
+        new_supply: market.supply.checked_add(amount).ok_or(ProgramError::ArithmeticOverflow)?,
+    };
+    market.supply = settlement.new_supply;
+    stats.record_trade(&mut market, &settlement, clock.epoch);
+    record_volume(&mut market, settlement.gross_price, clock.unix_timestamp);
+    Position::pack(position, &mut position_account.data.borrow_mut()).at_account(3)?;
+    Presale::pack(presale, &mut presale_account.data.borrow_mut()).at_account(10)?;
+    Market::pack(market, &mut market_account.data.borrow_mut()).at_account(2)?;
+    Stats::pack(stats, &mut stats_account.data.borrow_mut()).at_account(8)?;
+    log_trade(market_account.key, buyer.key, true, amount, &settlement, None, None)?;
+    set_return_data(&settlement.try_to_vec()?);
+    Ok(())
+}
+
+/// Loads `market`'s presale after checking its owner and address.
+pub(super) fn load_presale(
+    program_id: &Pubkey,
+    market: &Pubkey,
+    presale_account: &AccountInfo,
+) -> Result<Presale, ProgramError> {
+    if presale_account.owner != program_id {
+        return Err(FriendtechError::IncorrectOwner.into());
+    }
+    let presale = Presale::unpack(&presale_account.data.borrow())?;
+    if presale.market != *market {
+        return Err(FriendtechError::AccountMismatch.into());
+    }
+    let presale_key = Pubkey::create_program_address(&[PRESALE_SEED, market.as_ref(), &[presale.bump]], program_id)?;
+    if presale_key != *presale_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(presale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{install_test_runtime, sample_config, sample_market, test_accounts, TEST_NOW};
+    use crate::{find_config_address, Config};
+    use solana_program::system_program;
+
+    #[test]
+    fn test_start_presale() {
+        install_test_runtime();
+        let program_id = Pubkey::new_unique();
+        let (subject, market_key) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (config_key, bump) = find_config_address(&program_id);
+        let keys = [
+            subject,
+            subject,
+            market_key,
+            find_presale_address(&program_id, &market_key).0,
+            system_program::id(),
+            config_key,
+        ];
+        let mut lamports = [1_000_000_000, 0, 0, 0, 0, 0];
+        let mut data = vec![vec![]; 6];
+        data[2] = vec![0u8; Market::LEN];
+        Market::pack(Market { supply: 0, ..sample_market(subject) }, &mut data[2]).unwrap();
+        data[3] = vec![0u8; Presale::LEN];
+        data[5] = vec![0u8; Config::LEN];
+        Config::pack(Config { bump, ..sample_config(Pubkey::new_unique()) }, &mut data[5]).unwrap();
+        let mut accounts = test_accounts(&keys, &mut lamports, &mut data, &program_id, true);
+        let system = system_program::id();
+        accounts[3].owner = &system;
+
+        // The presale price may not undercut the curve across its allocation.
+        let (root, floor, opens_at) = ([7u8; 32], presale_price_floor(10).unwrap(), TEST_NOW + 3_600);
+        let report = process_start_presale(&program_id, &accounts, root, floor - 1, 10, opens_at).unwrap_err();
+        assert_eq!(report.error, ProgramError::InvalidArgument);
+        process_start_presale(&program_id, &accounts, root, floor, 10, opens_at).unwrap();
+        let presale = Presale::unpack(&accounts[3].data.borrow()).unwrap();
+        assert_eq!((presale.root, presale.price, presale.allocation, presale.sold), (root, floor, 10, 0));
+        assert_eq!(Market::unpack(&accounts[2].data.borrow()).unwrap().opens_at, opens_at);
+    }
+}
@@ -0,0 +1,167 @@
+//! Subject identities: attested social handles and mutual holding checks.
+
+use super::accounts::{check_payer, check_signer, create_pda_account, load_config, load_holding};
+use crate::{
+    attestation_message, find_profile_address, AccountType, AtAccount, ErrorReport, FriendtechError, Holding,
+    MutualHolding, Profile, PROFILE_SEED,
+};
+use borsh::BorshSerialize;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    ed25519_program,
+    instruction::Instruction,
+    program::set_return_data,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar::instructions as sysvar_instructions,
+};
+
+pub(super) fn process_attest_identity(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    handle_hash: [u8; 32],
+    attestor_sig: [u8; 64],
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let payer = next_account_info(accounts_iter)?;
+    let subject = next_account_info(accounts_iter)?;
+    let profile_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let instructions_sysvar = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+
+    check_payer(payer).at_account(0)?;
+    check_signer(subject).at_account(1)?;
+    let config = load_config(program_id, config_account).at_account(3)?;
+
+    // The attestor's signature must be checked by the Ed25519 program in the
+    // instruction immediately before this one.
+    let current_index = sysvar_instructions::load_current_index_checked(instructions_sysvar).at_account(4)?;
+    if current_index == 0 {
+        return Err(ErrorReport::at(FriendtechError::InvalidAttestation, 4));
+    }
+    let verify_ix = sysvar_instructions::load_instruction_at_checked(current_index as usize - 1, instructions_sysvar)
+        .at_account(4)?;
+    let message = attestation_message(program_id, subject.key, &handle_hash);
+    verify_ed25519_ix(&verify_ix, &config.attestor, &message, &attestor_sig).at_account(4)?;
+
+    let (profile_key, bump) = find_profile_address(program_id, subject.key);
+    if profile_key != *profile_account.key {
+        return Err(ErrorReport::at(ProgramError::InvalidSeeds, 2));
+    }
+    if profile_account.owner != program_id {
+        create_pda_account(
+            payer,
+            profile_account,
+            system_program_account,
+            program_id,
+            Profile::LEN,
+            &[PROFILE_SEED, subject.key.as_ref(), &[bump]],
+        )?;
+    }
+
+    let profile = Profile {
+        account_type: AccountType::Profile,
+        subject: *subject.key,
+        handle_hash,
+        attestor: config.attestor,
+        attestor_sig,
+        bump,
+    };
+    Profile::pack(profile, &mut profile_account.data.borrow_mut()).at_account(2)
+}
+
+pub(super) fn process_check_mutual_holding(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    a: Pubkey,
+    b: Pubkey,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let a_holding_account = next_account_info(accounts_iter)?;
+    let b_holding_account = next_account_info(accounts_iter)?;
+
+    let holds = |holding: Option<Holding>| holding.is_some_and(|holding| holding.markets > 0);
+    let result = MutualHolding {
+        a_holds_b: holds(load_holding(program_id, &a, &b, a_holding_account).at_account(0)?),
+        b_holds_a: holds(load_holding(program_id, &b, &a, b_holding_account).at_account(1)?),
+    };
+    set_return_data(&result.try_to_vec()?);
+    Ok(())
+}
+
+/// Checks that `ix` is an Ed25519 program instruction verifying exactly one
+/// `signature` by `signer` over `message`, with all data carried inline.
+pub(super) fn verify_ed25519_ix(
+    ix: &Instruction,
+    signer: &Pubkey,
+    message: &[u8],
+    signature: &[u8; 64],
+) -> Result<(), ProgramError> {
+    const OFFSETS_START: usize = 2;
+    const OFFSETS_LEN: usize = 14;
+    let invalid = || ProgramError::from(FriendtechError::InvalidAttestation);
+
+    let data = &ix.data;
+    if ix.program_id != ed25519_program::id() || data.len() < OFFSETS_START + OFFSETS_LEN || data[0] != 1 {
+        return Err(invalid());
+    }
+    let offset = |i: usize| u16::from_le_bytes([data[OFFSETS_START + 2 * i], data[OFFSETS_START + 2 * i + 1]]);
+    let (sig_offset, sig_ix, key_offset, key_ix, msg_offset, msg_size, msg_ix) =
+        (offset(0), offset(1), offset(2), offset(3), offset(4), offset(5), offset(6));
+    // u16::MAX points the Ed25519 program at its own instruction data.
+    if sig_ix != u16::MAX || key_ix != u16::MAX || msg_ix != u16::MAX {
+        return Err(invalid());
+    }
+    let field = |start: u16, len: usize| data.get(start as usize..start as usize + len).ok_or_else(invalid);
+    if field(key_offset, 32)? != signer.as_ref()
+        || field(sig_offset, 64)? != signature.as_ref()
+        || field(msg_offset, msg_size as usize)? != message
+    {
+        return Err(invalid());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds an Ed25519 program instruction carrying one inline signature.
+    fn ed25519_ix(signer: &Pubkey, message: &[u8], signature: &[u8; 64]) -> Instruction {
+        let (key_offset, sig_offset, msg_offset) = (16u16, 48u16, 112u16);
+        let mut data = vec![1u8, 0];
+        for value in [sig_offset, u16::MAX, key_offset, u16::MAX, msg_offset, message.len() as u16, u16::MAX] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(signer.as_ref());
+        data.extend_from_slice(signature);
+        data.extend_from_slice(message);
+        Instruction { program_id: ed25519_program::id(), accounts: vec![], data }
+    }
+
+    #[test]
+    fn test_verify_ed25519_ix() {
+        let (program_id, subject, attestor) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let message = attestation_message(&program_id, &subject, &[7; 32]);
+        let signature = [9; 64];
+        let ix = ed25519_ix(&attestor, &message, &signature);
+        assert!(verify_ed25519_ix(&ix, &attestor, &message, &signature).is_ok());
+
+        // A different signer, handle or signature must not pass.
+        assert!(verify_ed25519_ix(&ix, &subject, &message, &signature).is_err());
+        let other_message = attestation_message(&program_id, &subject, &[8; 32]);
+        assert!(verify_ed25519_ix(&ix, &attestor, &other_message, &signature).is_err());
+        assert!(verify_ed25519_ix(&ix, &attestor, &message, &[1; 64]).is_err());
+
+        let mut wrong_program = ix.clone();
+        wrong_program.program_id = Pubkey::new_unique();
+        assert!(verify_ed25519_ix(&wrong_program, &attestor, &message, &signature).is_err());
+
+        // Offsets pointing at another instruction's data are rejected.
+        let mut external = ix;
+        external.data[4..6].copy_from_slice(&0u16.to_le_bytes());
+        assert!(verify_ed25519_ix(&external, &attestor, &message, &signature).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+//! `TradeReceipt` records written by trades that ask for one, and
+//! `CloseTradeReceipt`.
+
+use super::accounts::{check_payer, close_account, create_pda_account};
+use crate::{
+    find_receipt_address, AccountType, AtAccount, ErrorReport, FriendtechError, TradeReceipt, TradeSettlement,
+    NO_ACCOUNT, RECEIPT_RETENTION_SLOTS, RECEIPT_SEED,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+/// Accounts a trade writes its receipt with; the payer and receipt PDA come
+/// with their indices in the trade's accounts.
+pub(super) struct ReceiptAccounts<'a, 'b> {
+    pub(super) payer: (&'a AccountInfo<'b>, u8),
+    pub(super) receipt: (&'a AccountInfo<'b>, u8),
+    pub(super) system_program: &'a AccountInfo<'b>,
+}
+
+/// Creates `trader`'s receipt of a settled trade at its
+/// `[RECEIPT_SEED, trader, receipt_id]` PDA. An id can only be used once per
+/// trader, so a trade reusing one fails.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn write_receipt(
+    program_id: &Pubkey,
+    accounts: &ReceiptAccounts,
+    receipt_id: &[u8; 32],
+    market: &Pubkey,
+    trader: &Pubkey,
+    is_buy: bool,
+    amount: u64,
+    settlement: &TradeSettlement,
+    slot: u64,
+) -> Result<(), ErrorReport> {
+    let ((payer, payer_index), (receipt_account, index)) = (accounts.payer, accounts.receipt);
+    check_payer(payer).at_account(payer_index)?;
+    let (receipt_key, bump) = find_receipt_address(program_id, trader, receipt_id);
+    if receipt_key != *receipt_account.key {
+        return Err(ErrorReport::at(ProgramError::InvalidSeeds, index));
+    }
+    if receipt_account.owner == program_id {
+        return Err(ErrorReport::at(ProgramError::AccountAlreadyInitialized, index));
+    }
+    create_pda_account(
+        payer,
+        receipt_account,
+        accounts.system_program,
+        program_id,
+        TradeReceipt::LEN,
+        &[RECEIPT_SEED, trader.as_ref(), receipt_id, &[bump]],
+    )
+    .at_account(index)?;
+    let receipt = TradeReceipt {
+        account_type: AccountType::TradeReceipt,
+        market: *market,
+        trader: *trader,
+        is_buy,
+        amount,
+        net_amount: settlement.net_amount,
+        slot,
+        rent_payer: *payer.key,
+        bump,
+    };
+    TradeReceipt::pack(receipt, &mut receipt_account.data.borrow_mut()).at_account(index)
+}
+
+pub(super) fn process_close_trade_receipt(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let receipt_account = next_account_info(accounts_iter)?;
+    let rent_payer = next_account_info(accounts_iter)?;
+
+    if receipt_account.owner != program_id {
+        return Err(ErrorReport::at(FriendtechError::IncorrectOwner, 0));
+    }
+    let receipt = TradeReceipt::unpack(&receipt_account.data.borrow()).at_account(0)?;
+    if receipt.rent_payer != *rent_payer.key {
+        return Err(ErrorReport::at(FriendtechError::AccountMismatch, 1));
+    }
+    let expires_at = receipt.slot.saturating_add(RECEIPT_RETENTION_SLOTS);
+    let slot = Clock::get()?.slot;
+    if slot < expires_at {
+        return Err(ErrorReport::at(FriendtechError::ReceiptRetained, NO_ACCOUNT).values(expires_at, slot));
+    }
+    close_account(receipt_account, rent_payer).at_account(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_close_trade_receipt_checks_receipt() {
+        let program_id = Pubkey::new_unique();
+        let (receipt_key, rent_payer) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let receipt = TradeReceipt {
+            account_type: AccountType::TradeReceipt,
+            market: Pubkey::new_unique(),
+            trader: Pubkey::new_unique(),
+            is_buy: true,
+            amount: 3,
+            net_amount: 1_000,
+            slot: 42,
+            rent_payer,
+            bump: 255,
+        };
+        let (mut receipt_lamports, mut payer_lamports) = (1_000, 0);
+        let (mut receipt_data, mut payer_data) = (vec![0u8; TradeReceipt::LEN], vec![]);
+        TradeReceipt::pack(receipt, &mut receipt_data).unwrap();
+        let owner = Pubkey::new_unique();
+        let mut accounts = [
+            AccountInfo::new(&receipt_key, false, true, &mut receipt_lamports, &mut receipt_data, &owner, false, 0),
+            AccountInfo::new(&rent_payer, false, true, &mut payer_lamports, &mut payer_data, &owner, false, 0),
+        ];
+
+        let report = process_close_trade_receipt(&program_id, &accounts).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::IncorrectOwner.into(), 0));
+        accounts[0].owner = &program_id;
+        let impostor = Pubkey::new_unique();
+        accounts[1].key = &impostor;
+        let report = process_close_trade_receipt(&program_id, &accounts).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::AccountMismatch.into(), 1));
+    }
+}
@@ -0,0 +1,159 @@
+//! Liquidity-mining rewards: the rewards mint, emission rates and claims.
+
+use super::accounts::{
+    check_feature, check_payer, check_signer, check_token_program, create_pda_account, load_config, load_market,
+    load_position,
+};
+use crate::{
+    accrue_rewards, find_rewards_mint_address, update_reward_index, AtAccount, ErrorReport, FriendtechError, Market,
+    Position, CONFIG_SEED, FEATURE_LIQUIDITY_MINING, REWARDS_MINT_SEED,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+use spl_token::{instruction as spl_token_instruction, state::Mint as SplMint};
+
+pub(super) fn process_create_rewards_mint(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    decimals: u8,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let payer = next_account_info(accounts_iter)?;
+    let admin = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let mint_account = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+
+    check_payer(payer).at_account(0)?;
+    check_signer(admin).at_account(1)?;
+    let config = load_config(program_id, config_account).at_account(2)?;
+    if config.admin != *admin.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 1));
+    }
+    check_feature(&config, FEATURE_LIQUIDITY_MINING)?;
+    check_token_program(token_program).at_account(4)?;
+    let (mint_key, bump) = find_rewards_mint_address(program_id);
+    if mint_key != *mint_account.key {
+        return Err(ErrorReport::at(ProgramError::InvalidSeeds, 3));
+    }
+
+    create_pda_account(
+        payer,
+        mint_account,
+        system_program_account,
+        &spl_token::id(),
+        SplMint::LEN,
+        &[REWARDS_MINT_SEED, &[bump]],
+    )?;
+    let ix = spl_token_instruction::initialize_mint2(
+        token_program.key,
+        mint_account.key,
+        config_account.key,
+        None,
+        decimals,
+    )?;
+    invoke(&ix, &[mint_account.clone(), token_program.clone()])?;
+    Ok(())
+}
+
+pub(super) fn process_set_emission_rate(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    emission_rate: u64,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let admin = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+
+    check_signer(admin).at_account(0)?;
+    let config = load_config(program_id, config_account).at_account(1)?;
+    if config.admin != *admin.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    check_feature(&config, FEATURE_LIQUIDITY_MINING)?;
+    let mut market = load_market(program_id, market_account).at_account(2)?;
+    update_reward_index(&mut market, Clock::get()?.unix_timestamp)?;
+    market.emission_rate = emission_rate;
+    Market::pack(market, &mut market_account.data.borrow_mut()).at_account(2)
+}
+
+pub(super) fn process_claim_rewards(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+    let position_account = next_account_info(accounts_iter)?;
+    let mint_account = next_account_info(accounts_iter)?;
+    let token_account = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+
+    check_signer(owner).at_account(0)?;
+    let mut market = load_market(program_id, market_account).at_account(1)?;
+    let mut position =
+        load_position(program_id, owner.key, market_account.key, position_account, None).at_account(2)?;
+    if find_rewards_mint_address(program_id).0 != *mint_account.key {
+        return Err(ErrorReport::at(ProgramError::InvalidSeeds, 3));
+    }
+    check_token_program(token_program).at_account(5)?;
+    let config = load_config(program_id, config_account).at_account(6)?;
+    check_feature(&config, FEATURE_LIQUIDITY_MINING)?;
+
+    // Claims leave the supply unchanged, so the market need not store its
+    // updated index.
+    update_reward_index(&mut market, Clock::get()?.unix_timestamp)?;
+    accrue_rewards(&market, &mut position)?;
+    let amount = std::mem::take(&mut position.rewards_owed);
+    if amount > 0 {
+        let ix = spl_token_instruction::mint_to(
+            token_program.key,
+            mint_account.key,
+            token_account.key,
+            config_account.key,
+            &[],
+            amount,
+        )?;
+        invoke_signed(
+            &ix,
+            &[mint_account.clone(), token_account.clone(), config_account.clone(), token_program.clone()],
+            &[&[CONFIG_SEED, &[config.bump]]],
+        )?;
+    }
+    Position::pack(position, &mut position_account.data.borrow_mut()).at_account(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{install_test_runtime, sample_config, sample_market, test_accounts, TEST_NOW};
+    use crate::{find_config_address, Config};
+
+    #[test]
+    fn test_set_emission_rate() {
+        install_test_runtime();
+        let program_id = Pubkey::new_unique();
+        let admin = Pubkey::new_unique();
+        let (config_key, bump) = find_config_address(&program_id);
+        let keys = [admin, config_key, Pubkey::new_unique()];
+        let mut lamports = [0; 3];
+        let mut data = vec![vec![], vec![0u8; Config::LEN], vec![0u8; Market::LEN]];
+        Config::pack(Config { bump, ..sample_config(admin) }, &mut data[1]).unwrap();
+        let market = Market { emission_rate: 10, rewards_updated_at: TEST_NOW - 100, ..sample_market(admin) };
+        Market::pack(market, &mut data[2]).unwrap();
+        let accounts = test_accounts(&keys, &mut lamports, &mut data, &program_id, true);
+
+        // Emissions so far accrue at the old rate before the new one applies.
+        process_set_emission_rate(&program_id, &accounts, 25).unwrap();
+        let market = Market::unpack(&accounts[2].data.borrow()).unwrap();
+        assert_eq!((market.emission_rate, market.rewards_updated_at), (25, TEST_NOW));
+        assert!(market.reward_index > 0);
+    }
+}
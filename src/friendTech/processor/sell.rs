@@ -0,0 +1,201 @@
+//! `SellShares` and `SellAll`.
+
+use super::accounts::{
+    check_distinct, check_hold_period, check_interface_fee, check_router, check_signer, check_token_program,
+    check_unlocked, check_vault, close_account, load_config, load_holding, load_market, load_position, load_stats,
+    transfer_from_vault,
+};
+use super::candles::record_trade_candle;
+use super::log_trade;
+use super::receipt::{write_receipt, ReceiptAccounts};
+use crate::{
+    accrue_fees, accrue_rewards, dump_fee_bps, quote_sell, record_dump_window_sell, record_volume, roll_volume_window,
+    sell_tax_bps, update_reward_index, wind_down_stage, AtAccount, ErrorReport, FriendtechError, Holding, InterfaceFee,
+    Market, MarketPause, Position, Stats, WindDownStage, NO_ACCOUNT,
+};
+use borsh::BorshSerialize;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    program::set_return_data,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+/// How much a sell takes.
+pub(super) enum SellSize {
+    Shares(u64),
+    /// The whole balance, optionally closing the position afterwards.
+    All {
+        close_position: bool,
+    },
+}
+
+pub(super) fn process_sell_shares(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    size: SellSize,
+    client_id: Option<[u8; 8]>,
+    interface_fee: Option<InterfaceFee>,
+    receipt_id: Option<[u8; 32]>,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let seller = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+    let position_account = next_account_info(accounts_iter)?;
+    let token_account = next_account_info(accounts_iter)?;
+    let vault = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+    let stats_account = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let holding_account = next_account_info(accounts_iter)?;
+
+    check_signer(seller).at_account(0)?;
+    let mut market = load_market(program_id, market_account).at_account(1)?;
+    check_router(&market, accounts, 1)?;
+    check_vault(&market, vault).at_account(4)?;
+    check_token_program(token_program).at_account(5)?;
+    let mut stats = load_stats(program_id, stats_account).at_account(6)?;
+    let config = load_config(program_id, config_account).at_account(7)?;
+    let interface_fee = match interface_fee {
+        Some(fee) => Some(check_interface_fee(&config, &fee, next_account_info(accounts_iter)?, 9)?),
+        None => None,
+    };
+    let recipient = interface_fee.map_or(vault, |(_, recipient)| recipient);
+    let fee_accounts = [(token_account, 3), (vault, 4), (recipient, 9)];
+    check_distinct(&fee_accounts[..2 + interface_fee.is_some() as usize])?;
+    let receipt = match receipt_id {
+        Some(receipt_id) => {
+            let index = 10 + interface_fee.is_some() as u8;
+            let receipt_accounts = ReceiptAccounts {
+                payer: (next_account_info(accounts_iter)?, index),
+                receipt: (next_account_info(accounts_iter)?, index + 1),
+                system_program: next_account_info(accounts_iter)?,
+            };
+            Some((receipt_id, receipt_accounts))
+        }
+        None => None,
+    };
+    let rent_recipient = match size {
+        SellSize::All { close_position: true } => Some(next_account_info(accounts_iter)?),
+        _ => None,
+    };
+    let candles = if market.candles {
+        let index = 9 + interface_fee.is_some() as u8 + 3 * receipt.is_some() as u8 + rent_recipient.is_some() as u8;
+        Some((next_account_info(accounts_iter)?, index))
+    } else {
+        None
+    };
+    let clock = Clock::get()?;
+    // Winding down, sells go through at the plain curve price.
+    let winding_down = match wind_down_stage(&config, clock.unix_timestamp) {
+        WindDownStage::Active => false,
+        WindDownStage::SellsOnly => true,
+        WindDownStage::Redeeming => return Err(ErrorReport::at(FriendtechError::WindingDown, NO_ACCOUNT)),
+    };
+    if market.pause == MarketPause::Halted && !winding_down {
+        return Err(ErrorReport::at(FriendtechError::MarketPaused, 1));
+    }
+    let mut position =
+        load_position(program_id, seller.key, market_account.key, position_account, None).at_account(2)?;
+    check_unlocked(&position, clock.unix_timestamp).at_account(2)?;
+    let amount = match size {
+        SellSize::Shares(amount) => amount,
+        SellSize::All { .. } => position.balance,
+    };
+
+    if position.balance < amount {
+        return Err(ErrorReport::at(FriendtechError::InsufficientFunds, 2).values(amount, position.balance));
+    }
+    if clock.unix_timestamp < market.opens_at {
+        return Err(ErrorReport::at(FriendtechError::MarketNotOpen, 1)
+            .values(market.opens_at as u64, clock.unix_timestamp as u64));
+    }
+    if !winding_down {
+        check_hold_period(&market, &position, clock.unix_timestamp, 2)?;
+    }
+    roll_volume_window(&mut market, clock.unix_timestamp);
+
+    let tax_bps = if winding_down {
+        0
+    } else {
+        sell_tax_bps(&market, &position, clock.unix_timestamp) + dump_fee_bps(&market, amount, clock.unix_timestamp)
+    };
+    let mut settlement = quote_sell(&config, &market, amount)?.with_sell_tax(tax_bps)?;
+    if let Some((fee_bps, recipient)) = interface_fee {
+        settlement = settlement.with_interface_fee(fee_bps, false)?;
+        transfer_from_vault(&market, market_account, vault, recipient, token_program, settlement.interface_fee)?;
+    }
+    transfer_from_vault(&market, market_account, vault, token_account, token_program, settlement.net_amount)?;
+
+    update_reward_index(&mut market, clock.unix_timestamp)?;
+    accrue_rewards(&market, &mut position)?;
+    if rent_recipient.is_some() && position.rewards_owed > 0 {
+        return Err(ErrorReport::at(FriendtechError::UnclaimedRewards, 2).values(0, position.rewards_owed));
+    }
+    position.balance -= amount;
+    if position.balance == 0 && amount > 0 {
+        market.holders = market.holders.saturating_sub(1);
+        if let Some(mut holding) =
+            load_holding(program_id, seller.key, &market.subject, holding_account).at_account(8)?
+        {
+            holding.markets = holding.markets.saturating_sub(1);
+            Holding::pack(holding, &mut holding_account.data.borrow_mut()).at_account(8)?;
+        }
+    }
+    record_dump_window_sell(&mut market, amount, clock.unix_timestamp);
+    market.supply = settlement.new_supply;
+    accrue_fees(&mut market, settlement.protocol_fee, settlement.subject_fee)?;
+    stats.record_trade(&mut market, &settlement, clock.epoch);
+    record_volume(&mut market, settlement.gross_price, clock.unix_timestamp);
+    match rent_recipient {
+        Some(rent_recipient) => close_account(position_account, rent_recipient).at_account(9)?,
+        None => Position::pack(position, &mut position_account.data.borrow_mut()).at_account(2)?,
+    }
+    Market::pack(market, &mut market_account.data.borrow_mut()).at_account(1)?;
+    Stats::pack(stats, &mut stats_account.data.borrow_mut()).at_account(6)?;
+    record_trade_candle(program_id, candles, market_account.key, amount, settlement.gross_price, clock.unix_timestamp)?;
+    if let Some((receipt_id, receipt_accounts)) = receipt {
+        write_receipt(
+            program_id,
+            &receipt_accounts,
+            &receipt_id,
+            market_account.key,
+            seller.key,
+            false,
+            amount,
+            &settlement,
+            clock.slot,
+        )?;
+    }
+    let recipient = interface_fee.map(|(_, recipient)| recipient.key);
+    log_trade(market_account.key, seller.key, false, amount, &settlement, client_id, recipient)?;
+    set_return_data(&settlement.try_to_vec()?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_accounts;
+    use solana_program::program_error::ProgramError;
+
+    #[test]
+    fn test_sell_checks_seller_and_market() {
+        let (program_id, other_program) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let keys: Vec<Pubkey> = (0..9).map(|_| Pubkey::new_unique()).collect();
+        let mut lamports = [0u64; 9];
+        let mut data = vec![vec![]; 9];
+        let mut accounts = test_accounts(&keys, &mut lamports, &mut data, &other_program, false);
+
+        let report = process_sell_shares(&program_id, &accounts, SellSize::Shares(1), None, None, None).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (ProgramError::MissingRequiredSignature, 0));
+        accounts[0].is_signer = true;
+        let report = process_sell_shares(&program_id, &accounts, SellSize::Shares(1), None, None, None).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::IncorrectOwner.into(), 1));
+        let report =
+            process_sell_shares(&program_id, &accounts[..8], SellSize::Shares(1), None, None, None).unwrap_err();
+        assert_eq!(report.error, ProgramError::NotEnoughAccountKeys);
+    }
+}
@@ -0,0 +1,144 @@
+//! Devnet-only export and import of market state.
+
+use super::accounts::{
+    check_payer, check_position_address, check_signer, check_token_program, create_pda_account, create_vault,
+    load_config, load_market,
+};
+use crate::{
+    find_market_address, find_position_address, find_vault_address, AtAccount, ErrorReport, FriendtechError, Market,
+    MarketSnapshot, Position, MARKET_SEED, NO_ACCOUNT, POSITION_SEED,
+};
+use borsh::BorshSerialize;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    program::set_return_data,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+
+pub(super) fn process_export_market_snapshot(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let market_account = next_account_info(accounts_iter)?;
+
+    let market = load_market(program_id, market_account).at_account(0)?;
+    let mut positions = Vec::new();
+    for (i, position_account) in accounts_iter.enumerate() {
+        let index = (i + 1) as u8;
+        if position_account.owner != program_id {
+            return Err(ErrorReport::at(FriendtechError::IncorrectOwner, index));
+        }
+        let position = Position::unpack(&position_account.data.borrow()).at_account(index)?;
+        check_position_address(program_id, market_account.key, &position, position_account).at_account(index)?;
+        positions.push(position);
+    }
+
+    let data = MarketSnapshot { market, positions }.try_to_vec()?;
+    let max_len = solana_program::program::MAX_RETURN_DATA;
+    if data.len() > max_len {
+        return Err(
+            ErrorReport::at(ProgramError::InvalidArgument, NO_ACCOUNT).values(max_len as u64, data.len() as u64)
+        );
+    }
+    set_return_data(&data);
+    Ok(())
+}
+
+pub(super) fn process_import_market_snapshot(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    snapshot: MarketSnapshot,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let payer = next_account_info(accounts_iter)?;
+    let admin = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+    let vault = next_account_info(accounts_iter)?;
+    let quote_mint = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+
+    check_payer(payer).at_account(0)?;
+    check_signer(admin).at_account(1)?;
+    let config = load_config(program_id, config_account).at_account(2)?;
+    if config.admin != *admin.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 1));
+    }
+    check_token_program(token_program).at_account(6)?;
+
+    let market = snapshot.market;
+    let (market_key, bump) = find_market_address(program_id, &market.subject, &market.quote_mint);
+    if market_key != *market_account.key || bump != market.bump || market.quote_mint != *quote_mint.key {
+        return Err(ErrorReport::at(ProgramError::InvalidSeeds, 3));
+    }
+    if market_account.owner == program_id {
+        return Err(ErrorReport::at(ProgramError::AccountAlreadyInitialized, 3));
+    }
+    let (vault_key, vault_bump) = find_vault_address(program_id, &market_key);
+    if vault_key != *vault.key || vault_key != market.vault {
+        return Err(ErrorReport::at(ProgramError::InvalidSeeds, 4));
+    }
+
+    create_vault(payer, vault, quote_mint, token_program, system_program_account, &market_key, vault_bump)?;
+    create_pda_account(
+        payer,
+        market_account,
+        system_program_account,
+        program_id,
+        Market::LEN,
+        &[MARKET_SEED, market.subject.as_ref(), market.quote_mint.as_ref(), &[bump]],
+    )?;
+    Market::pack(market, &mut market_account.data.borrow_mut()).at_account(3)?;
+
+    for (i, position) in snapshot.positions.into_iter().enumerate() {
+        let index = (8 + i) as u8;
+        let position_account = next_account_info(accounts_iter)?;
+        let (position_key, position_bump) = find_position_address(program_id, &market_key, &position.owner);
+        if position_key != *position_account.key || position_bump != position.bump {
+            return Err(ErrorReport::at(ProgramError::InvalidSeeds, index));
+        }
+        if position_account.owner == program_id {
+            return Err(ErrorReport::at(ProgramError::AccountAlreadyInitialized, index));
+        }
+        create_pda_account(
+            payer,
+            position_account,
+            system_program_account,
+            program_id,
+            Position::LEN,
+            &[POSITION_SEED, market_key.as_ref(), position.owner.as_ref(), &[position_bump]],
+        )?;
+        Position::pack(position, &mut position_account.data.borrow_mut()).at_account(index)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{install_test_runtime, sample_market, test_accounts};
+    use borsh::BorshDeserialize;
+    use solana_program::program::get_return_data;
+
+    #[test]
+    fn test_export_market_snapshot() {
+        install_test_runtime();
+        let program_id = Pubkey::new_unique();
+        let (market_key, holder) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let market = sample_market(Pubkey::new_unique());
+        let (position_key, bump) = find_position_address(&program_id, &market_key, &holder);
+        let position = Position { balance: 12, ..Position::new(holder, bump) };
+        let keys = [market_key, position_key];
+        let mut lamports = [0, 0];
+        let mut data = vec![vec![0u8; Market::LEN], vec![0u8; Position::LEN]];
+        Market::pack(market.clone(), &mut data[0]).unwrap();
+        Position::pack(position.clone(), &mut data[1]).unwrap();
+        let accounts = test_accounts(&keys, &mut lamports, &mut data, &program_id, false);
+
+        process_export_market_snapshot(&program_id, &accounts).unwrap();
+        let (_, exported) = get_return_data().unwrap();
+        let snapshot = MarketSnapshot::try_from_slice(&exported).unwrap();
+        assert_eq!(snapshot, MarketSnapshot { market, positions: vec![position] });
+    }
+}
@@ -0,0 +1,314 @@
+//! Market settings a subject controls directly: minimum hold, sell tax,
+//! pausing, router policy, message fee and succession.
+
+use super::accounts::{check_sell_tax, check_signer, load_market};
+use super::log_event;
+use crate::{
+    AtAccount, ErrorReport, FriendtechError, Market, MarketPause, MarketPauseEvent, RouterPolicy, SuccessionEvent,
+    MARKET_PAUSE_LOG_TAG, MAX_MIN_HOLD_DURATION, MAX_ROUTERS, MAX_SUCCESSION_DELAY, NO_ACCOUNT, SUCCESSION_LOG_TAG,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+pub(super) fn process_set_min_hold_duration(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    min_hold_duration: i64,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let subject = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+
+    check_signer(subject).at_account(0)?;
+    let mut market = load_market(program_id, market_account).at_account(1)?;
+    if market.authority != *subject.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    if !(0..=MAX_MIN_HOLD_DURATION).contains(&min_hold_duration) {
+        return Err(ErrorReport::at(ProgramError::InvalidArgument, NO_ACCOUNT)
+            .values(MAX_MIN_HOLD_DURATION as u64, min_hold_duration as u64));
+    }
+    market.min_hold_duration = min_hold_duration;
+    Market::pack(market, &mut market_account.data.borrow_mut()).at_account(1)
+}
+
+pub(super) fn process_set_sell_tax(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    sell_tax_bps: u16,
+    sell_tax_period: i64,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let subject = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+
+    check_signer(subject).at_account(0)?;
+    let mut market = load_market(program_id, market_account).at_account(1)?;
+    if market.authority != *subject.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    check_sell_tax(sell_tax_bps, sell_tax_period)?;
+    // Holders bought under the current tax, so raising it takes a proposal
+    // they can veto.
+    let turns_off = sell_tax_bps == 0 || sell_tax_period == 0;
+    let tax_on = market.sell_tax_bps > 0 && market.sell_tax_period > 0;
+    if !turns_off && (!tax_on || sell_tax_bps > market.sell_tax_bps || sell_tax_period > market.sell_tax_period) {
+        return Err(ErrorReport::at(FriendtechError::SellTaxIncrease, NO_ACCOUNT)
+            .values(market.sell_tax_bps.into(), sell_tax_bps.into()));
+    }
+    market.sell_tax_bps = sell_tax_bps;
+    market.sell_tax_period = sell_tax_period;
+    Market::pack(market, &mut market_account.data.borrow_mut()).at_account(1)
+}
+
+pub(super) fn process_set_market_paused(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    paused: bool,
+    allow_sells: bool,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let subject = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+
+    check_signer(subject).at_account(0)?;
+    let mut market = load_market(program_id, market_account).at_account(1)?;
+    if market.authority != *subject.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    market.pause = match (paused, allow_sells) {
+        (false, _) => MarketPause::Unpaused,
+        (true, true) => MarketPause::SellsOnly,
+        (true, false) => MarketPause::Halted,
+    };
+    let event = MarketPauseEvent { market: *market_account.key, pause: market.pause };
+    Market::pack(market, &mut market_account.data.borrow_mut()).at_account(1)?;
+    log_event(MARKET_PAUSE_LOG_TAG, &event)?;
+    Ok(())
+}
+
+pub(super) fn process_set_router_policy(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    policy: RouterPolicy,
+    routers: Vec<Pubkey>,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let subject = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+
+    check_signer(subject).at_account(0)?;
+    let mut market = load_market(program_id, market_account).at_account(1)?;
+    if market.authority != *subject.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    if routers.len() > MAX_ROUTERS {
+        return Err(
+            ErrorReport::at(ProgramError::InvalidArgument, NO_ACCOUNT).values(MAX_ROUTERS as u64, routers.len() as u64)
+        );
+    }
+    market.router_policy = policy;
+    market.routers = [Pubkey::default(); MAX_ROUTERS];
+    market.routers[..routers.len()].copy_from_slice(&routers);
+    Market::pack(market, &mut market_account.data.borrow_mut()).at_account(1)
+}
+
+pub(super) fn process_set_message_fee(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    message_fee: u64,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let subject = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+
+    check_signer(subject).at_account(0)?;
+    let mut market = load_market(program_id, market_account).at_account(1)?;
+    if market.authority != *subject.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    market.message_fee = message_fee;
+    Market::pack(market, &mut market_account.data.borrow_mut()).at_account(1)
+}
+
+pub(super) fn process_nominate_successor(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    successor: Option<Pubkey>,
+    delay: i64,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let subject = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+
+    check_signer(subject).at_account(0)?;
+    let mut market = load_market(program_id, market_account).at_account(1)?;
+    if market.authority != *subject.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    if !(0..=MAX_SUCCESSION_DELAY).contains(&delay) {
+        return Err(ErrorReport::at(ProgramError::InvalidArgument, NO_ACCOUNT)
+            .values(MAX_SUCCESSION_DELAY as u64, delay as u64));
+    }
+    // Without a delay the nominee may accept at once, which needs no clock.
+    market.succession_after =
+        if successor.is_some() && delay > 0 { Clock::get()?.unix_timestamp.saturating_add(delay) } else { 0 };
+    market.successor = successor;
+    log_succession(market_account, market)
+}
+
+pub(super) fn process_accept_subject_role(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let successor = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+
+    check_signer(successor).at_account(0)?;
+    let mut market = load_market(program_id, market_account).at_account(1)?;
+    if market.successor != Some(*successor.key) {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    if market.succession_after > 0 {
+        let now = Clock::get()?.unix_timestamp;
+        if now < market.succession_after {
+            return Err(ErrorReport::at(FriendtechError::SuccessionLocked, NO_ACCOUNT)
+                .values(market.succession_after as u64, now as u64));
+        }
+    }
+    market.authority = *successor.key;
+    market.successor = None;
+    market.succession_after = 0;
+    log_succession(market_account, market)
+}
+
+/// Stores `market` and logs its succession state.
+fn log_succession(market_account: &AccountInfo, market: Market) -> Result<(), ErrorReport> {
+    let event = SuccessionEvent {
+        market: *market_account.key,
+        authority: market.authority,
+        successor: market.successor,
+        succession_after: market.succession_after,
+    };
+    Market::pack(market, &mut market_account.data.borrow_mut()).at_account(1)?;
+    log_event(SUCCESSION_LOG_TAG, &event)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::sample_market;
+
+    #[test]
+    fn test_set_router_policy() {
+        let program_id = Pubkey::new_unique();
+        let (subject, market_key) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (mut subject_lamports, mut market_lamports) = (0, 0);
+        let mut subject_data = vec![];
+        let mut market_data = vec![0u8; Market::LEN];
+        Market::pack(sample_market(subject), &mut market_data).unwrap();
+        let owner = Pubkey::new_unique();
+        let subject_account =
+            AccountInfo::new(&subject, true, false, &mut subject_lamports, &mut subject_data, &owner, false, 0);
+        let market_account =
+            AccountInfo::new(&market_key, false, true, &mut market_lamports, &mut market_data, &program_id, false, 0);
+        let accounts = [subject_account, market_account];
+
+        let routers = vec![Pubkey::new_unique(), Pubkey::new_unique()];
+        process_set_router_policy(&program_id, &accounts, RouterPolicy::Allowlist, routers.clone()).unwrap();
+        let market = Market::unpack(&accounts[1].data.borrow()).unwrap();
+        assert_eq!(market.router_policy, RouterPolicy::Allowlist);
+        assert_eq!(market.routers, [routers[0], routers[1], Pubkey::default(), Pubkey::default()]);
+
+        // A new list replaces the old one.
+        process_set_router_policy(&program_id, &accounts, RouterPolicy::DirectOnly, vec![]).unwrap();
+        let market = Market::unpack(&accounts[1].data.borrow()).unwrap();
+        assert_eq!(
+            (market.router_policy, market.routers),
+            (RouterPolicy::DirectOnly, [Pubkey::default(); MAX_ROUTERS])
+        );
+
+        let too_many = vec![Pubkey::new_unique(); MAX_ROUTERS + 1];
+        let report = process_set_router_policy(&program_id, &accounts, RouterPolicy::Allowlist, too_many).unwrap_err();
+        assert_eq!((report.error, report.log.actual), (ProgramError::InvalidArgument, MAX_ROUTERS as u64 + 1));
+    }
+
+    #[test]
+    fn test_set_sell_tax_only_lowers() {
+        let program_id = Pubkey::new_unique();
+        let (subject, market_key) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (mut subject_lamports, mut market_lamports) = (0, 0);
+        let mut subject_data = vec![];
+        let mut market_data = vec![0u8; Market::LEN];
+        let market = Market { sell_tax_bps: 1_000, sell_tax_period: 3_600, ..sample_market(subject) };
+        Market::pack(market, &mut market_data).unwrap();
+        let owner = Pubkey::new_unique();
+        let subject_account =
+            AccountInfo::new(&subject, true, false, &mut subject_lamports, &mut subject_data, &owner, false, 0);
+        let market_account =
+            AccountInfo::new(&market_key, false, true, &mut market_lamports, &mut market_data, &program_id, false, 0);
+        let accounts = [subject_account, market_account];
+
+        // Holders bought under the current tax, so neither a higher rate nor
+        // a longer period applies without a proposal.
+        let report = process_set_sell_tax(&program_id, &accounts, 1_001, 3_600).unwrap_err();
+        assert_eq!(
+            (report.error, report.log.expected, report.log.actual),
+            (FriendtechError::SellTaxIncrease.into(), 1_000, 1_001)
+        );
+        let report = process_set_sell_tax(&program_id, &accounts, 1_000, 3_601).unwrap_err();
+        assert_eq!(report.error, FriendtechError::SellTaxIncrease.into());
+
+        process_set_sell_tax(&program_id, &accounts, 500, 600).unwrap();
+        let market = Market::unpack(&accounts[1].data.borrow()).unwrap();
+        assert_eq!((market.sell_tax_bps, market.sell_tax_period), (500, 600));
+        process_set_sell_tax(&program_id, &accounts, 0, 0).unwrap();
+        // Once off, the tax only comes back through a proposal.
+        let report = process_set_sell_tax(&program_id, &accounts, 100, 60).unwrap_err();
+        assert_eq!(report.error, FriendtechError::SellTaxIncrease.into());
+    }
+
+    #[test]
+    fn test_subject_succession() {
+        let program_id = Pubkey::new_unique();
+        let (subject, successor, market_key) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let (mut subject_lamports, mut successor_lamports, mut market_lamports) = (0, 0, 0);
+        let (mut subject_data, mut successor_data) = (vec![], vec![]);
+        let mut market_data = vec![0u8; Market::LEN];
+        Market::pack(sample_market(subject), &mut market_data).unwrap();
+        let owner = Pubkey::new_unique();
+        let subject_account =
+            AccountInfo::new(&subject, true, false, &mut subject_lamports, &mut subject_data, &owner, false, 0);
+        let successor_account =
+            AccountInfo::new(&successor, true, false, &mut successor_lamports, &mut successor_data, &owner, false, 0);
+        let market_account =
+            AccountInfo::new(&market_key, false, true, &mut market_lamports, &mut market_data, &program_id, false, 0);
+        let by_subject = [subject_account, market_account.clone()];
+        let by_successor = [successor_account, market_account];
+
+        // Only the authority nominates, and only its nominee accepts.
+        let report = process_nominate_successor(&program_id, &by_successor, Some(successor), 0).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::Unauthorized.into(), 0));
+        let report = process_nominate_successor(&program_id, &by_subject, Some(successor), MAX_SUCCESSION_DELAY + 1)
+            .unwrap_err();
+        assert_eq!(report.error, ProgramError::InvalidArgument);
+        process_nominate_successor(&program_id, &by_subject, Some(successor), 0).unwrap();
+        let report = process_accept_subject_role(&program_id, &by_subject).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::Unauthorized.into(), 0));
+
+        process_accept_subject_role(&program_id, &by_successor).unwrap();
+        let market = Market::unpack(&by_subject[1].data.borrow()).unwrap();
+        assert_eq!((market.subject, market.authority, market.successor), (subject, successor, None));
+
+        // The old key lost the subject's role; the new one holds it.
+        let report = process_set_min_hold_duration(&program_id, &by_subject, 60).unwrap_err();
+        assert_eq!(report.error, FriendtechError::Unauthorized.into());
+        process_set_min_hold_duration(&program_id, &by_successor, 60).unwrap();
+        assert!(process_accept_subject_role(&program_id, &by_successor).is_err());
+    }
+}
@@ -0,0 +1,255 @@
+//! The SOL treasury and the stake accounts it delegates.
+
+use super::accounts::{check_feature, check_signer, load_config};
+use crate::{
+    find_treasury_address, find_treasury_stake_address, AtAccount, ErrorReport, FriendtechError,
+    FEATURE_TREASURY_STAKING, TREASURY_SEED, TREASURY_STAKE_SEED,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+    stake::{
+        instruction as stake_instruction,
+        state::{Authorized, Lockup, StakeStateV2},
+    },
+    system_instruction, system_program,
+    sysvar::Sysvar,
+};
+
+pub(super) fn process_delegate_treasury_stake(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    stake_index: u64,
+    lamports: u64,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let admin = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let treasury = next_account_info(accounts_iter)?;
+    let stake_account = next_account_info(accounts_iter)?;
+    let vote_account = next_account_info(accounts_iter)?;
+    let clock_sysvar = next_account_info(accounts_iter)?;
+    let stake_history_sysvar = next_account_info(accounts_iter)?;
+    let stake_config = next_account_info(accounts_iter)?;
+    let rent_sysvar = next_account_info(accounts_iter)?;
+    let stake_program = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+
+    check_signer(admin).at_account(0)?;
+    let config = load_config(program_id, config_account).at_account(1)?;
+    if config.admin != *admin.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    check_feature(&config, FEATURE_TREASURY_STAKING)?;
+    let treasury_bump = find_treasury_bump(program_id, treasury).at_account(2)?;
+    let stake_bump = find_treasury_stake_bump(program_id, stake_index, stake_account).at_account(3)?;
+    if *stake_program.key != solana_program::stake::program::id() {
+        return Err(ErrorReport::at(ProgramError::IncorrectProgramId, 9));
+    }
+    if *system_program_account.key != system_program::id() {
+        return Err(ErrorReport::at(ProgramError::IncorrectProgramId, 10));
+    }
+    check_treasury_surplus(treasury, lamports)?;
+
+    let treasury_seeds: &[&[u8]] = &[TREASURY_SEED, &[treasury_bump]];
+    let index_bytes = stake_index.to_le_bytes();
+    let stake_seeds: &[&[u8]] = &[TREASURY_STAKE_SEED, &index_bytes, &[stake_bump]];
+    let ix = system_instruction::create_account(
+        treasury.key,
+        stake_account.key,
+        lamports,
+        StakeStateV2::size_of() as u64,
+        stake_program.key,
+    );
+    invoke_signed(
+        &ix,
+        &[treasury.clone(), stake_account.clone(), system_program_account.clone()],
+        &[treasury_seeds, stake_seeds],
+    )?;
+    let authorized = Authorized { staker: *treasury.key, withdrawer: *treasury.key };
+    let ix = stake_instruction::initialize(stake_account.key, &authorized, &Lockup::default());
+    invoke(&ix, &[stake_account.clone(), rent_sysvar.clone(), stake_program.clone()])?;
+    let ix = stake_instruction::delegate_stake(stake_account.key, treasury.key, vote_account.key);
+    invoke_signed(
+        &ix,
+        &[
+            stake_account.clone(),
+            vote_account.clone(),
+            clock_sysvar.clone(),
+            stake_history_sysvar.clone(),
+            stake_config.clone(),
+            treasury.clone(),
+            stake_program.clone(),
+        ],
+        &[treasury_seeds],
+    )?;
+    Ok(())
+}
+
+pub(super) fn process_deactivate_treasury_stake(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    stake_index: u64,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let admin = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let treasury = next_account_info(accounts_iter)?;
+    let stake_account = next_account_info(accounts_iter)?;
+    let clock_sysvar = next_account_info(accounts_iter)?;
+    let stake_program = next_account_info(accounts_iter)?;
+
+    check_signer(admin).at_account(0)?;
+    let config = load_config(program_id, config_account).at_account(1)?;
+    if config.admin != *admin.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    let treasury_bump = find_treasury_bump(program_id, treasury).at_account(2)?;
+    find_treasury_stake_bump(program_id, stake_index, stake_account).at_account(3)?;
+    if *stake_program.key != solana_program::stake::program::id() {
+        return Err(ErrorReport::at(ProgramError::IncorrectProgramId, 5));
+    }
+
+    let ix = stake_instruction::deactivate_stake(stake_account.key, treasury.key);
+    invoke_signed(
+        &ix,
+        &[stake_account.clone(), clock_sysvar.clone(), treasury.clone(), stake_program.clone()],
+        &[&[TREASURY_SEED, &[treasury_bump]]],
+    )?;
+    Ok(())
+}
+
+pub(super) fn process_withdraw_treasury_stake(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    stake_index: u64,
+    lamports: u64,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let admin = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let treasury = next_account_info(accounts_iter)?;
+    let stake_account = next_account_info(accounts_iter)?;
+    let clock_sysvar = next_account_info(accounts_iter)?;
+    let stake_history_sysvar = next_account_info(accounts_iter)?;
+    let stake_program = next_account_info(accounts_iter)?;
+
+    check_signer(admin).at_account(0)?;
+    let config = load_config(program_id, config_account).at_account(1)?;
+    if config.admin != *admin.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    let treasury_bump = find_treasury_bump(program_id, treasury).at_account(2)?;
+    find_treasury_stake_bump(program_id, stake_index, stake_account).at_account(3)?;
+    if *stake_program.key != solana_program::stake::program::id() {
+        return Err(ErrorReport::at(ProgramError::IncorrectProgramId, 6));
+    }
+
+    let ix = stake_instruction::withdraw(stake_account.key, treasury.key, treasury.key, lamports, None);
+    invoke_signed(
+        &ix,
+        &[
+            stake_account.clone(),
+            treasury.clone(),
+            clock_sysvar.clone(),
+            stake_history_sysvar.clone(),
+            stake_program.clone(),
+        ],
+        &[&[TREASURY_SEED, &[treasury_bump]]],
+    )?;
+    Ok(())
+}
+
+pub(super) fn process_withdraw_treasury(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    lamports: u64,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let admin = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let treasury = next_account_info(accounts_iter)?;
+    let recipient = next_account_info(accounts_iter)?;
+    let system_program_account = next_account_info(accounts_iter)?;
+
+    check_signer(admin).at_account(0)?;
+    let config = load_config(program_id, config_account).at_account(1)?;
+    if config.admin != *admin.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    let treasury_bump = find_treasury_bump(program_id, treasury).at_account(2)?;
+    check_treasury_surplus(treasury, lamports)?;
+
+    let ix = system_instruction::transfer(treasury.key, recipient.key, lamports);
+    invoke_signed(
+        &ix,
+        &[treasury.clone(), recipient.clone(), system_program_account.clone()],
+        &[&[TREASURY_SEED, &[treasury_bump]]],
+    )?;
+    Ok(())
+}
+
+/// Bump of the treasury PDA, after checking `treasury` is it.
+pub(super) fn find_treasury_bump(program_id: &Pubkey, treasury: &AccountInfo) -> Result<u8, ProgramError> {
+    let (treasury_key, bump) = find_treasury_address(program_id);
+    if treasury_key != *treasury.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(bump)
+}
+
+/// Bump of the treasury stake PDA for `stake_index`, after checking
+/// `stake_account` is it.
+pub(super) fn find_treasury_stake_bump(
+    program_id: &Pubkey,
+    stake_index: u64,
+    stake_account: &AccountInfo,
+) -> Result<u8, ProgramError> {
+    let (stake_key, bump) = find_treasury_stake_address(program_id, stake_index);
+    if stake_key != *stake_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(bump)
+}
+
+/// Checks that the treasury can spend `lamports` and stay rent exempt.
+pub(super) fn check_treasury_surplus(treasury: &AccountInfo, lamports: u64) -> Result<(), ErrorReport> {
+    let surplus = treasury.lamports().saturating_sub(Rent::get()?.minimum_balance(0));
+    if lamports == 0 || lamports > surplus {
+        return Err(ErrorReport::at(FriendtechError::InsufficientFunds, 2).values(lamports, surplus));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{install_test_runtime, sample_config, test_accounts};
+    use crate::{find_config_address, Config};
+    use solana_program::program_pack::Pack;
+
+    #[test]
+    fn test_withdraw_treasury() {
+        install_test_runtime();
+        let program_id = Pubkey::new_unique();
+        let admin = Pubkey::new_unique();
+        let (config_key, bump) = find_config_address(&program_id);
+        let rent_exempt = Rent::default().minimum_balance(0);
+        let keys =
+            [admin, config_key, find_treasury_address(&program_id).0, Pubkey::new_unique(), system_program::id()];
+        let mut lamports = [0, 0, rent_exempt + 5_000, 0, 0];
+        let mut data = vec![vec![]; 5];
+        data[1] = vec![0u8; Config::LEN];
+        Config::pack(Config { bump, ..sample_config(admin) }, &mut data[1]).unwrap();
+        let accounts = test_accounts(&keys, &mut lamports, &mut data, &program_id, true);
+
+        // Only the surplus above the treasury's rent exemption can leave.
+        let report = process_withdraw_treasury(&program_id, &accounts, 5_001).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::InsufficientFunds.into(), 2));
+        process_withdraw_treasury(&program_id, &accounts, 5_000).unwrap();
+        assert_eq!((accounts[2].lamports(), accounts[3].lamports()), (rent_exempt, 5_000));
+    }
+}
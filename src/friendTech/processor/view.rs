@@ -0,0 +1,180 @@
+//! Read-only views of market state for other programs to call by CPI, and
+//! market reports and holding proofs logged for off-chain services.
+
+use super::accounts::{check_signer, load_market, load_position};
+use super::log_event;
+use crate::{
+    find_position_address, market_report, AtAccount, ErrorReport, FriendtechError, HoldingProofEvent, MarketFees,
+    HOLDING_PROOF_LOG_TAG, MARKET_REPORT_LOG_TAG,
+};
+use borsh::BorshSerialize;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    program::set_return_data,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+pub(super) fn process_view_supply(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let market_account = next_account_info(accounts_iter)?;
+
+    let market = load_market(program_id, market_account).at_account(0)?;
+    set_return_data(&market.supply.try_to_vec()?);
+    Ok(())
+}
+
+pub(super) fn process_view_holder_balance(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    owner: Pubkey,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let market_account = next_account_info(accounts_iter)?;
+    let position_account = next_account_info(accounts_iter)?;
+
+    load_market(program_id, market_account).at_account(0)?;
+    let balance = if position_account.owner == program_id {
+        load_position(program_id, &owner, market_account.key, position_account, None).at_account(1)?.balance
+    } else {
+        let (position_key, _) = find_position_address(program_id, market_account.key, &owner);
+        if position_key != *position_account.key {
+            return Err(ErrorReport::at(ProgramError::InvalidSeeds, 1));
+        }
+        0
+    };
+    set_return_data(&balance.try_to_vec()?);
+    Ok(())
+}
+
+pub(super) fn process_view_fees(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let market_account = next_account_info(accounts_iter)?;
+
+    let market = load_market(program_id, market_account).at_account(0)?;
+    let fees = MarketFees {
+        protocol_fee_bps: market.protocol_fee_bps,
+        subject_fee_bps: market.subject_fee_bps,
+        protocol_fees: market.protocol_fees,
+        subject_fees: market.subject_fees,
+    };
+    set_return_data(&fees.try_to_vec()?);
+    Ok(())
+}
+
+pub(super) fn process_collect_market_report(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let market_account = next_account_info(accounts_iter)?;
+
+    let market = load_market(program_id, market_account).at_account(0)?;
+    let report = market_report(market_account.key, &market, Clock::get()?.unix_timestamp)?;
+    log_event(MARKET_REPORT_LOG_TAG, &report)?;
+    Ok(())
+}
+
+pub(super) fn process_prove_holding(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    min_balance: u64,
+    nonce: [u8; 32],
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let owner = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+    let position_account = next_account_info(accounts_iter)?;
+
+    check_signer(owner).at_account(0)?;
+    load_market(program_id, market_account).at_account(1)?;
+    let position = load_position(program_id, owner.key, market_account.key, position_account, None).at_account(2)?;
+    if position.balance < min_balance {
+        return Err(ErrorReport::at(FriendtechError::InsufficientFunds, 2).values(min_balance, position.balance));
+    }
+    let proof = HoldingProofEvent {
+        market: *market_account.key,
+        owner: *owner.key,
+        min_balance,
+        nonce,
+        slot: Clock::get()?.slot,
+    };
+    log_event(HOLDING_PROOF_LOG_TAG, &proof)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::sample_market;
+    use crate::{Market, Position};
+    use solana_program::program_pack::Pack;
+
+    #[test]
+    fn test_view_holder_balance_checks_position() {
+        let program_id = Pubkey::new_unique();
+        let (market_key, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (position_key, bump) = find_position_address(&program_id, &market_key, &owner);
+        let position = Position { balance: 5, ..Position::new(owner, bump) };
+        let mut market_data = vec![0u8; Market::LEN];
+        Market::pack(sample_market(Pubkey::new_unique()), &mut market_data).unwrap();
+        let mut position_data = vec![0u8; Position::LEN];
+        Position::pack(position, &mut position_data).unwrap();
+        let (mut market_lamports, mut lamports) = (0, 0);
+        let system = Pubkey::default();
+        let mut accounts = [
+            AccountInfo::new(&market_key, false, false, &mut market_lamports, &mut market_data, &program_id, false, 0),
+            AccountInfo::new(&position_key, false, false, &mut lamports, &mut position_data, &system, false, 0),
+        ];
+
+        // A position PDA not created yet holds nothing, but it must be the
+        // owner's PDA.
+        assert!(process_view_holder_balance(&program_id, &accounts, owner).is_ok());
+        let report = process_view_holder_balance(&program_id, &accounts, Pubkey::new_unique()).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (ProgramError::InvalidSeeds, 1));
+
+        accounts[1].owner = &program_id;
+        assert!(process_view_holder_balance(&program_id, &accounts, owner).is_ok());
+        let report = process_view_holder_balance(&program_id, &accounts, Pubkey::new_unique()).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::AccountMismatch.into(), 1));
+
+        let impostor = Pubkey::new_unique();
+        accounts[0].owner = &impostor;
+        let report = process_view_holder_balance(&program_id, &accounts, owner).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::IncorrectOwner.into(), 0));
+    }
+
+    #[test]
+    fn test_prove_holding_checks_balance() {
+        let program_id = Pubkey::new_unique();
+        let (market_key, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (position_key, bump) = find_position_address(&program_id, &market_key, &owner);
+        let mut market_data = vec![0u8; Market::LEN];
+        Market::pack(sample_market(Pubkey::new_unique()), &mut market_data).unwrap();
+        let mut position_data = vec![0u8; Position::LEN];
+        Position::pack(Position { balance: 5, ..Position::new(owner, bump) }, &mut position_data).unwrap();
+        let (mut owner_lamports, mut market_lamports, mut position_lamports) = (0, 0, 0);
+        let mut owner_data = vec![];
+        let system = Pubkey::default();
+        let mut accounts = [
+            AccountInfo::new(&owner, false, false, &mut owner_lamports, &mut owner_data, &system, false, 0),
+            AccountInfo::new(&market_key, false, false, &mut market_lamports, &mut market_data, &program_id, false, 0),
+            AccountInfo::new(
+                &position_key,
+                false,
+                false,
+                &mut position_lamports,
+                &mut position_data,
+                &program_id,
+                false,
+                0,
+            ),
+        ];
+
+        let report = process_prove_holding(&program_id, &accounts, 5, [1; 32]).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (ProgramError::MissingRequiredSignature, 0));
+        accounts[0].is_signer = true;
+        let report = process_prove_holding(&program_id, &accounts, 6, [1; 32]).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::InsufficientFunds.into(), 2));
+        assert_eq!((report.log.expected, report.log.actual), (6, 5));
+    }
+}
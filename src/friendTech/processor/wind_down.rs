@@ -0,0 +1,123 @@
+//! `StartWindDown` and `RedeemShares`, the program's staged exit: sells only
+//! until the wind-down deadline, then pro-rata redemptions from the vaults.
+
+use super::accounts::{
+    check_distinct, check_signer, check_token_program, check_unlocked, check_vault, load_config, load_holding,
+    load_market, load_position, token_balance, transfer_from_vault,
+};
+use crate::{
+    accrue_rewards, redemption_amount, update_reward_index, wind_down_stage, AtAccount, Config, ErrorReport,
+    FriendtechError, Holding, Market, Position, WindDownStage, MIN_WIND_DOWN_NOTICE, NO_ACCOUNT,
+};
+use borsh::BorshSerialize;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    program::set_return_data,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+pub(super) fn process_start_wind_down(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    deadline: i64,
+) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let admin = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+
+    check_signer(admin).at_account(0)?;
+    let mut config = load_config(program_id, config_account).at_account(1)?;
+    if config.admin != *admin.key {
+        return Err(ErrorReport::at(FriendtechError::Unauthorized, 0));
+    }
+    if config.wind_down_deadline.is_some() {
+        return Err(ErrorReport::at(FriendtechError::WindingDown, 1));
+    }
+    let earliest = Clock::get()?.unix_timestamp.saturating_add(MIN_WIND_DOWN_NOTICE);
+    if deadline < earliest {
+        return Err(ErrorReport::at(ProgramError::InvalidArgument, NO_ACCOUNT).values(earliest as u64, deadline as u64));
+    }
+    config.wind_down_deadline = Some(deadline);
+    Config::pack(config, &mut config_account.data.borrow_mut()).at_account(1)
+}
+
+pub(super) fn process_redeem_shares(program_id: &Pubkey, accounts: &[AccountInfo]) -> Result<(), ErrorReport> {
+    let accounts_iter = &mut accounts.iter();
+    let holder = next_account_info(accounts_iter)?;
+    let market_account = next_account_info(accounts_iter)?;
+    let position_account = next_account_info(accounts_iter)?;
+    let token_account = next_account_info(accounts_iter)?;
+    let vault = next_account_info(accounts_iter)?;
+    let token_program = next_account_info(accounts_iter)?;
+    let config_account = next_account_info(accounts_iter)?;
+    let holding_account = next_account_info(accounts_iter)?;
+
+    check_signer(holder).at_account(0)?;
+    let mut market = load_market(program_id, market_account).at_account(1)?;
+    check_vault(&market, vault).at_account(4)?;
+    check_token_program(token_program).at_account(5)?;
+    check_distinct(&[(token_account, 3), (vault, 4)])?;
+    let config = load_config(program_id, config_account).at_account(6)?;
+    let mut position =
+        load_position(program_id, holder.key, market_account.key, position_account, None).at_account(2)?;
+    let now = Clock::get()?.unix_timestamp;
+    check_unlocked(&position, now).at_account(2)?;
+    if position.balance == 0 {
+        return Err(ErrorReport::at(FriendtechError::InsufficientFunds, 2).values(1, 0));
+    }
+    if wind_down_stage(&config, now) != WindDownStage::Redeeming {
+        let deadline = config.wind_down_deadline.unwrap_or(i64::MAX);
+        return Err(ErrorReport::at(FriendtechError::RedemptionNotOpen, 6).values(deadline as u64, now as u64));
+    }
+
+    let amount = redemption_amount(&market, token_balance(vault).at_account(4)?, position.balance);
+    transfer_from_vault(&market, market_account, vault, token_account, token_program, amount)?;
+    update_reward_index(&mut market, now)?;
+    accrue_rewards(&market, &mut position)?;
+    market.supply -= position.balance;
+    market.holders = market.holders.saturating_sub(1);
+    position.balance = 0;
+    if let Some(mut holding) = load_holding(program_id, holder.key, &market.subject, holding_account).at_account(7)? {
+        holding.markets = holding.markets.saturating_sub(1);
+        Holding::pack(holding, &mut holding_account.data.borrow_mut()).at_account(7)?;
+    }
+    Position::pack(position, &mut position_account.data.borrow_mut()).at_account(2)?;
+    Market::pack(market, &mut market_account.data.borrow_mut()).at_account(1)?;
+    set_return_data(&amount.try_to_vec()?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::find_config_address;
+    use crate::tests::sample_config;
+
+    #[test]
+    fn test_start_wind_down_checks_admin() {
+        let program_id = Pubkey::new_unique();
+        let (admin, (config_key, bump)) = (Pubkey::new_unique(), find_config_address(&program_id));
+        let mut config = Config { bump, ..sample_config(admin) };
+        config.wind_down_deadline = Some(1_000);
+        let mut config_data = vec![0u8; Config::LEN];
+        Config::pack(config, &mut config_data).unwrap();
+        let (mut admin_lamports, mut config_lamports, mut admin_data) = (0, 0, vec![]);
+        let system = Pubkey::default();
+        let mut accounts = [
+            AccountInfo::new(&admin, true, false, &mut admin_lamports, &mut admin_data, &system, false, 0),
+            AccountInfo::new(&config_key, false, true, &mut config_lamports, &mut config_data, &program_id, false, 0),
+        ];
+
+        // A wind-down cannot be restarted, e.g. to move its deadline.
+        let report = process_start_wind_down(&program_id, &accounts, 2_000).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::WindingDown.into(), 1));
+        let impostor = Pubkey::new_unique();
+        accounts[0].key = &impostor;
+        let report = process_start_wind_down(&program_id, &accounts, 2_000).unwrap_err();
+        assert_eq!((report.error, report.log.account_index), (FriendtechError::Unauthorized.into(), 0));
+    }
+}
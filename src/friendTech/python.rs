@@ -0,0 +1,196 @@
+//! pyo3 bindings for trading bots: quotes, PDA derivation and instruction
+//! encoding, all backed by the same code the program runs. Build with
+//! `maturin build --features friendtech-py`.
+//!
+//! Addresses are base58 strings, account data is `bytes`, and instructions
+//! are returned as `(program_id, [(pubkey, is_signer, is_writable)], data)`
+//! tuples ready for solders or solana-py.
+
+use crate::{sdk, Config, InterfaceFee, Market, PriceImpact, TradeSettlement};
+use pyo3::{exceptions::PyValueError, prelude::*};
+use solana_program::{instruction::Instruction, program_pack::Pack, pubkey::Pubkey};
+use std::str::FromStr;
+
+type PyInstruction = (String, Vec<(String, bool, bool)>, Vec<u8>);
+
+/// A quoted trade, mirroring `TradeSettlement` and `PriceImpact`.
+#[pyclass(get_all)]
+pub struct Quote {
+    pub gross_price: u64,
+    pub protocol_fee: u64,
+    pub subject_fee: u64,
+    pub interface_fee: u64,
+    pub net_amount: u64,
+    pub new_supply: u64,
+    pub pre_trade_price: u64,
+    pub post_trade_price: u64,
+    pub price_impact_bps: i64,
+}
+
+impl From<(TradeSettlement, PriceImpact)> for Quote {
+    fn from((settlement, impact): (TradeSettlement, PriceImpact)) -> Self {
+        Quote {
+            gross_price: settlement.gross_price,
+            protocol_fee: settlement.protocol_fee,
+            subject_fee: settlement.subject_fee,
+            interface_fee: settlement.interface_fee,
+            net_amount: settlement.net_amount,
+            new_supply: settlement.new_supply,
+            pre_trade_price: impact.pre_trade_price,
+            post_trade_price: impact.post_trade_price,
+            price_impact_bps: impact.price_impact_bps,
+        }
+    }
+}
+
+/// Quotes a buy of `amount` shares from raw config and market account data.
+#[pyfunction]
+fn quote_buy(config_data: &[u8], market_data: &[u8], amount: u64) -> PyResult<Quote> {
+    let (config, market) = unpack_accounts(config_data, market_data)?;
+    let settlement = crate::quote_buy(&config, &market, amount).map_err(to_py_error)?;
+    let impact = crate::price_impact(&market, amount, true).map_err(to_py_error)?;
+    Ok(Quote::from((settlement, impact)))
+}
+
+/// Quotes a sale of `amount` shares from raw config and market account data.
+#[pyfunction]
+fn quote_sell(config_data: &[u8], market_data: &[u8], amount: u64) -> PyResult<Quote> {
+    let (config, market) = unpack_accounts(config_data, market_data)?;
+    let settlement = crate::quote_sell(&config, &market, amount).map_err(to_py_error)?;
+    let impact = crate::price_impact(&market, amount, false).map_err(to_py_error)?;
+    Ok(Quote::from((settlement, impact)))
+}
+
+#[pyfunction]
+fn find_config_address(program_id: &str) -> PyResult<(String, u8)> {
+    Ok(to_py_address(crate::find_config_address(&parse_pubkey(program_id)?)))
+}
+
+#[pyfunction]
+fn find_market_address(program_id: &str, subject: &str, quote_mint: &str) -> PyResult<(String, u8)> {
+    let (subject, quote_mint) = (parse_pubkey(subject)?, parse_pubkey(quote_mint)?);
+    Ok(to_py_address(crate::find_market_address(&parse_pubkey(program_id)?, &subject, &quote_mint)))
+}
+
+#[pyfunction]
+fn find_vault_address(program_id: &str, market: &str) -> PyResult<(String, u8)> {
+    Ok(to_py_address(crate::find_vault_address(&parse_pubkey(program_id)?, &parse_pubkey(market)?)))
+}
+
+#[pyfunction]
+fn find_position_address(program_id: &str, market: &str, owner: &str) -> PyResult<(String, u8)> {
+    let (market, owner) = (parse_pubkey(market)?, parse_pubkey(owner)?);
+    Ok(to_py_address(crate::find_position_address(&parse_pubkey(program_id)?, &market, &owner)))
+}
+
+/// Encodes a `BuyShares` instruction, see `sdk::buy_shares`. `interface_fee`
+/// is a `(fee_bps, recipient)` tuple.
+#[pyfunction(signature = (
+    program_id, payer, buyer, subject, quote_mint, buyer_token_account, amount, client_id = None, interface_fee = None,
+    receipt_id = None
+))]
+#[allow(clippy::too_many_arguments)]
+fn buy_shares(
+    program_id: &str,
+    payer: &str,
+    buyer: &str,
+    subject: &str,
+    quote_mint: &str,
+    buyer_token_account: &str,
+    amount: u64,
+    client_id: Option<[u8; 8]>,
+    interface_fee: Option<(u16, &str)>,
+    receipt_id: Option<[u8; 32]>,
+) -> PyResult<PyInstruction> {
+    let ix = sdk::buy_shares(
+        &parse_pubkey(program_id)?,
+        &parse_pubkey(payer)?,
+        &parse_pubkey(buyer)?,
+        &parse_pubkey(subject)?,
+        &parse_pubkey(quote_mint)?,
+        &parse_pubkey(buyer_token_account)?,
+        amount,
+        client_id,
+        parse_interface_fee(interface_fee)?,
+        receipt_id,
+    );
+    Ok(to_py_instruction(ix))
+}
+
+/// Encodes a `SellShares` instruction, see `sdk::sell_shares`.
+#[pyfunction(signature = (
+    program_id, seller, subject, quote_mint, seller_token_account, amount, client_id = None, interface_fee = None,
+    receipt_id = None
+))]
+#[allow(clippy::too_many_arguments)]
+fn sell_shares(
+    program_id: &str,
+    seller: &str,
+    subject: &str,
+    quote_mint: &str,
+    seller_token_account: &str,
+    amount: u64,
+    client_id: Option<[u8; 8]>,
+    interface_fee: Option<(u16, &str)>,
+    receipt_id: Option<[u8; 32]>,
+) -> PyResult<PyInstruction> {
+    let ix = sdk::sell_shares(
+        &parse_pubkey(program_id)?,
+        &parse_pubkey(seller)?,
+        &parse_pubkey(subject)?,
+        &parse_pubkey(quote_mint)?,
+        &parse_pubkey(seller_token_account)?,
+        amount,
+        client_id,
+        parse_interface_fee(interface_fee)?,
+        receipt_id,
+    );
+    Ok(to_py_instruction(ix))
+}
+
+#[pymodule]
+fn friendtech(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<Quote>()?;
+    m.add_function(wrap_pyfunction!(quote_buy, m)?)?;
+    m.add_function(wrap_pyfunction!(quote_sell, m)?)?;
+    m.add_function(wrap_pyfunction!(find_config_address, m)?)?;
+    m.add_function(wrap_pyfunction!(find_market_address, m)?)?;
+    m.add_function(wrap_pyfunction!(find_vault_address, m)?)?;
+    m.add_function(wrap_pyfunction!(find_position_address, m)?)?;
+    m.add_function(wrap_pyfunction!(buy_shares, m)?)?;
+    m.add_function(wrap_pyfunction!(sell_shares, m)?)?;
+    Ok(())
+}
+
+fn unpack_accounts(config_data: &[u8], market_data: &[u8]) -> PyResult<(Config, Market)> {
+    let config = Config::unpack(config_data).map_err(to_py_error)?;
+    let market = Market::unpack(market_data).map_err(to_py_error)?;
+    Ok((config, market))
+}
+
+fn to_py_address((address, bump): (Pubkey, u8)) -> (String, u8) {
+    (address.to_string(), bump)
+}
+
+fn parse_pubkey(address: &str) -> PyResult<Pubkey> {
+    Pubkey::from_str(address).map_err(|_| PyValueError::new_err(format!("invalid address: {address}")))
+}
+
+fn parse_interface_fee(interface_fee: Option<(u16, &str)>) -> PyResult<Option<InterfaceFee>> {
+    interface_fee
+        .map(|(fee_bps, recipient)| Ok(InterfaceFee { fee_bps, recipient: parse_pubkey(recipient)? }))
+        .transpose()
+}
+
+fn to_py_instruction(ix: Instruction) -> PyInstruction {
+    let accounts = ix
+        .accounts
+        .into_iter()
+        .map(|meta| (meta.pubkey.to_string(), meta.is_signer, meta.is_writable))
+        .collect();
+    (ix.program_id.to_string(), accounts, ix.data)
+}
+
+fn to_py_error(error: solana_program::program_error::ProgramError) -> PyErr {
+    PyValueError::new_err(error.to_string())
+}
@@ -0,0 +1,284 @@
+//! Off-chain reconstruction of market state from logged `TradeEvent`s.
+//!
+//! Indexers and tests start a `Replay` from known config, market, position
+//! and stats accounts, then apply every trade in the order it executed, with
+//! the block time and epoch of its transaction. Each trade is re-quoted from
+//! the replayed state, so a logged settlement the program could not have
+//! produced is an error rather than replayed state. The replayed accounts
+//! match the program's own byte for byte as long as no non-trade instruction
+//! changed the config or markets in between, e.g. a new emission rate or a
+//! snapshot import.
+
+use crate::{
+    accrue_fees, dump_fee_bps, quote_buy, quote_sell, record_dump_window_sell, record_volume, roll_volume_window,
+    sdk::events::{decode_event, Event},
+    sell_tax_bps, update_reward_index, weighted_tax_basis, wind_down_stage, Config, Market, Position, Stats,
+    TradeEvent, TradeSettlement, WindDownStage,
+};
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+use std::collections::HashMap;
+
+/// Why a trade could not be replayed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReplayError {
+    /// The trade's market was never passed to `Replay::track`.
+    UnknownMarket(Pubkey),
+    /// The trade does not continue from the replayed supply, so an earlier
+    /// trade of the market is missing or out of order.
+    SupplyGap {
+        expected: u64,
+        actual: u64,
+    },
+    /// The trade sells more shares than the replayed position of the trader
+    /// holds, so the position was never passed to `Replay::track_position`
+    /// or an earlier trade of it is missing.
+    UnknownPosition(Pubkey),
+    /// The trade's logged settlement differs from the one the replayed state
+    /// quotes, so the replay started from different accounts or the log
+    /// was not written by the program.
+    SettlementMismatch {
+        expected: TradeSettlement,
+        actual: TradeSettlement,
+    },
+    Program(ProgramError),
+}
+
+impl From<ProgramError> for ReplayError {
+    fn from(error: ProgramError) -> Self {
+        ReplayError::Program(error)
+    }
+}
+
+/// Markets and global stats as the program holds them after the replayed
+/// trades.
+pub struct Replay {
+    /// Fee switches and wind-down deadline the trades are quoted with.
+    pub config: Config,
+    pub markets: HashMap<Pubkey, Market>,
+    pub stats: Stats,
+    /// Positions by market and owner. Only their balances and sell tax
+    /// basis are replayed, which decide the holder count and sell taxes.
+    pub positions: HashMap<(Pubkey, Pubkey), Position>,
+}
+
+impl Replay {
+    /// Starts from the config and stats accounts as of the first trade to
+    /// replay.
+    pub fn new(config: Config, stats: Stats) -> Self {
+        Replay { config, markets: HashMap::new(), stats, positions: HashMap::new() }
+    }
+
+    /// Adds the market at `address` as of the first trade to replay.
+    pub fn track(&mut self, address: Pubkey, market: Market) {
+        self.markets.insert(address, market);
+    }
+
+    /// Adds a position in the market at `market` as of the first trade to
+    /// replay. Positions never tracked are taken to start empty.
+    pub fn track_position(&mut self, market: Pubkey, position: &Position) {
+        self.positions.insert((market, position.owner), position.clone());
+    }
+
+    /// Applies a trade executed at unix timestamp `now` during `epoch`,
+    /// mirroring the market updates of the instruction that logged it.
+    pub fn apply(&mut self, event: &TradeEvent, now: i64, epoch: u64) -> Result<(), ReplayError> {
+        let market = self.markets.get_mut(&event.market).ok_or(ReplayError::UnknownMarket(event.market))?;
+        let settlement = &event.settlement;
+        let expected = if event.is_buy {
+            market.supply.checked_add(event.amount)
+        } else {
+            market.supply.checked_sub(event.amount)
+        };
+        if expected != Some(settlement.new_supply) {
+            return Err(ReplayError::SupplyGap { expected: expected.unwrap_or(0), actual: settlement.new_supply });
+        }
+        let position =
+            self.positions.entry((event.market, event.trader)).or_insert_with(|| Position::new(event.trader, 0));
+        let new_balance = if event.is_buy {
+            position.balance.checked_add(event.amount)
+        } else {
+            position.balance.checked_sub(event.amount)
+        };
+        let new_balance = new_balance.ok_or(ReplayError::UnknownPosition(event.trader))?;
+
+        roll_volume_window(market, now);
+        let quoted = quote_trade(&self.config, market, position, event, now)?;
+        if quoted != *settlement {
+            return Err(ReplayError::SettlementMismatch { expected: quoted, actual: settlement.clone() });
+        }
+
+        if event.is_buy && position.balance == 0 && event.amount > 0 {
+            market.holders = market.holders.checked_add(1).ok_or(ProgramError::ArithmeticOverflow)?;
+        } else if !event.is_buy && new_balance == 0 && event.amount > 0 {
+            market.holders = market.holders.saturating_sub(1);
+        }
+        if event.is_buy {
+            // Presale shares' sell tax decays from the public open.
+            let tax_basis_at =
+                if now < market.opens_at { market.opens_at } else { weighted_tax_basis(position, event.amount, now) };
+            position.set_tax_basis_at(tax_basis_at);
+        }
+        position.balance = new_balance;
+
+        update_reward_index(market, now)?;
+        if !event.is_buy {
+            record_dump_window_sell(market, event.amount, now);
+        }
+        market.supply = settlement.new_supply;
+        accrue_fees(market, settlement.protocol_fee, settlement.subject_fee)?;
+        self.stats.record_trade(market, settlement, epoch);
+        record_volume(market, settlement.gross_price, now);
+        Ok(())
+    }
+}
+
+/// The settlement the program quotes `event`'s trade from the replayed
+/// `market` and `position`, with the event's interface fee, whose rate is
+/// not logged. A presale buy, the only trade before the market opens, is
+/// priced by the presale account, so only its fee-free shape is checked.
+fn quote_trade(
+    config: &Config,
+    market: &Market,
+    position: &Position,
+    event: &TradeEvent,
+    now: i64,
+) -> Result<TradeSettlement, ProgramError> {
+    let mut quoted = if event.is_buy && now < market.opens_at {
+        let gross_price = event.settlement.gross_price;
+        let new_supply = market.supply.checked_add(event.amount).ok_or(ProgramError::ArithmeticOverflow)?;
+        TradeSettlement {
+            gross_price,
+            protocol_fee: 0,
+            subject_fee: 0,
+            interface_fee: 0,
+            net_amount: gross_price,
+            new_supply,
+        }
+    } else if event.is_buy {
+        quote_buy(config, market, event.amount)?
+    } else {
+        let tax_bps = match wind_down_stage(config, now) {
+            WindDownStage::Active => sell_tax_bps(market, position, now) + dump_fee_bps(market, event.amount, now),
+            _ => 0,
+        };
+        quote_sell(config, market, event.amount)?.with_sell_tax(tax_bps)?
+    };
+    quoted.interface_fee = event.settlement.interface_fee;
+    quoted.net_amount = if event.is_buy {
+        quoted.net_amount.checked_add(quoted.interface_fee)
+    } else {
+        quoted.net_amount.checked_sub(quoted.interface_fee)
+    }
+    .ok_or(ProgramError::ArithmeticOverflow)?;
+    Ok(quoted)
+}
+
+/// Decodes the data fields of a `sol_log_data` log into a `TradeEvent` of
+/// any schema version, or `None` if they are not a trade log.
+pub fn decode_trade_event(fields: &[&[u8]]) -> Option<TradeEvent> {
+    match decode_event(fields)?.event {
+        Event::Trade(event) => Some(event),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{sample_config, sample_market};
+    use crate::{
+        quote_buy, quote_sell, AccountType, TradeSettlement, EVENT_SCHEMA_VERSION, TRADE_LOG_TAG, VOLUME_BUCKETS,
+        VOLUME_BUCKET_SECS,
+    };
+    use borsh::BorshSerialize;
+
+    fn sample_stats() -> Stats {
+        Stats {
+            account_type: AccountType::Stats,
+            total_markets: 1,
+            total_volume: 0,
+            total_fees: 0,
+            epoch: 0,
+            epoch_active_markets: 0,
+            last_epoch_active_markets: 0,
+            bump: 255,
+        }
+    }
+
+    const TRADER: Pubkey = Pubkey::new_from_array([7; 32]);
+
+    fn trade(market: Pubkey, is_buy: bool, amount: u64, settlement: TradeSettlement) -> TradeEvent {
+        TradeEvent {
+            market,
+            trader: TRADER,
+            is_buy,
+            amount,
+            settlement,
+            client_id: None,
+            interface_fee_recipient: None,
+        }
+    }
+
+    #[test]
+    fn test_replay_trades() {
+        let config = sample_config(Pubkey::new_unique());
+        let address = Pubkey::new_unique();
+        let mut replay = Replay::new(config.clone(), sample_stats());
+        replay.track(address, sample_market(Pubkey::new_unique()));
+
+        // A settlement the replayed market does not quote is not applied.
+        let buy = quote_buy(&config, &replay.markets[&address], 10).unwrap();
+        let inflated = TradeSettlement { gross_price: buy.gross_price + 1, ..buy.clone() };
+        let report = replay.apply(&trade(address, true, 10, inflated.clone()), 7_200, 3).unwrap_err();
+        assert_eq!(report, ReplayError::SettlementMismatch { expected: buy.clone(), actual: inflated });
+        assert_eq!(replay.markets[&address].supply, buy.new_supply - 10);
+
+        replay.apply(&trade(address, true, 10, buy.clone()), 7_200, 3).unwrap();
+        let market = &replay.markets[&address];
+        assert_eq!(market.supply, buy.new_supply);
+        assert_eq!((market.protocol_fees, market.subject_fees), (buy.protocol_fee, buy.subject_fee));
+        assert_eq!(market.volume_buckets[2 % VOLUME_BUCKETS], buy.gross_price);
+        assert_eq!(market.last_trade_epoch, 3);
+        assert_eq!(market.holders, 1);
+
+        let now = 7_200 + VOLUME_BUCKET_SECS;
+        let sell = quote_sell(&config, market, 4).unwrap().with_sell_tax(dump_fee_bps(market, 4, now)).unwrap();
+        replay.apply(&trade(address, false, 4, sell.clone()), now, 3).unwrap();
+        let market = &replay.markets[&address];
+        assert_eq!(market.supply, sell.new_supply);
+        assert_eq!((market.dump_window_start, market.dump_window_sold), (now, 4));
+        assert_eq!(market.volume_buckets[3 % VOLUME_BUCKETS], sell.gross_price);
+        assert_eq!(replay.stats.total_volume, buy.gross_price + sell.gross_price);
+        assert_eq!(replay.stats.epoch_active_markets, 1);
+        assert_eq!((market.holders, replay.positions[&(address, TRADER)].balance), (1, 6));
+
+        // A sale replayed twice does not continue from the replayed supply.
+        let report = replay.apply(&trade(address, false, 4, sell.clone()), 7_200, 3).unwrap_err();
+        assert_eq!(report, ReplayError::SupplyGap { expected: sell.new_supply - 4, actual: sell.new_supply });
+        let elsewhere = Pubkey::new_unique();
+        assert_eq!(replay.apply(&trade(elsewhere, true, 1, buy), 0, 0), Err(ReplayError::UnknownMarket(elsewhere)));
+
+        // Selling the rest of the position leaves the market without holders,
+        // and selling shares the replay never saw bought is an error.
+        let market = &replay.markets[&address];
+        let sell_rest = quote_sell(&config, market, 6).unwrap().with_sell_tax(dump_fee_bps(market, 6, now)).unwrap();
+        replay.apply(&trade(address, false, 6, sell_rest.clone()), now, 3).unwrap();
+        assert_eq!(replay.markets[&address].holders, 0);
+        replay.markets.get_mut(&address).unwrap().supply = sell_rest.new_supply + 6;
+        let report = replay.apply(&trade(address, false, 6, sell_rest), now, 3).unwrap_err();
+        assert_eq!(report, ReplayError::UnknownPosition(TRADER));
+    }
+
+    #[test]
+    fn test_decode_trade_event() {
+        let config = sample_config(Pubkey::new_unique());
+        let market = sample_market(Pubkey::new_unique());
+        let event = trade(Pubkey::new_unique(), true, 1, quote_buy(&config, &market, 1).unwrap());
+        let data = event.try_to_vec().unwrap();
+        assert_eq!(decode_trade_event(&[TRADE_LOG_TAG, &[EVENT_SCHEMA_VERSION], &data]), Some(event.clone()));
+        assert_eq!(decode_trade_event(&[TRADE_LOG_TAG, &data]), Some(event));
+        assert_eq!(decode_trade_event(&[b"error", &data]), None);
+        assert_eq!(decode_trade_event(&[TRADE_LOG_TAG, &data[..data.len() - 1]]), None);
+        assert_eq!(decode_trade_event(&[TRADE_LOG_TAG]), None);
+    }
+}
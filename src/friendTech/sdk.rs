@@ -0,0 +1,692 @@
+//! Client-side helpers for building FriendTech instructions and decoding
+//! their results.
+
+use crate::{
+    find_access_address, find_candles_address, find_collateral_authority_address, find_config_address,
+    find_dca_escrow_address, find_dca_schedule_address, find_holding_address, find_insurance_vault_address,
+    find_market_address,
+    find_position_address, find_receipt_address, find_rewards_mint_address, find_stats_address, find_vault_address,
+    FeeRecipient, FriendtechInstruction, InterfaceFee, MarketFees, MutualHolding, RouterPolicy,
+};
+use borsh::BorshDeserialize;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+
+pub mod events;
+#[cfg(feature = "test-fixtures")]
+pub mod fixtures;
+
+/// Builds a `BuyShares` of `amount` shares in `subject`'s market for
+/// `quote_mint`, with `payer` funding the buyer's position if it is new.
+/// Frontends pass their `client_id` to be credited in the trade log and an
+/// `interface_fee` to be paid for the trade. With a `receipt_id`, such as the
+/// transaction's recent blockhash, the buy writes a `TradeReceipt` at
+/// `find_receipt_address(program_id, buyer, receipt_id)`.
+#[allow(clippy::too_many_arguments)]
+pub fn buy_shares(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    buyer: &Pubkey,
+    subject: &Pubkey,
+    quote_mint: &Pubkey,
+    buyer_token_account: &Pubkey,
+    amount: u64,
+    client_id: Option<[u8; 8]>,
+    interface_fee: Option<InterfaceFee>,
+    receipt_id: Option<[u8; 32]>,
+) -> Instruction {
+    let mut accounts = buy_accounts(program_id, payer, buyer, subject, quote_mint, buyer_token_account);
+    accounts.extend(interface_fee.as_ref().map(|fee| AccountMeta::new(fee.recipient, false)));
+    accounts.extend(receipt_id.map(|id| AccountMeta::new(find_receipt_address(program_id, buyer, &id).0, false)));
+    let instruction = FriendtechInstruction::BuyShares { amount, client_id, interface_fee, receipt_id };
+    Instruction::new_with_borsh(*program_id, &instruction, accounts)
+}
+
+/// Builds a `BuyMaxForBudget` spending at most `budget` on `subject`'s
+/// market for `quote_mint`.
+#[allow(clippy::too_many_arguments)]
+pub fn buy_max_for_budget(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    buyer: &Pubkey,
+    subject: &Pubkey,
+    quote_mint: &Pubkey,
+    buyer_token_account: &Pubkey,
+    budget: u64,
+    min_shares: u64,
+) -> Instruction {
+    let accounts = buy_accounts(program_id, payer, buyer, subject, quote_mint, buyer_token_account);
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::BuyMaxForBudget { budget, min_shares }, accounts)
+}
+
+/// Builds a `BuyIfPriceBetween` of `amount` shares in `subject`'s market for
+/// `quote_mint`, executing only at per-share curve prices within
+/// `min..=max`.
+#[allow(clippy::too_many_arguments)]
+pub fn buy_if_price_between(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    buyer: &Pubkey,
+    subject: &Pubkey,
+    quote_mint: &Pubkey,
+    buyer_token_account: &Pubkey,
+    min: u64,
+    max: u64,
+    amount: u64,
+) -> Instruction {
+    let accounts = buy_accounts(program_id, payer, buyer, subject, quote_mint, buyer_token_account);
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::BuyIfPriceBetween { min, max, amount }, accounts)
+}
+
+fn buy_accounts(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    buyer: &Pubkey,
+    subject: &Pubkey,
+    quote_mint: &Pubkey,
+    buyer_token_account: &Pubkey,
+) -> Vec<AccountMeta> {
+    let market = find_market_address(program_id, subject, quote_mint).0;
+    vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(*buyer, true),
+        AccountMeta::new(market, false),
+        AccountMeta::new(find_position_address(program_id, &market, buyer).0, false),
+        AccountMeta::new(*buyer_token_account, false),
+        AccountMeta::new(find_vault_address(program_id, &market).0, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new(find_stats_address(program_id).0, false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        AccountMeta::new(find_holding_address(program_id, buyer, subject).0, false),
+    ]
+}
+
+/// Builds a `SellShares` of `amount` of `seller`'s shares in `subject`'s
+/// market for `quote_mint`. `client_id`, `interface_fee` and `receipt_id`
+/// work as for `buy_shares`, with the seller paying the receipt's rent.
+#[allow(clippy::too_many_arguments)]
+pub fn sell_shares(
+    program_id: &Pubkey,
+    seller: &Pubkey,
+    subject: &Pubkey,
+    quote_mint: &Pubkey,
+    seller_token_account: &Pubkey,
+    amount: u64,
+    client_id: Option<[u8; 8]>,
+    interface_fee: Option<InterfaceFee>,
+    receipt_id: Option<[u8; 32]>,
+) -> Instruction {
+    let market = find_market_address(program_id, subject, quote_mint).0;
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*seller, true),
+        AccountMeta::new(market, false),
+        AccountMeta::new(find_position_address(program_id, &market, seller).0, false),
+        AccountMeta::new(*seller_token_account, false),
+        AccountMeta::new(find_vault_address(program_id, &market).0, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(find_stats_address(program_id).0, false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        AccountMeta::new(find_holding_address(program_id, seller, subject).0, false),
+    ];
+    accounts.extend(interface_fee.as_ref().map(|fee| AccountMeta::new(fee.recipient, false)));
+    if let Some(id) = receipt_id {
+        accounts.push(AccountMeta::new(*seller, true));
+        accounts.push(AccountMeta::new(find_receipt_address(program_id, seller, &id).0, false));
+        accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    }
+    let instruction = FriendtechInstruction::SellShares { amount, client_id, interface_fee, receipt_id };
+    Instruction::new_with_borsh(*program_id, &instruction, accounts)
+}
+
+/// Builds the `ExecuteDca` a keeper sends for `owner`'s schedule in
+/// `subject`'s market for `quote_mint`.
+pub fn execute_dca(program_id: &Pubkey, keeper: &Pubkey, owner: &Pubkey, subject: &Pubkey, quote_mint: &Pubkey) -> Instruction {
+    let market = find_market_address(program_id, subject, quote_mint).0;
+    let schedule = find_dca_schedule_address(program_id, &market, owner).0;
+    let accounts = vec![
+        AccountMeta::new(*keeper, true),
+        AccountMeta::new(schedule, false),
+        AccountMeta::new(find_dca_escrow_address(program_id, &schedule).0, false),
+        AccountMeta::new_readonly(*owner, false),
+        AccountMeta::new(market, false),
+        AccountMeta::new(find_position_address(program_id, &market, owner).0, false),
+        AccountMeta::new(find_vault_address(program_id, &market).0, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new(find_stats_address(program_id).0, false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        AccountMeta::new(find_holding_address(program_id, owner, subject).0, false),
+    ];
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::ExecuteDca, accounts)
+}
+
+/// Builds the `ClaimRewards` of `owner`'s liquidity-mining rewards from
+/// their position in `subject`'s market for `quote_mint`, minted to
+/// `rewards_token_account`.
+pub fn claim_rewards(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    subject: &Pubkey,
+    quote_mint: &Pubkey,
+    rewards_token_account: &Pubkey,
+) -> Instruction {
+    let market = find_market_address(program_id, subject, quote_mint).0;
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new_readonly(market, false),
+        AccountMeta::new(find_position_address(program_id, &market, owner).0, false),
+        AccountMeta::new(find_rewards_mint_address(program_id).0, false),
+        AccountMeta::new(*rewards_token_account, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+    ];
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::ClaimRewards, accounts)
+}
+
+/// Builds a `TransferAdmin` nominating `new_admin`, e.g. an SPL-Governance
+/// governance PDA, as the next admin.
+pub fn transfer_admin(program_id: &Pubkey, admin: &Pubkey, new_admin: Option<Pubkey>) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new(find_config_address(program_id).0, false),
+    ];
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::TransferAdmin { new_admin }, accounts)
+}
+
+/// Builds the `AcceptAdmin` the nominee signs, or that a governance
+/// proposal executes.
+pub fn accept_admin(program_id: &Pubkey, new_admin: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*new_admin, true),
+        AccountMeta::new(find_config_address(program_id).0, false),
+    ];
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::AcceptAdmin, accounts)
+}
+
+/// Builds a `SetFeatures` enabling exactly the `FEATURE_*` bits in
+/// `features`.
+pub fn set_features(program_id: &Pubkey, admin: &Pubkey, features: u64) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new(find_config_address(program_id).0, false),
+    ];
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::SetFeatures { features }, accounts)
+}
+
+/// Builds a `SetMessageFee` for `subject`'s market for `quote_mint`.
+pub fn set_message_fee(program_id: &Pubkey, subject: &Pubkey, quote_mint: &Pubkey, message_fee: u64) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*subject, true),
+        AccountMeta::new(find_market_address(program_id, subject, quote_mint).0, false),
+    ];
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::SetMessageFee { message_fee }, accounts)
+}
+
+/// Builds a `PayMessageFee` of `sender` for `count` messages to `subject`
+/// in its market for `quote_mint`.
+pub fn pay_message_fee(
+    program_id: &Pubkey,
+    sender: &Pubkey,
+    subject: &Pubkey,
+    quote_mint: &Pubkey,
+    sender_token_account: &Pubkey,
+    count: u32,
+) -> Instruction {
+    let market = find_market_address(program_id, subject, quote_mint).0;
+    let accounts = vec![
+        AccountMeta::new(*sender, true),
+        AccountMeta::new(market, false),
+        AccountMeta::new(find_access_address(program_id, &market, sender).0, false),
+        AccountMeta::new(*sender_token_account, false),
+        AccountMeta::new(find_vault_address(program_id, &market).0, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+    ];
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::PayMessageFee { count }, accounts)
+}
+
+/// Builds a `SetFeeStream` for `subject`'s market for `quote_mint`, paying
+/// out the stream to `current_beneficiary`, if one is set.
+pub fn set_fee_stream(
+    program_id: &Pubkey,
+    subject: &Pubkey,
+    quote_mint: &Pubkey,
+    current_beneficiary: Option<&Pubkey>,
+    beneficiary: Option<Pubkey>,
+    rate_per_second: u64,
+) -> Instruction {
+    let market = find_market_address(program_id, subject, quote_mint).0;
+    let mut accounts = vec![AccountMeta::new_readonly(*subject, true), AccountMeta::new(market, false)];
+    if let Some(current_beneficiary) = current_beneficiary {
+        accounts.push(AccountMeta::new(find_vault_address(program_id, &market).0, false));
+        accounts.push(AccountMeta::new(*current_beneficiary, false));
+        accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+    }
+    let instruction = FriendtechInstruction::SetFeeStream { beneficiary, rate_per_second };
+    Instruction::new_with_borsh(*program_id, &instruction, accounts)
+}
+
+/// Builds a `ClaimStreamedFees` of `subject`'s market for `quote_mint`,
+/// paying its `beneficiary`.
+pub fn claim_streamed_fees(
+    program_id: &Pubkey,
+    subject: &Pubkey,
+    quote_mint: &Pubkey,
+    beneficiary: &Pubkey,
+) -> Instruction {
+    let market = find_market_address(program_id, subject, quote_mint).0;
+    let accounts = vec![
+        AccountMeta::new(market, false),
+        AccountMeta::new(find_vault_address(program_id, &market).0, false),
+        AccountMeta::new(*beneficiary, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::ClaimStreamedFees, accounts)
+}
+
+/// Builds a `SetRouterPolicy` for `subject`'s market for `quote_mint`.
+pub fn set_router_policy(
+    program_id: &Pubkey,
+    subject: &Pubkey,
+    quote_mint: &Pubkey,
+    policy: RouterPolicy,
+    routers: Vec<Pubkey>,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*subject, true),
+        AccountMeta::new(find_market_address(program_id, subject, quote_mint).0, false),
+    ];
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::SetRouterPolicy { policy, routers }, accounts)
+}
+
+/// Builds a `NominateSuccessor` for `subject`'s market for `quote_mint`,
+/// signed by its current `authority`.
+pub fn nominate_successor(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    subject: &Pubkey,
+    quote_mint: &Pubkey,
+    successor: Option<Pubkey>,
+    delay: i64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*authority, true),
+        AccountMeta::new(find_market_address(program_id, subject, quote_mint).0, false),
+    ];
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::NominateSuccessor { successor, delay }, accounts)
+}
+
+/// Builds an `AcceptSubjectRole` of `subject`'s market for `quote_mint`.
+pub fn accept_subject_role(program_id: &Pubkey, successor: &Pubkey, subject: &Pubkey, quote_mint: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*successor, true),
+        AccountMeta::new(find_market_address(program_id, subject, quote_mint).0, false),
+    ];
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::AcceptSubjectRole, accounts)
+}
+
+/// Builds a `CloseTradeReceipt` of `trader`'s receipt `receipt_id`,
+/// refunding `rent_payer`.
+pub fn close_trade_receipt(
+    program_id: &Pubkey,
+    trader: &Pubkey,
+    receipt_id: &[u8; 32],
+    rent_payer: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(find_receipt_address(program_id, trader, receipt_id).0, false),
+        AccountMeta::new(*rent_payer, false),
+    ];
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::CloseTradeReceipt, accounts)
+}
+
+/// Builds a `ClaimProtocolFees` of `market`, in `quote_mint`, paying
+/// `destination`, or while the config has a fee split, its first recipient's
+/// `destination` and then the `remaining` recipients' in order. The insurance
+/// vault is passed only while the config's `insurance_fee_bps` is non-zero.
+pub fn claim_protocol_fees(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    market: &Pubkey,
+    quote_mint: &Pubkey,
+    insurance_fee_bps: u16,
+    destination: &Pubkey,
+    remaining: &[Pubkey],
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        AccountMeta::new(*market, false),
+        AccountMeta::new(find_vault_address(program_id, market).0, false),
+        AccountMeta::new(*destination, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    if insurance_fee_bps != 0 {
+        accounts.push(AccountMeta::new(find_insurance_vault_address(program_id, quote_mint).0, false));
+    }
+    accounts.extend(remaining.iter().map(|destination| AccountMeta::new(*destination, false)));
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::ClaimProtocolFees, accounts)
+}
+
+/// Builds a `SetFeeSplit` replacing the protocol fee split with
+/// `recipients`.
+pub fn set_fee_split(program_id: &Pubkey, admin: &Pubkey, recipients: Vec<FeeRecipient>) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new(find_config_address(program_id).0, false),
+    ];
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::SetFeeSplit { recipients }, accounts)
+}
+
+/// Builds a `CheckMutualHolding` of `a` and `b`; decode its return data
+/// with `decode_mutual_holding`.
+pub fn check_mutual_holding(program_id: &Pubkey, a: &Pubkey, b: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(find_holding_address(program_id, a, b).0, false),
+        AccountMeta::new_readonly(find_holding_address(program_id, b, a).0, false),
+    ];
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::CheckMutualHolding { a: *a, b: *b }, accounts)
+}
+
+/// Decodes the return data of a `CheckMutualHolding`.
+pub fn decode_mutual_holding(return_data: &[u8]) -> std::io::Result<MutualHolding> {
+    MutualHolding::try_from_slice(return_data)
+}
+
+/// Builds a `ViewSupply` of `market`; decode its return data with
+/// `decode_view_u64`.
+pub fn view_supply(program_id: &Pubkey, market: &Pubkey) -> Instruction {
+    let accounts = vec![AccountMeta::new_readonly(*market, false)];
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::ViewSupply, accounts)
+}
+
+/// Builds a `ViewHolderBalance` of `owner`'s shares in `market`; decode its
+/// return data with `decode_view_u64`.
+pub fn view_holder_balance(program_id: &Pubkey, market: &Pubkey, owner: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*market, false),
+        AccountMeta::new_readonly(find_position_address(program_id, market, owner).0, false),
+    ];
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::ViewHolderBalance { owner: *owner }, accounts)
+}
+
+/// Builds a `ViewFees` of `market`; decode its return data with
+/// `decode_market_fees`.
+pub fn view_fees(program_id: &Pubkey, market: &Pubkey) -> Instruction {
+    let accounts = vec![AccountMeta::new_readonly(*market, false)];
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::ViewFees, accounts)
+}
+
+/// Decodes the return data of a `ViewSupply` or `ViewHolderBalance`.
+pub fn decode_view_u64(return_data: &[u8]) -> std::io::Result<u64> {
+    u64::try_from_slice(return_data)
+}
+
+/// Decodes the return data of a `ViewFees`.
+pub fn decode_market_fees(return_data: &[u8]) -> std::io::Result<MarketFees> {
+    MarketFees::try_from_slice(return_data)
+}
+
+/// Builds a `CollectMarketReport` of `market`; decode the report it logs
+/// with `events::decode_event`.
+pub fn collect_market_report(program_id: &Pubkey, market: &Pubkey) -> Instruction {
+    let accounts = vec![AccountMeta::new_readonly(*market, false)];
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::CollectMarketReport, accounts)
+}
+
+/// Builds a `ProveHolding` that `owner` holds at least `min_balance` shares
+/// of `market`, bound to the verifier's `nonce`.
+pub fn prove_holding(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    market: &Pubkey,
+    min_balance: u64,
+    nonce: [u8; 32],
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new_readonly(*market, false),
+        AccountMeta::new_readonly(find_position_address(program_id, market, owner).0, false),
+    ];
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::ProveHolding { min_balance, nonce }, accounts)
+}
+
+/// Builds a `SetCollateralPrograms` approving `programs` to lock positions.
+pub fn set_collateral_programs(program_id: &Pubkey, admin: &Pubkey, programs: Vec<Pubkey>) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new(find_config_address(program_id).0, false),
+    ];
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::SetCollateralPrograms { programs }, accounts)
+}
+
+/// Builds a `LockPositionAsCollateral` of `owner`'s position in `market`,
+/// for `lending_program` to invoke signing with its collateral authority.
+pub fn lock_position_as_collateral(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    market: &Pubkey,
+    lending_program: &Pubkey,
+) -> Instruction {
+    let (authority, bump) = find_collateral_authority_address(lending_program);
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(find_position_address(program_id, market, owner).0, false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        AccountMeta::new_readonly(*lending_program, false),
+        AccountMeta::new_readonly(authority, true),
+    ];
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::LockPositionAsCollateral { bump }, accounts)
+}
+
+/// Builds an `UnlockPosition` of `owner`'s position in `market`, for
+/// `lending_program`, which holds the lock, to invoke signing with its
+/// collateral authority.
+pub fn unlock_position(program_id: &Pubkey, owner: &Pubkey, market: &Pubkey, lending_program: &Pubkey) -> Instruction {
+    let (authority, bump) = find_collateral_authority_address(lending_program);
+    let accounts = vec![
+        AccountMeta::new(find_position_address(program_id, market, owner).0, false),
+        AccountMeta::new_readonly(authority, true),
+    ];
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::UnlockPosition { bump }, accounts)
+}
+
+/// Builds a `CreateCandles` opting `market` in, signed by its `authority`,
+/// with `payer` funding the account.
+pub fn create_candles(program_id: &Pubkey, payer: &Pubkey, authority: &Pubkey, market: &Pubkey) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(*authority, true),
+        AccountMeta::new(*market, false),
+        AccountMeta::new(find_candles_address(program_id, market).0, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::CreateCandles, accounts)
+}
+
+/// Appends `market`'s `Candles` PDA to a trade built by this module, as
+/// trades into a market that `create_candles` opted in require; read the
+/// candles back with `recent_candles`.
+pub fn with_candles(mut trade: Instruction, market: &Pubkey) -> Instruction {
+    trade.accounts.push(AccountMeta::new(find_candles_address(&trade.program_id, market).0, false));
+    trade
+}
+
+/// Builds a `StartWindDown` redeeming from `deadline` on.
+pub fn start_wind_down(program_id: &Pubkey, admin: &Pubkey, deadline: i64) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new(find_config_address(program_id).0, false),
+    ];
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::StartWindDown { deadline }, accounts)
+}
+
+/// Builds a `RedeemShares` of `holder`'s position in `subject`'s market for
+/// `quote_mint`, paying into `holder_token_account`.
+pub fn redeem_shares(
+    program_id: &Pubkey,
+    holder: &Pubkey,
+    subject: &Pubkey,
+    quote_mint: &Pubkey,
+    holder_token_account: &Pubkey,
+) -> Instruction {
+    let market = find_market_address(program_id, subject, quote_mint).0;
+    let accounts = vec![
+        AccountMeta::new_readonly(*holder, true),
+        AccountMeta::new(market, false),
+        AccountMeta::new(find_position_address(program_id, &market, holder).0, false),
+        AccountMeta::new(*holder_token_account, false),
+        AccountMeta::new(find_vault_address(program_id, &market).0, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(find_config_address(program_id).0, false),
+        AccountMeta::new(find_holding_address(program_id, holder, subject).0, false),
+    ];
+    Instruction::new_with_borsh(*program_id, &FriendtechInstruction::RedeemShares, accounts)
+}
+
+/// Snapshot export and import for reproducing markets on test clusters.
+#[cfg(feature = "devnet")]
+pub mod snapshot {
+    use crate::{find_config_address, find_market_address, find_position_address, FriendtechInstruction, MarketSnapshot};
+    use borsh::BorshDeserialize;
+    use solana_program::{
+        instruction::{AccountMeta, Instruction},
+        pubkey::Pubkey,
+        system_program,
+    };
+
+    /// Builds an `ExportMarketSnapshot` of `market` and the given positions.
+    pub fn export_market_snapshot(program_id: &Pubkey, market: &Pubkey, positions: &[Pubkey]) -> Instruction {
+        let mut accounts = vec![AccountMeta::new_readonly(*market, false)];
+        accounts.extend(positions.iter().map(|position| AccountMeta::new_readonly(*position, false)));
+        Instruction::new_with_borsh(*program_id, &FriendtechInstruction::ExportMarketSnapshot, accounts)
+    }
+
+    /// Decodes the return data of an `ExportMarketSnapshot`.
+    pub fn decode_market_snapshot(return_data: &[u8]) -> std::io::Result<MarketSnapshot> {
+        MarketSnapshot::try_from_slice(return_data)
+    }
+
+    /// Builds an `ImportMarketSnapshot` restoring `snapshot` under
+    /// `program_id`, deriving every account from the snapshot itself.
+    pub fn import_market_snapshot(
+        program_id: &Pubkey,
+        payer: &Pubkey,
+        admin: &Pubkey,
+        snapshot: MarketSnapshot,
+    ) -> Instruction {
+        let (config, _) = find_config_address(program_id);
+        let (market, _) = find_market_address(program_id, &snapshot.market.subject, &snapshot.market.quote_mint);
+        let mut accounts = vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new_readonly(config, false),
+            AccountMeta::new(market, false),
+            AccountMeta::new(snapshot.market.vault, false),
+            AccountMeta::new_readonly(snapshot.market.quote_mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ];
+        accounts.extend(snapshot.positions.iter().map(|position| {
+            let (address, _) = find_position_address(program_id, &market, &position.owner);
+            AccountMeta::new(address, false)
+        }));
+        let instruction = FriendtechInstruction::ImportMarketSnapshot { snapshot: Box::new(snapshot) };
+        Instruction::new_with_borsh(*program_id, &instruction, accounts)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::tests::sample_config;
+        use crate::{find_vault_address, Market, MarketPause, Position};
+        use borsh::BorshSerialize;
+
+        #[test]
+        fn test_import_market_snapshot() {
+            let program_id = Pubkey::new_unique();
+            let (subject, quote_mint, owner) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+            let (market, bump) = find_market_address(&program_id, &subject, &quote_mint);
+            let (vault, _) = find_vault_address(&program_id, &market);
+            let (position, position_bump) = find_position_address(&program_id, &market, &owner);
+            let snapshot = MarketSnapshot {
+                market: Market {
+                    supply: 3,
+                    protocol_fees: 7,
+                    subject_fees: 7,
+                    last_trade_epoch: 4,
+                    min_hold_duration: 600,
+                    pause: MarketPause::SellsOnly,
+                    ..Market::new(subject, quote_mint, vault, &sample_config(subject), bump)
+                },
+                positions: vec![Position { balance: 3, ..Position::new(owner, position_bump) }],
+            };
+            assert_eq!(decode_market_snapshot(&snapshot.try_to_vec().unwrap()).unwrap(), snapshot);
+
+            let ix = import_market_snapshot(&program_id, &owner, &owner, snapshot);
+            let keys: Vec<Pubkey> = ix.accounts.iter().map(|meta| meta.pubkey).collect();
+            assert_eq!((keys[3], keys[4], keys[8]), (market, vault, position));
+            assert_eq!(keys.len(), 9);
+        }
+    }
+}
+
+/// Faucet and clock controls for exercising markets on test clusters.
+#[cfg(feature = "devnet")]
+pub mod faucet {
+    use crate::{find_config_address, find_faucet_mint_address, FriendtechInstruction};
+    use solana_program::{
+        instruction::{AccountMeta, Instruction},
+        pubkey::Pubkey,
+        system_program,
+    };
+
+    /// Builds the `CreateFaucetMint` creating the faucet's quote mint.
+    pub fn create_faucet_mint(program_id: &Pubkey, payer: &Pubkey, admin: &Pubkey, decimals: u8) -> Instruction {
+        let accounts = vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+            AccountMeta::new(find_faucet_mint_address(program_id).0, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ];
+        Instruction::new_with_borsh(*program_id, &FriendtechInstruction::CreateFaucetMint { decimals }, accounts)
+    }
+
+    /// Builds a `FaucetMint` of `amount` faucet quote tokens to
+    /// `token_account`.
+    pub fn faucet_mint(program_id: &Pubkey, token_account: &Pubkey, amount: u64) -> Instruction {
+        let accounts = vec![
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+            AccountMeta::new(find_faucet_mint_address(program_id).0, false),
+            AccountMeta::new(*token_account, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ];
+        Instruction::new_with_borsh(*program_id, &FriendtechInstruction::FaucetMint { amount }, accounts)
+    }
+
+    /// Builds a `FastForwardMarket` of `market` and the given positions.
+    pub fn fast_forward_market(
+        program_id: &Pubkey,
+        admin: &Pubkey,
+        market: &Pubkey,
+        positions: &[Pubkey],
+        seconds: i64,
+        epochs: u64,
+    ) -> Instruction {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new_readonly(find_config_address(program_id).0, false),
+            AccountMeta::new(*market, false),
+        ];
+        accounts.extend(positions.iter().map(|position| AccountMeta::new(*position, false)));
+        let instruction = FriendtechInstruction::FastForwardMarket { seconds, epochs };
+        Instruction::new_with_borsh(*program_id, &instruction, accounts)
+    }
+}
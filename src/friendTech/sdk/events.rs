@@ -0,0 +1,182 @@
+//! Decoding of the program's `sol_log_data` events across schema versions.
+//!
+//! Events logged before versioning carry no version field and are version 0;
+//! their trade events come in the layouts from before and after interface
+//! fees, told apart by length. From version 1 on, layouts only append
+//! fields, so events of versions newer than `EVENT_SCHEMA_VERSION` decode to
+//! the fields this version knows.
+
+use crate::{
+    FeeSwitchEvent, HoldingProofEvent, InsurancePayoutEvent, MarketPauseEvent, MarketReportEvent, SuccessionEvent,
+    TradeEvent, TradeSettlement, FEE_SWITCH_LOG_TAG, HOLDING_PROOF_LOG_TAG, INSURANCE_LOG_TAG, MARKET_PAUSE_LOG_TAG,
+    MARKET_REPORT_LOG_TAG, SUCCESSION_LOG_TAG, TRADE_LOG_TAG,
+};
+use borsh::BorshDeserialize;
+use solana_program::pubkey::Pubkey;
+
+/// An event the program logged.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    Trade(TradeEvent),
+    FeeSwitch(FeeSwitchEvent),
+    HoldingProof(HoldingProofEvent),
+    InsurancePayout(InsurancePayoutEvent),
+    MarketPause(MarketPauseEvent),
+    MarketReport(MarketReportEvent),
+    Succession(SuccessionEvent),
+}
+
+/// An event with the schema version it was logged at.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VersionedEvent {
+    pub version: u8,
+    pub event: Event,
+}
+
+/// Decodes the data fields of a `sol_log_data` log into an event, or `None`
+/// if they are not an event of this program.
+pub fn decode_event(fields: &[&[u8]]) -> Option<VersionedEvent> {
+    match fields {
+        [tag, payload] => Some(VersionedEvent { version: 0, event: decode_unversioned(tag, payload)? }),
+        [tag, [version], payload] if *version > 0 => {
+            Some(VersionedEvent { version: *version, event: decode_versioned(tag, payload)? })
+        }
+        _ => None,
+    }
+}
+
+fn decode_versioned(tag: &[u8], payload: &[u8]) -> Option<Event> {
+    // Reading a prefix of the payload skips fields appended by newer versions.
+    fn read<T: BorshDeserialize>(mut payload: &[u8]) -> Option<T> {
+        T::deserialize(&mut payload).ok()
+    }
+    let event = match tag {
+        TRADE_LOG_TAG => Event::Trade(read(payload)?),
+        FEE_SWITCH_LOG_TAG => Event::FeeSwitch(read(payload)?),
+        HOLDING_PROOF_LOG_TAG => Event::HoldingProof(read(payload)?),
+        INSURANCE_LOG_TAG => Event::InsurancePayout(read(payload)?),
+        MARKET_PAUSE_LOG_TAG => Event::MarketPause(read(payload)?),
+        MARKET_REPORT_LOG_TAG => Event::MarketReport(read(payload)?),
+        SUCCESSION_LOG_TAG => Event::Succession(read(payload)?),
+        _ => return None,
+    };
+    Some(event)
+}
+
+fn decode_unversioned(tag: &[u8], payload: &[u8]) -> Option<Event> {
+    let event = match tag {
+        TRADE_LOG_TAG => Event::Trade(
+            TradeEvent::try_from_slice(payload)
+                .or_else(|_| TradeEventV0::try_from_slice(payload).map(TradeEvent::from))
+                .ok()?,
+        ),
+        FEE_SWITCH_LOG_TAG => Event::FeeSwitch(FeeSwitchEvent::try_from_slice(payload).ok()?),
+        INSURANCE_LOG_TAG => Event::InsurancePayout(InsurancePayoutEvent::try_from_slice(payload).ok()?),
+        MARKET_PAUSE_LOG_TAG => Event::MarketPause(MarketPauseEvent::try_from_slice(payload).ok()?),
+        SUCCESSION_LOG_TAG => Event::Succession(SuccessionEvent::try_from_slice(payload).ok()?),
+        _ => return None,
+    };
+    Some(event)
+}
+
+/// `TradeEvent` as first logged, before interface fees.
+#[derive(BorshDeserialize)]
+struct TradeEventV0 {
+    market: Pubkey,
+    trader: Pubkey,
+    is_buy: bool,
+    amount: u64,
+    gross_price: u64,
+    protocol_fee: u64,
+    subject_fee: u64,
+    net_amount: u64,
+    new_supply: u64,
+    client_id: Option<[u8; 8]>,
+}
+
+impl From<TradeEventV0> for TradeEvent {
+    fn from(event: TradeEventV0) -> Self {
+        TradeEvent {
+            market: event.market,
+            trader: event.trader,
+            is_buy: event.is_buy,
+            amount: event.amount,
+            settlement: TradeSettlement {
+                gross_price: event.gross_price,
+                protocol_fee: event.protocol_fee,
+                subject_fee: event.subject_fee,
+                interface_fee: 0,
+                net_amount: event.net_amount,
+                new_supply: event.new_supply,
+            },
+            client_id: event.client_id,
+            interface_fee_recipient: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MarketPause, EVENT_SCHEMA_VERSION};
+    use borsh::BorshSerialize;
+
+    fn trade() -> TradeEvent {
+        TradeEvent {
+            market: Pubkey::new_unique(),
+            trader: Pubkey::new_unique(),
+            is_buy: true,
+            amount: 3,
+            settlement: TradeSettlement {
+                gross_price: 1_000,
+                protocol_fee: 50,
+                subject_fee: 50,
+                interface_fee: 0,
+                net_amount: 1_100,
+                new_supply: 10,
+            },
+            client_id: Some(*b"frontend"),
+            interface_fee_recipient: None,
+        }
+    }
+
+    #[test]
+    fn test_decode_versioned_events() {
+        let event = trade();
+        let payload = event.try_to_vec().unwrap();
+        let decoded = decode_event(&[TRADE_LOG_TAG, &[EVENT_SCHEMA_VERSION], &payload]).unwrap();
+        assert_eq!(decoded, VersionedEvent { version: EVENT_SCHEMA_VERSION, event: Event::Trade(event.clone()) });
+
+        // A newer version's appended fields are skipped.
+        let newer = [payload.as_slice(), &[1, 2, 3]].concat();
+        let decoded = decode_event(&[TRADE_LOG_TAG, &[EVENT_SCHEMA_VERSION + 1], &newer]).unwrap();
+        assert_eq!(decoded.event, Event::Trade(event));
+
+        let pause = MarketPauseEvent { market: Pubkey::new_unique(), pause: MarketPause::Halted };
+        let payload = pause.try_to_vec().unwrap();
+        let decoded = decode_event(&[MARKET_PAUSE_LOG_TAG, &[EVENT_SCHEMA_VERSION], &payload]).unwrap();
+        assert_eq!(decoded.event, Event::MarketPause(pause));
+
+        assert_eq!(decode_event(&[b"error", &[EVENT_SCHEMA_VERSION], &payload]), None);
+        assert_eq!(decode_event(&[TRADE_LOG_TAG, &[EVENT_SCHEMA_VERSION], &payload]), None);
+        assert_eq!(decode_event(&[TRADE_LOG_TAG]), None);
+    }
+
+    #[test]
+    fn test_decode_unversioned_trade_layouts() {
+        let event = trade();
+        let decoded = decode_event(&[TRADE_LOG_TAG, &event.try_to_vec().unwrap()]).unwrap();
+        assert_eq!(decoded, VersionedEvent { version: 0, event: Event::Trade(event.clone()) });
+
+        // The first layout had no interface fee in the settlement and no
+        // recipient after the client id.
+        let settlement = &event.settlement;
+        let mut original = (event.market, event.trader, event.is_buy, event.amount).try_to_vec().unwrap();
+        for value in [settlement.gross_price, settlement.protocol_fee, settlement.subject_fee] {
+            original.extend(value.to_le_bytes());
+        }
+        original.extend((settlement.net_amount, settlement.new_supply, event.client_id).try_to_vec().unwrap());
+        let decoded = decode_event(&[TRADE_LOG_TAG, &original]).unwrap();
+        assert_eq!(decoded.event, Event::Trade(event));
+    }
+}
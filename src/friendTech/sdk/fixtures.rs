@@ -0,0 +1,313 @@
+//! `solana-program-test` fixtures with funded wallets, a quote mint and
+//! markets already at chosen supplies, for integration tests against the
+//! program.
+//!
+//! ```ignore
+//! let fixture = FixtureBuilder::new(program_id).wallets(3, 1_000_000).market(50).market(5_000).start().await;
+//! ```
+//!
+//! State is written straight into the test bank instead of being traded
+//! into place, so fixtures start in one block regardless of supply.
+//!
+//! `solana-program-test` drops the data a natively run program logs with
+//! `sol_log_data`, so fixtures collect it for `take_logged_data` instead.
+
+use crate::{
+    find_config_address, find_holding_address, find_market_address, find_position_address, find_stats_address,
+    find_vault_address, process_instruction, quote_sell, AccountType, Config, Holding, Market, Position, Stats,
+};
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::Instruction,
+    program_option::COption,
+    program_pack::Pack,
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    pubkey::Pubkey,
+    rent::Rent,
+};
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+};
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+use std::sync::{Mutex, Once};
+
+const QUOTE_DECIMALS: u8 = 6;
+const WALLET_LAMPORTS: u64 = 10_000_000_000;
+
+static LOGGED_DATA: Mutex<Vec<Vec<Vec<u8>>>> = Mutex::new(Vec::new());
+
+/// A funded wallet and its quote token account.
+pub struct Wallet {
+    pub keypair: Keypair,
+    pub token_account: Pubkey,
+}
+
+/// A market created by the fixture. Its whole supply is held by the
+/// subject's position, and the vault holds enough quote tokens to buy it
+/// all back.
+pub struct MarketFixture {
+    pub subject: Wallet,
+    pub market: Pubkey,
+    pub vault: Pubkey,
+    pub subject_position: Pubkey,
+}
+
+/// A started test validator and the accounts the fixture set up.
+pub struct Fixture {
+    pub context: ProgramTestContext,
+    pub program_id: Pubkey,
+    pub admin: Keypair,
+    pub config: Pubkey,
+    pub stats: Pubkey,
+    pub quote_mint: Pubkey,
+    pub wallets: Vec<Wallet>,
+    pub markets: Vec<MarketFixture>,
+}
+
+/// Describes the accounts a `Fixture` starts with.
+pub struct FixtureBuilder {
+    program_id: Pubkey,
+    protocol_fee_bps: u16,
+    subject_fee_bps: u16,
+    wallets: usize,
+    wallet_quote_tokens: u64,
+    market_supplies: Vec<u64>,
+}
+
+impl FixtureBuilder {
+    pub fn new(program_id: Pubkey) -> Self {
+        FixtureBuilder {
+            program_id,
+            protocol_fee_bps: 500,
+            subject_fee_bps: 500,
+            wallets: 0,
+            wallet_quote_tokens: 0,
+            market_supplies: Vec::new(),
+        }
+    }
+
+    /// Fee rates of the config and every fixture market.
+    pub fn fees(mut self, protocol_fee_bps: u16, subject_fee_bps: u16) -> Self {
+        self.protocol_fee_bps = protocol_fee_bps;
+        self.subject_fee_bps = subject_fee_bps;
+        self
+    }
+
+    /// Adds `count` trader wallets, each with SOL for fees and rent and
+    /// `quote_tokens` base units of the quote mint.
+    pub fn wallets(mut self, count: usize, quote_tokens: u64) -> Self {
+        self.wallets = count;
+        self.wallet_quote_tokens = quote_tokens;
+        self
+    }
+
+    /// Adds a market with `supply` shares outstanding, owned by a new subject.
+    pub fn market(mut self, supply: u64) -> Self {
+        self.market_supplies.push(supply);
+        self
+    }
+
+    pub async fn start(self) -> Fixture {
+        let program_id = self.program_id;
+        let mut program_test = ProgramTest::new("friendtech", program_id, processor!(process_instruction));
+        let rent = Rent::default();
+        let admin = Keypair::new();
+        let quote_mint = Pubkey::new_unique();
+        add_wallet_lamports(&mut program_test, &admin.pubkey());
+
+        let (config_key, config_bump) = find_config_address(&program_id);
+        let config = Config {
+            protocol_fee_bps: self.protocol_fee_bps,
+            subject_fee_bps: self.subject_fee_bps,
+            veto_threshold_bps: 5_000,
+            ..Config::new(admin.pubkey(), config_bump)
+        };
+        add_packed(&mut program_test, &rent, config_key, &program_id, &config);
+
+        let mut mint_supply = 0u64;
+        let mut wallets = Vec::with_capacity(self.wallets);
+        for _ in 0..self.wallets {
+            wallets.push(add_wallet(&mut program_test, &rent, &quote_mint, self.wallet_quote_tokens));
+            mint_supply += self.wallet_quote_tokens;
+        }
+
+        let mut markets = Vec::with_capacity(self.market_supplies.len());
+        for &supply in &self.market_supplies {
+            let subject = add_wallet(&mut program_test, &rent, &quote_mint, 0);
+            let subject_key = subject.keypair.pubkey();
+            let (market_key, bump) = find_market_address(&program_id, &subject_key, &quote_mint);
+            let (vault, _) = find_vault_address(&program_id, &market_key);
+            let market = Market {
+                supply,
+                holders: (supply > 0) as u64,
+                ..Market::new(subject_key, quote_mint, vault, &config, bump)
+            };
+            // Selling everything at once sums the whole curve, which, with
+            // the fees each sale keeps back, covers any sequence of sells.
+            let vault_tokens = quote_sell(&config, &market, supply).map_or(0, |quote| quote.gross_price);
+            mint_supply += vault_tokens;
+            add_token_account(&mut program_test, &rent, vault, &quote_mint, &market_key, vault_tokens);
+            add_packed(&mut program_test, &rent, market_key, &program_id, &market);
+
+            let (subject_position, position_bump) = find_position_address(&program_id, &market_key, &subject_key);
+            let position = Position { balance: supply, ..Position::new(subject_key, position_bump) };
+            add_packed(&mut program_test, &rent, subject_position, &program_id, &position);
+            if supply > 0 {
+                let (holding, holding_bump) = find_holding_address(&program_id, &subject_key, &subject_key);
+                let subject_holding = Holding {
+                    account_type: AccountType::Holding,
+                    holder: subject_key,
+                    subject: subject_key,
+                    markets: 1,
+                    bump: holding_bump,
+                };
+                add_packed(&mut program_test, &rent, holding, &program_id, &subject_holding);
+            }
+            markets.push(MarketFixture { subject, market: market_key, vault, subject_position });
+        }
+
+        let (stats_key, stats_bump) = find_stats_address(&program_id);
+        let stats = Stats {
+            account_type: AccountType::Stats,
+            total_markets: markets.len() as u64,
+            total_volume: 0,
+            total_fees: 0,
+            epoch: 0,
+            epoch_active_markets: 0,
+            last_epoch_active_markets: 0,
+            bump: stats_bump,
+        };
+        add_packed(&mut program_test, &rent, stats_key, &program_id, &stats);
+
+        let mint = Mint {
+            mint_authority: COption::Some(admin.pubkey()),
+            supply: mint_supply,
+            decimals: QUOTE_DECIMALS,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        };
+        add_packed(&mut program_test, &rent, quote_mint, &spl_token::id(), &mint);
+
+        let context = program_test.start_with_context().await;
+        static CAPTURE: Once = Once::new();
+        CAPTURE.call_once(|| {
+            let inner = set_syscall_stubs(Box::new(DefaultStubs));
+            set_syscall_stubs(Box::new(CapturingStubs { inner }));
+        });
+        Fixture { context, program_id, admin, config: config_key, stats: stats_key, quote_mint, wallets, markets }
+    }
+}
+
+/// Takes the fields of every `sol_log_data` call since the last take, across
+/// all fixtures of the test binary.
+pub fn take_logged_data() -> Vec<Vec<Vec<u8>>> {
+    std::mem::take(&mut LOGGED_DATA.lock().unwrap())
+}
+
+struct DefaultStubs;
+
+impl SyscallStubs for DefaultStubs {}
+
+/// `solana-program-test`'s stubs, with `sol_log_data` collected for
+/// `take_logged_data`.
+struct CapturingStubs {
+    inner: Box<dyn SyscallStubs>,
+}
+
+impl SyscallStubs for CapturingStubs {
+    fn sol_log(&self, message: &str) {
+        self.inner.sol_log(message)
+    }
+    fn sol_log_compute_units(&self) {
+        self.inner.sol_log_compute_units()
+    }
+    fn sol_remaining_compute_units(&self) -> u64 {
+        self.inner.sol_remaining_compute_units()
+    }
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        self.inner.sol_invoke_signed(instruction, account_infos, signers_seeds)
+    }
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.inner.sol_get_clock_sysvar(var_addr)
+    }
+    fn sol_get_epoch_schedule_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.inner.sol_get_epoch_schedule_sysvar(var_addr)
+    }
+    fn sol_get_fees_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.inner.sol_get_fees_sysvar(var_addr)
+    }
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.inner.sol_get_rent_sysvar(var_addr)
+    }
+    fn sol_get_epoch_rewards_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.inner.sol_get_epoch_rewards_sysvar(var_addr)
+    }
+    fn sol_get_last_restart_slot(&self, var_addr: *mut u8) -> u64 {
+        self.inner.sol_get_last_restart_slot(var_addr)
+    }
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        self.inner.sol_get_return_data()
+    }
+    fn sol_set_return_data(&self, data: &[u8]) {
+        self.inner.sol_set_return_data(data)
+    }
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        LOGGED_DATA.lock().unwrap().push(fields.iter().map(|field| field.to_vec()).collect());
+    }
+    fn sol_get_processed_sibling_instruction(&self, index: usize) -> Option<Instruction> {
+        self.inner.sol_get_processed_sibling_instruction(index)
+    }
+    fn sol_get_stack_height(&self) -> u64 {
+        self.inner.sol_get_stack_height()
+    }
+}
+
+fn add_packed<T: Pack>(program_test: &mut ProgramTest, rent: &Rent, address: Pubkey, owner: &Pubkey, state: &T) {
+    let mut data = vec![0u8; T::LEN];
+    state.pack_into_slice(&mut data);
+    let account =
+        Account { lamports: rent.minimum_balance(T::LEN), data, owner: *owner, executable: false, rent_epoch: 0 };
+    program_test.add_account(address, account);
+}
+
+fn add_wallet_lamports(program_test: &mut ProgramTest, address: &Pubkey) {
+    let account = Account { lamports: WALLET_LAMPORTS, ..Account::default() };
+    program_test.add_account(*address, account);
+}
+
+fn add_token_account(
+    program_test: &mut ProgramTest,
+    rent: &Rent,
+    address: Pubkey,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) {
+    let token_account = TokenAccount {
+        mint: *mint,
+        owner: *owner,
+        amount,
+        delegate: COption::None,
+        state: AccountState::Initialized,
+        is_native: COption::None,
+        delegated_amount: 0,
+        close_authority: COption::None,
+    };
+    add_packed(program_test, rent, address, &spl_token::id(), &token_account);
+}
+
+fn add_wallet(program_test: &mut ProgramTest, rent: &Rent, mint: &Pubkey, quote_tokens: u64) -> Wallet {
+    let keypair = Keypair::new();
+    let token_account = Pubkey::new_unique();
+    add_wallet_lamports(program_test, &keypair.pubkey());
+    add_token_account(program_test, rent, token_account, mint, &keypair.pubkey(), quote_tokens);
+    Wallet { keypair, token_account }
+}
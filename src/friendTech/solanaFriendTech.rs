@@ -1,30 +1,883 @@
 use solana_program::{
-    account_info::{next_account_info, AccountInfo},
-    entrypoint,
-    program::{invoke},
+    log::sol_log_data,
     program_error::ProgramError,
-    program_pack::Pack,
+    program_pack::{IsInitialized, Pack, Sealed},
     pubkey::Pubkey,
 };
-use spl_token::{self, state::Account as SplTokenAccount, instruction as spl_token_instruction};
 use borsh::{BorshDeserialize, BorshSerialize};
 
+pub mod emissions;
+pub mod math;
+pub mod merkle;
+pub mod pda;
+mod processor;
+pub mod replay;
+pub mod sdk;
+#[cfg(feature = "friendtech-py")]
+pub mod python;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use pda::*;
+pub use processor::process_instruction;
+
 // Constants for the dual-phase pricing algorithm.
 const DEFAULT_CURRENT_VOLUME: f64 = 10.0;
 const DEFAULT_AVERAGE_VOLUME: f64 = 7.0;
 const DEFAULT_TIME_SINCE_LAST_TRADE: f64 = 1.0;
+// Volume ratios above this price like it, so volume piled into one hour lifts
+// prices by at most 2%.
+const MAX_VOLUME_RATIO: f64 = 2.0;
+
+// Curve prices are fixed to this many units per base unit before being
+// summed over a trade's shares.
+const PRICE_PRECISION: u128 = 1_000_000;
+
+// Fees are expressed in basis points of a trade's curve price.
+const BPS_DENOMINATOR: u64 = 10_000;
+const MAX_FEE_BPS: u16 = 1_000;
+
+// A basket buy takes 7 shared accounts plus 4 per market.
+const MAX_BASKET_MARKETS: usize = 10;
+const BASKET_FIXED_ACCOUNTS: usize = 7;
+const BASKET_MARKET_ACCOUNTS: usize = 4;
+
+// Subjects can hold new positions back from selling for at most a day.
+const MAX_MIN_HOLD_DURATION: i64 = 86_400;
+// A market's sell tax starts at most at 20% and decays over at most 30 days.
+const MAX_SELL_TAX_BPS: u16 = 2_000;
+const MAX_SELL_TAX_PERIOD: i64 = 30 * 86_400;
+// A nominated successor waits at most 30 days before taking over a market.
+const MAX_SUCCESSION_DELAY: i64 = 30 * 86_400;
+// Holders get at least 30 days to sell between a wind-down starting and
+// redemptions opening.
+const MIN_WIND_DOWN_NOTICE: i64 = 30 * 86_400;
+// Sells pay an extra dump fee of a quarter of the share of supply sold in the
+// current hour-long window, capped at 10%.
+const DUMP_WINDOW: i64 = 3_600;
+const DUMP_FEE_DIVISOR: u128 = 4;
+const MAX_DUMP_FEE_BPS: u16 = 1_000;
+
+/// Size of a share account written by the program's first release, a bare
+/// `LegacyShareAccount` without an `AccountType` or a market.
+const LEGACY_SHARE_ACCOUNT_LEN: usize = 32 + 8;
+
+/// Markets keep their trade volume in this many hourly buckets, a rolling
+/// 24 hours; see `rolling_volume`.
+pub const VOLUME_BUCKETS: usize = 24;
+const VOLUME_BUCKET_SECS: i64 = 3_600;
+
+/// Hourly candles a market's `Candles` keeps, a rolling 48 hours; see
+/// `recent_candles`.
+pub const CANDLE_BUCKETS: usize = 48;
+const CANDLE_SECS: i64 = 3_600;
+
+/// Programs a market can certify to route trades into it by CPI; see
+/// `RouterPolicy::Allowlist`.
+pub const MAX_ROUTERS: usize = 4;
+
+/// Recipients the protocol fee can be split across; see `Config::fee_split`.
+pub const MAX_FEE_RECIPIENTS: usize = 4;
+
+/// Programs the admin can approve to lock positions as collateral; see
+/// `Config::collateral_programs`.
+pub const MAX_COLLATERAL_PROGRAMS: usize = 4;
+
+/// Members a group market can split its subject fees across; see
+/// `Market::group`.
+pub const MAX_GROUP_MEMBERS: usize = 8;
+
+// Airdrops track claims in a fixed bitmap, so each has at most this many leaves.
+pub const MAX_AIRDROP_CLAIMS: u32 = 8_192;
+const AIRDROP_BITMAP_BYTES: usize = MAX_AIRDROP_CLAIMS as usize / 8;
+
+/// Slots a `TradeReceipt` is kept before `CloseTradeReceipt` may reclaim its
+/// rent, about two days.
+pub const RECEIPT_RETENTION_SLOTS: u64 = 432_000;
+
+/// `Config::features` bits of optional subsystems. Subsystems under
+/// development get a bit before they ship, so they can launch disabled.
+pub const FEATURE_DCA: u64 = 1 << 0;
+pub const FEATURE_AIRDROPS: u64 = 1 << 1;
+pub const FEATURE_PRESALES: u64 = 1 << 2;
+pub const FEATURE_BASKETS: u64 = 1 << 3;
+pub const FEATURE_TREASURY_STAKING: u64 = 1 << 4;
+pub const FEATURE_SUBSCRIPTIONS: u64 = 1 << 5;
+pub const FEATURE_LENDING: u64 = 1 << 6;
+pub const FEATURE_AMM_POOL: u64 = 1 << 7;
+pub const FEATURE_REFERRALS: u64 = 1 << 8;
+pub const FEATURE_LIQUIDITY_MINING: u64 = 1 << 9;
+/// Features of a new config: every shipped subsystem.
+pub const DEFAULT_FEATURES: u64 = FEATURE_DCA
+    | FEATURE_AIRDROPS
+    | FEATURE_PRESALES
+    | FEATURE_BASKETS
+    | FEATURE_TREASURY_STAKING
+    | FEATURE_LIQUIDITY_MINING;
+
+/// Discriminator stored as the first byte of every program-owned account.
+#[derive(Clone, Copy, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub enum AccountType {
+    Uninitialized,
+    Config,
+    Profile,
+    Market,
+    Position,
+    Stats,
+    Proposal,
+    VetoRecord,
+    Holding,
+    DcaSchedule,
+    Airdrop,
+    Presale,
+    TradeReceipt,
+    MessageAccess,
+    Candles,
+}
+
+/// Global program settings, stored at the `[CONFIG_SEED]` PDA.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct Config {
+    pub account_type: AccountType,
+    /// Signer of config changes and treasury moves. It can be an
+    /// SPL-Governance governance or native treasury PDA, which signs when the
+    /// realm executes a passed proposal; see `TransferAdmin`.
+    pub admin: Pubkey,
+    /// Key trusted to sign subject/social-handle bindings.
+    pub attestor: Pubkey,
+    /// Fee rates copied into each market at creation.
+    pub protocol_fee_bps: u16,
+    pub subject_fee_bps: u16,
+    /// Seconds between a parameter proposal and the earliest time it executes.
+    pub proposal_delay: i64,
+    /// Share of a market's supply whose holders can veto a proposal.
+    pub veto_threshold_bps: u16,
+    /// Global fee switch; protocol fees are only charged while it and the
+    /// market's switch are both on.
+    pub protocol_fees_enabled: bool,
+    /// Cap on the interface fee a trade may add for its frontend.
+    pub max_interface_fee_bps: u16,
+    /// Share of claimed protocol fees routed to the insurance vault of their
+    /// quote mint.
+    pub insurance_fee_bps: u16,
+    /// Admin nominated by `TransferAdmin`, until it accepts.
+    pub pending_admin: Option<Pubkey>,
+    /// Enabled `FEATURE_*` subsystems.
+    pub features: u64,
+    /// Weighted recipients of claimed protocol fees net of insurance, filled
+    /// from the front. With no recipients, claims go to the destination the
+    /// admin passes.
+    pub fee_split: [FeeRecipient; MAX_FEE_RECIPIENTS],
+    /// Programs, e.g. lending protocols, that may lock positions as
+    /// collateral by CPI; unused slots are `Pubkey::default()`.
+    pub collateral_programs: [Pubkey; MAX_COLLATERAL_PROGRAMS],
+    /// Unix timestamp redemptions open at once `StartWindDown` started
+    /// winding the program down; `None` while it runs. See `wind_down_stage`.
+    pub wind_down_deadline: Option<i64>,
+    pub bump: u8,
+}
+
+impl Config {
+    /// A config for `admin`, who is also the attestor, at the PDA with
+    /// `bump`, with fees, delay and thresholds at zero and the default
+    /// features enabled.
+    pub fn new(admin: Pubkey, bump: u8) -> Self {
+        Config {
+            account_type: AccountType::Config,
+            admin,
+            attestor: admin,
+            protocol_fee_bps: 0,
+            subject_fee_bps: 0,
+            proposal_delay: 0,
+            veto_threshold_bps: 0,
+            protocol_fees_enabled: true,
+            max_interface_fee_bps: 0,
+            insurance_fee_bps: 0,
+            pending_admin: None,
+            features: DEFAULT_FEATURES,
+            fee_split: [FeeRecipient::default(); MAX_FEE_RECIPIENTS],
+            collateral_programs: [Pubkey::default(); MAX_COLLATERAL_PROGRAMS],
+            wind_down_deadline: None,
+            bump,
+        }
+    }
+}
+
+/// A recipient of a share of claimed protocol fees, e.g. the team, a DAO
+/// treasury or an insurance fund.
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct FeeRecipient {
+    /// Quote token account receiving the share.
+    pub destination: Pubkey,
+    /// Share in basis points; zero marks an unused slot.
+    pub weight_bps: u16,
+}
+
+/// Per-subject profile, stored at the `[PROFILE_SEED, subject]` PDA.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct Profile {
+    pub account_type: AccountType,
+    pub subject: Pubkey,
+    /// Hash of the subject's social handle, zeroed until attested.
+    pub handle_hash: [u8; 32],
+    /// Attestor that signed `handle_hash`, kept alongside the signature so
+    /// frontends can re-verify the binding off-chain.
+    pub attestor: Pubkey,
+    pub attestor_sig: [u8; 64],
+    pub bump: u8,
+}
+
+/// Implements `Pack` for a fixed-size Borsh state type whose first field is its `AccountType`.
+///
+/// `unpack` rejects empty or zeroed data with `UninitializedAccount` before
+/// deserializing, and data tagged as another account type with
+/// `InvalidAccountData`.
+macro_rules! impl_borsh_pack {
+    ($ty:ty, $account_type:expr, $len:expr) => {
+        impl Sealed for $ty {}
+        impl IsInitialized for $ty {
+            fn is_initialized(&self) -> bool {
+                self.account_type == $account_type
+            }
+        }
+        impl Pack for $ty {
+            const LEN: usize = $len;
+            fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+                if input.first().is_none_or(|tag| *tag == AccountType::Uninitialized as u8) {
+                    return Err(FriendtechError::UninitializedAccount.into());
+                }
+                let value = Self::unpack_unchecked(input)?;
+                if !value.is_initialized() {
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                Ok(value)
+            }
+            fn pack_into_slice(&self, dst: &mut [u8]) {
+                let mut writer = dst;
+                self.serialize(&mut writer).expect("account buffer sized to LEN");
+            }
+            fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+                Self::deserialize(&mut &src[..]).map_err(|_| ProgramError::InvalidAccountData)
+            }
+        }
+    };
+}
+
+/// A subject's share market in one quote currency, stored at the
+/// `[MARKET_SEED, subject, quote_mint]` PDA. A subject may run one market per
+/// quote mint; each has its own curve and vault and they share the profile.
+/// The market PDA is also the token authority of its vault.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct Market {
+    pub account_type: AccountType,
+    pub subject: Pubkey,
+    pub quote_mint: Pubkey,
+    /// Quote token account at the `[VAULT_SEED, market]` PDA holding trade proceeds.
+    pub vault: Pubkey,
+    pub supply: u64,
+    pub protocol_fee_bps: u16,
+    pub subject_fee_bps: u16,
+    /// Fees accrued in the vault and not yet claimed.
+    pub protocol_fees: u64,
+    pub subject_fees: u64,
+    /// Epoch of the market's latest trade, `u64::MAX` before its first.
+    pub last_trade_epoch: u64,
+    /// Per-market protocol fee switch, see `Config::protocol_fees_enabled`.
+    pub protocol_fees_enabled: bool,
+    /// Seconds a position must be held after opening before it can sell, set
+    /// by the subject; 0 allows immediate sells.
+    pub min_hold_duration: i64,
+    /// Trading halt set by the subject.
+    pub pause: MarketPause,
+    /// Sell tax paid to the subject right after a purchase, decaying linearly
+    /// to zero over `sell_tax_period` seconds; see `sell_tax_bps`.
+    pub sell_tax_bps: u16,
+    pub sell_tax_period: i64,
+    /// Unix timestamp the current dump fee window opened at; see
+    /// `dump_fee_bps`.
+    pub dump_window_start: i64,
+    /// Supply when the window opened and shares sold since.
+    pub dump_window_supply: u64,
+    pub dump_window_sold: u64,
+    /// Unix timestamp public trading opens at, after a presale; 0 for
+    /// markets opened at creation.
+    pub opens_at: i64,
+    /// Reward tokens emitted to holders per second, set by the admin.
+    pub emission_rate: u64,
+    /// Cumulative rewards per share, see `emissions`, as of the unix
+    /// timestamp `rewards_updated_at`.
+    pub reward_index: u128,
+    pub rewards_updated_at: i64,
+    /// Gross trade volume per hour, the bucket of unix hour `h` at index
+    /// `h % VOLUME_BUCKETS`, up to the newest hour `volume_hour`.
+    pub volume_buckets: [u64; VOLUME_BUCKETS],
+    pub volume_hour: i64,
+    /// Which programs may trade in the market by CPI, set by the subject.
+    pub router_policy: RouterPolicy,
+    /// Programs allowed under `RouterPolicy::Allowlist`; unused slots are
+    /// `Pubkey::default()`.
+    pub routers: [Pubkey; MAX_ROUTERS],
+    /// Key acting as the subject: it signs the subject's instructions and
+    /// claims their fees. It starts as `subject`, which stays the seed of the
+    /// market's address, and changes through `AcceptSubjectRole`.
+    pub authority: Pubkey,
+    /// Key nominated by `NominateSuccessor` to take over `authority`, and the
+    /// unix timestamp it may accept from.
+    pub successor: Option<Pubkey>,
+    pub succession_after: i64,
+    /// Quote tokens a sender pays per message to the subject, set by the
+    /// subject and split like trading fees; see `PayMessageFee`.
+    pub message_fee: u64,
+    /// Quote token account the subject's fees stream to at `fee_stream_rate`
+    /// per second, counted from `fee_streamed_at`; see `SetFeeStream`.
+    pub fee_beneficiary: Option<Pubkey>,
+    pub fee_stream_rate: u64,
+    pub fee_streamed_at: i64,
+    /// Members of a group market, e.g. a podcast's hosts, with their quote
+    /// token accounts and weights in the subject fees, set at creation and
+    /// filled from the front. Empty for a single subject's market.
+    pub group: [FeeRecipient; MAX_GROUP_MEMBERS],
+    /// Positions holding shares, counted as trades, presales and airdrops
+    /// take balances up from and down to zero.
+    pub holders: u64,
+    /// Whether trades record into the market's `Candles`, set once
+    /// `CreateCandles` opts the market in. Trades then take the candles PDA
+    /// after their other accounts.
+    pub candles: bool,
+    pub bump: u8,
+}
+
+impl Market {
+    /// An empty market of `subject` in `quote_mint` at the PDA with `bump`,
+    /// trading from `vault` at the fee rates of `config`.
+    pub fn new(subject: Pubkey, quote_mint: Pubkey, vault: Pubkey, config: &Config, bump: u8) -> Self {
+        Market {
+            account_type: AccountType::Market,
+            subject,
+            quote_mint,
+            vault,
+            supply: 0,
+            protocol_fee_bps: config.protocol_fee_bps,
+            subject_fee_bps: config.subject_fee_bps,
+            protocol_fees: 0,
+            subject_fees: 0,
+            last_trade_epoch: u64::MAX,
+            protocol_fees_enabled: true,
+            min_hold_duration: 0,
+            pause: MarketPause::Unpaused,
+            sell_tax_bps: 0,
+            sell_tax_period: 0,
+            dump_window_start: 0,
+            dump_window_supply: 0,
+            dump_window_sold: 0,
+            opens_at: 0,
+            emission_rate: 0,
+            reward_index: 0,
+            rewards_updated_at: 0,
+            volume_buckets: [0; VOLUME_BUCKETS],
+            volume_hour: 0,
+            router_policy: RouterPolicy::Open,
+            routers: [Pubkey::default(); MAX_ROUTERS],
+            authority: subject,
+            successor: None,
+            succession_after: 0,
+            message_fee: 0,
+            fee_beneficiary: None,
+            fee_stream_rate: 0,
+            fee_streamed_at: 0,
+            group: [FeeRecipient::default(); MAX_GROUP_MEMBERS],
+            holders: 0,
+            candles: false,
+            bump,
+        }
+    }
+}
+
+/// Stage of the program's wind-down at a given time; see `StartWindDown`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WindDownStage {
+    Active,
+    /// Nothing can be bought; holders sell at the curve price without sell
+    /// tax, dump fee, hold period or subject pause, and fees are claimed as
+    /// before.
+    SellsOnly,
+    /// Past the deadline, sells stop too and holders redeem their shares for
+    /// their part of the vault; see `redemption_amount`.
+    Redeeming,
+}
+
+/// Which trades a subject's pause still allows in their market.
+#[derive(Clone, Copy, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub enum MarketPause {
+    Unpaused,
+    /// Holders may still sell, but nobody can buy.
+    SellsOnly,
+    Halted,
+}
+
+/// Whether trades may reach a market through other programs. Direct trades,
+/// whose instruction is a transaction's own, are always allowed.
+#[derive(Clone, Copy, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub enum RouterPolicy {
+    Open,
+    /// No trades by CPI, e.g. to keep out automated aggregator flow.
+    DirectOnly,
+    /// Trades by CPI only from transaction instructions of a program in
+    /// `Market::routers`.
+    Allowlist,
+}
+
+/// Share account of the program's first release, stored at the address of
+/// the owner's position in its market. Loading it as a position migrates it
+/// in place; see `load_position`.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct LegacyShareAccount {
+    pub owner: Pubkey,
+    pub balance: u64,
+}
 
-/// Represents a shareholder account with ownership and balance details.
+/// A holder's shares in one market, stored at the `[POSITION_SEED, market, owner]` PDA.
+/// The market is not stored, as the address re-derived from `bump` names it,
+/// and timestamps and flags are packed behind accessors, keeping the most
+/// numerous account under 128 bytes.
 #[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
-pub struct ShareAccount {
+pub struct Position {
+    pub account_type: AccountType,
     pub owner: Pubkey,
     pub balance: u64,
+    acquired_at: u32,
+    tax_basis_at: u32,
+    veto_locked_until: u32,
+    /// Market reward index the position last accrued at, and the rewards
+    /// accrued but not yet claimed.
+    pub reward_index: u128,
+    pub rewards_owed: u64,
+    /// Locked, delegated and vesting flags in the low bits, tier above them.
+    flags: u8,
+    /// Collateral program holding the lock, if the locked flag is set.
+    lender: Pubkey,
+    pub bump: u8,
+}
+
+impl Position {
+    const LOCKED: u8 = 1 << 0;
+    const DELEGATED: u8 = 1 << 1;
+    const VESTING: u8 = 1 << 2;
+    const TIER_SHIFT: u32 = 3;
+    /// Highest tier the flags have room for.
+    pub const MAX_TIER: u8 = u8::MAX >> Self::TIER_SHIFT;
+
+    /// An empty position for `owner` at the PDA with `bump`.
+    pub fn new(owner: Pubkey, bump: u8) -> Self {
+        Position {
+            account_type: AccountType::Position,
+            owner,
+            balance: 0,
+            acquired_at: 0,
+            tax_basis_at: 0,
+            veto_locked_until: 0,
+            reward_index: 0,
+            rewards_owed: 0,
+            flags: 0,
+            lender: Pubkey::default(),
+            bump,
+        }
+    }
+
+    /// Unix timestamp of the buy that last took the balance up from zero.
+    pub fn acquired_at(&self) -> i64 {
+        self.acquired_at as i64
+    }
+
+    pub fn set_acquired_at(&mut self, timestamp: i64) {
+        self.acquired_at = compact_timestamp(timestamp);
+    }
+
+    /// Share-weighted average unix timestamp of the buys making up the
+    /// balance, from which the market's sell tax decays.
+    pub fn tax_basis_at(&self) -> i64 {
+        self.tax_basis_at as i64
+    }
+
+    pub fn set_tax_basis_at(&mut self, timestamp: i64) {
+        self.tax_basis_at = compact_timestamp(timestamp);
+    }
+
+    /// Unix timestamp until which the shares back a veto, during which they
+    /// cannot be sold or escrowed; see `VetoProposal`.
+    pub fn veto_locked_until(&self) -> i64 {
+        self.veto_locked_until as i64
+    }
+
+    pub fn set_veto_locked_until(&mut self, timestamp: i64) {
+        self.veto_locked_until = compact_timestamp(timestamp);
+    }
+
+    /// Collateral program the position is locked for; a locked position
+    /// cannot be sold or escrowed until that program unlocks it.
+    pub fn locked_by(&self) -> Option<Pubkey> {
+        (self.flags & Self::LOCKED != 0).then_some(self.lender)
+    }
+
+    pub fn set_locked_by(&mut self, lender: Option<Pubkey>) {
+        self.set_flag(Self::LOCKED, lender.is_some());
+        self.lender = lender.unwrap_or_default();
+    }
+
+    pub fn is_delegated(&self) -> bool {
+        self.flags & Self::DELEGATED != 0
+    }
+
+    pub fn set_delegated(&mut self, delegated: bool) {
+        self.set_flag(Self::DELEGATED, delegated);
+    }
+
+    pub fn is_vesting(&self) -> bool {
+        self.flags & Self::VESTING != 0
+    }
+
+    pub fn set_vesting(&mut self, vesting: bool) {
+        self.set_flag(Self::VESTING, vesting);
+    }
+
+    pub fn tier(&self) -> u8 {
+        self.flags >> Self::TIER_SHIFT
+    }
+
+    /// Sets the tier, capped at `MAX_TIER`.
+    pub fn set_tier(&mut self, tier: u8) {
+        let low_bits = (1 << Self::TIER_SHIFT) - 1;
+        self.flags = (self.flags & low_bits) | (tier.min(Self::MAX_TIER) << Self::TIER_SHIFT);
+    }
+
+    fn set_flag(&mut self, flag: u8, set: bool) {
+        if set {
+            self.flags |= flag;
+        } else {
+            self.flags &= !flag;
+        }
+    }
+}
+
+/// A unix timestamp in the `u32` positions store, clamped to the range
+/// from 1970 to 2106.
+fn compact_timestamp(timestamp: i64) -> u32 {
+    timestamp.clamp(0, u32::MAX as i64) as u32
+}
+
+/// Protocol-wide counters, stored at the `[STATS_SEED]` PDA and updated by
+/// market creation and every trade. Volume and fees are summed in base units
+/// across all quote mints.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct Stats {
+    pub account_type: AccountType,
+    pub total_markets: u64,
+    /// Curve price of all trades, before fees.
+    pub total_volume: u64,
+    pub total_fees: u64,
+    /// Epoch the `epoch_active_markets` count is for.
+    pub epoch: u64,
+    /// Markets that traded in `epoch`.
+    pub epoch_active_markets: u64,
+    /// Markets that traded in the epoch before `epoch`.
+    pub last_epoch_active_markets: u64,
+    pub bump: u8,
+}
+
+impl Stats {
+    /// Starts a new count of active markets when `epoch` has moved on.
+    fn roll_epoch(&mut self, epoch: u64) {
+        if epoch == self.epoch {
+            return;
+        }
+        self.last_epoch_active_markets = if epoch == self.epoch + 1 { self.epoch_active_markets } else { 0 };
+        self.epoch_active_markets = 0;
+        self.epoch = epoch;
+    }
+
+    /// Adds a trade in `market` during `epoch`. Counters saturate rather than
+    /// fail, so stats can never block trading.
+    fn record_trade(&mut self, market: &mut Market, settlement: &TradeSettlement, epoch: u64) {
+        self.roll_epoch(epoch);
+        if market.last_trade_epoch != epoch {
+            market.last_trade_epoch = epoch;
+            self.epoch_active_markets = self.epoch_active_markets.saturating_add(1);
+        }
+        self.total_volume = self.total_volume.saturating_add(settlement.gross_price);
+        self.total_fees = self
+            .total_fees
+            .saturating_add(settlement.protocol_fee)
+            .saturating_add(settlement.subject_fee);
+    }
+}
+
+/// Lifecycle of a `Proposal`.
+#[derive(Clone, Copy, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub enum ProposalStatus {
+    Pending,
+    Executed,
+    Vetoed,
+}
+
+/// A subject's pending change to their market's parameters, stored at the
+/// `[PROPOSAL_SEED, market]` PDA. A new proposal replaces the previous one
+/// and restarts the delay.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct Proposal {
+    pub account_type: AccountType,
+    pub market: Pubkey,
+    /// Increments with every proposal for the market, so veto records from
+    /// earlier proposals do not count.
+    pub id: u64,
+    pub subject_fee_bps: u16,
+    pub sell_tax_bps: u16,
+    pub sell_tax_period: i64,
+    /// Unix timestamp from which the proposal can execute; vetoes are only
+    /// accepted before it.
+    pub executable_at: i64,
+    /// Market supply when the proposal was made, which the veto threshold
+    /// is measured against.
+    pub supply: u64,
+    /// Shares held by the holders that vetoed.
+    pub veto_weight: u64,
+    pub status: ProposalStatus,
+    pub bump: u8,
+}
+
+impl Proposal {
+    /// Whether `veto_weight` is at least `threshold_bps` of `supply`.
+    fn veto_reached(&self, threshold_bps: u16) -> bool {
+        self.veto_weight as u128 * BPS_DENOMINATOR as u128 >= self.supply as u128 * threshold_bps as u128
+    }
+}
+
+/// Marks a holder's veto of one market's proposal, stored at the
+/// `[VETO_SEED, proposal, voter]` PDA and reused by later proposals.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct VetoRecord {
+    pub account_type: AccountType,
+    pub proposal: Pubkey,
+    pub voter: Pubkey,
+    /// `Proposal::id` the voter last vetoed.
+    pub proposal_id: u64,
+    pub bump: u8,
+}
+
+/// Social graph edge from a holder to a subject, stored at the
+/// `[HOLDING_SEED, holder, subject]` PDA and maintained by trades. The holder
+/// holds the subject while `markets` is non-zero; mutual holding is two edges
+/// in opposite directions.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct Holding {
+    pub account_type: AccountType,
+    pub holder: Pubkey,
+    pub subject: Pubkey,
+    /// Markets of the subject, one per quote mint, in which the holder has
+    /// a non-zero balance.
+    pub markets: u32,
+    pub bump: u8,
+}
+
+/// A holder's recurring buy of one market, stored at the
+/// `[DCA_SEED, market, owner]` PDA. Its quote tokens are escrowed in a token
+/// account at the `[DCA_ESCROW_SEED, schedule]` PDA, owned by the schedule.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct DcaSchedule {
+    pub account_type: AccountType,
+    pub owner: Pubkey,
+    pub market: Pubkey,
+    pub escrow: Pubkey,
+    /// Most quote tokens, fees included, that one execution spends.
+    pub amount_per_interval: u64,
+    /// Seconds from one execution until the next is due.
+    pub interval: i64,
+    /// Escrowed quote tokens not spent yet.
+    pub remaining: u64,
+    /// Unix timestamp from which the next execution is due.
+    pub next_execution_at: i64,
+    pub bump: u8,
+}
+
+/// What an `Airdrop` distributes.
+#[derive(Clone, Copy, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub enum AirdropKind {
+    /// Shares moved out of the subject's own position.
+    Shares,
+    /// Quote tokens escrowed in a token account at the
+    /// `[AIRDROP_ESCROW_SEED, airdrop]` PDA, owned by the airdrop.
+    QuoteTokens,
+}
+
+/// A subject's distribution to a snapshot of addresses, stored at the
+/// `[AIRDROP_SEED, market, id]` PDA. Each leaf of the Merkle tree under
+/// `root` is `airdrop_leaf(index, claimant, amount)`.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct Airdrop {
+    pub account_type: AccountType,
+    pub market: Pubkey,
+    pub id: u64,
+    pub root: [u8; 32],
+    pub kind: AirdropKind,
+    /// Shares or quote tokens not claimed yet.
+    pub remaining: u64,
+    /// Bit `index` is set once the leaf at `index` has claimed.
+    pub claimed: [u8; AIRDROP_BITMAP_BYTES],
+    pub bump: u8,
 }
 
+impl Airdrop {
+    fn is_claimed(&self, index: u32) -> bool {
+        self.claimed[index as usize / 8] & (1 << (index % 8)) != 0
+    }
+
+    fn set_claimed(&mut self, index: u32) {
+        self.claimed[index as usize / 8] |= 1 << (index % 8);
+    }
+}
+
+/// A fixed-price sale of a market's first shares to an allowlist, stored at
+/// the `[PRESALE_SEED, market]` PDA. Each leaf of the Merkle tree under
+/// `root` is `presale_leaf(buyer, cap)`.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct Presale {
+    pub account_type: AccountType,
+    pub market: Pubkey,
+    pub root: [u8; 32],
+    /// Quote tokens per share.
+    pub price: u64,
+    /// Shares on offer and shares sold so far. Unsold shares are never
+    /// minted, so the public curve opens at the presale's sold supply.
+    pub allocation: u64,
+    pub sold: u64,
+    pub bump: u8,
+}
+
+/// On-chain record of a settled trade, for integrators that verify payments
+/// without reading logs. Trades write one when given a `receipt_id`, at the
+/// `[RECEIPT_SEED, trader, receipt_id]` PDA; using the transaction's recent
+/// blockhash as the id makes the receipt findable from the transaction alone.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct TradeReceipt {
+    pub account_type: AccountType,
+    pub market: Pubkey,
+    pub trader: Pubkey,
+    pub is_buy: bool,
+    pub amount: u64,
+    /// Quote tokens the trader paid for a buy or received for a sale, fees
+    /// included.
+    pub net_amount: u64,
+    /// Slot the trade settled in.
+    pub slot: u64,
+    /// Account that paid the receipt's rent and gets it back on closing.
+    pub rent_payer: Pubkey,
+    pub bump: u8,
+}
+
+/// A sender's paid messages to a market's subject, stored at the
+/// `[ACCESS_SEED, market, sender]` PDA. Off-chain chat services count the
+/// messages paid for against the messages delivered.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct MessageAccess {
+    pub account_type: AccountType,
+    pub market: Pubkey,
+    pub sender: Pubkey,
+    /// Messages paid for so far.
+    pub messages: u64,
+    /// Quote tokens paid for them.
+    pub fees_paid: u64,
+    pub bump: u8,
+}
+
+/// Per-share trade prices of one hour of a market in quote tokens, with the
+/// hour's gross volume and trade count. A candle without trades is empty.
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct Candle {
+    pub open: u64,
+    pub high: u64,
+    pub low: u64,
+    pub close: u64,
+    pub volume: u64,
+    pub trades: u32,
+}
+
+/// Hourly price candles of a market for frontends to chart from the chain
+/// alone, stored at the `[CANDLES_SEED, market]` PDA once `CreateCandles`
+/// opts the market in. The candle of unix hour `h` is at index
+/// `h % CANDLE_BUCKETS`, up to the newest hour `hour`.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct Candles {
+    pub account_type: AccountType,
+    pub market: Pubkey,
+    pub candles: [Candle; CANDLE_BUCKETS],
+    pub hour: i64,
+    pub bump: u8,
+}
+
+/// Merkle leaf allowing `buyer` up to `cap` shares of a presale.
+pub fn presale_leaf(buyer: &Pubkey, cap: u64) -> [u8; 32] {
+    merkle::leaf(&[buyer.as_ref(), &cap.to_le_bytes()])
+}
+
+/// Merkle leaf granting `claimant` `amount` shares or quote tokens as leaf
+/// `index` of an airdrop.
+pub fn airdrop_leaf(index: u32, claimant: &Pubkey, amount: u64) -> [u8; 32] {
+    merkle::leaf(&[&index.to_le_bytes(), claimant.as_ref(), &amount.to_le_bytes()])
+}
+
+impl_borsh_pack!(Config, AccountType::Config, 1 + 32 + 32 + 2 + 2 + 8 + 2 + 1 + 2 + 2 + 33 + 8 + 34 * MAX_FEE_RECIPIENTS + 32 * MAX_COLLATERAL_PROGRAMS + 9 + 1);
+impl_borsh_pack!(Profile, AccountType::Profile, 1 + 32 + 32 + 32 + 64 + 1);
+impl_borsh_pack!(Market, AccountType::Market, 1 + 32 + 32 + 32 + 8 + 2 + 2 + 8 + 8 + 8 + 1 + 8 + 1 + 2 + 8 + 8 + 8 + 8 + 8 + 8 + 16 + 8 + 8 * VOLUME_BUCKETS + 8 + 1 + 32 * MAX_ROUTERS + 32 + 33 + 8 + 8 + 33 + 8 + 8 + 34 * MAX_GROUP_MEMBERS + 8 + 1 + 1);
+impl_borsh_pack!(Position, AccountType::Position, 1 + 32 + 8 + 4 + 4 + 4 + 16 + 8 + 1 + 32 + 1);
+impl_borsh_pack!(Stats, AccountType::Stats, 1 + 8 + 8 + 8 + 8 + 8 + 8 + 1);
+impl_borsh_pack!(Proposal, AccountType::Proposal, 1 + 32 + 8 + 2 + 2 + 8 + 8 + 8 + 8 + 1 + 1);
+impl_borsh_pack!(VetoRecord, AccountType::VetoRecord, 1 + 32 + 32 + 8 + 1);
+impl_borsh_pack!(Holding, AccountType::Holding, 1 + 32 + 32 + 4 + 1);
+impl_borsh_pack!(DcaSchedule, AccountType::DcaSchedule, 1 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 1);
+impl_borsh_pack!(Presale, AccountType::Presale, 1 + 32 + 32 + 8 + 8 + 8 + 1);
+impl_borsh_pack!(Airdrop, AccountType::Airdrop, 1 + 32 + 8 + 32 + 1 + 8 + AIRDROP_BITMAP_BYTES + 1);
+impl_borsh_pack!(TradeReceipt, AccountType::TradeReceipt, 1 + 32 + 32 + 1 + 8 + 8 + 8 + 32 + 1);
+impl_borsh_pack!(MessageAccess, AccountType::MessageAccess, 1 + 32 + 32 + 8 + 8 + 1);
+impl_borsh_pack!(Candles, AccountType::Candles, 1 + 32 + CANDLE_BUCKETS * (5 * 8 + 4) + 8 + 1);
+
+// Positions are the most numerous accounts, so their rent adds up the most.
+const _: () = assert!(Position::LEN < 128);
+
 /// Custom errors to represent specific failure reasons in the FriendTech program.
 pub enum FriendtechError {
     IncorrectOwner,
     InsufficientFunds,
+    InvalidAttestation,
+    AccountMismatch,
+    Unauthorized,
+    InvalidFee,
+    InvalidBasket,
+    ProposalNotReady,
+    ProposalClosed,
+    AlreadyVoted,
+    UninitializedAccount,
+    HoldPeriodActive,
+    MarketPaused,
+    SlippageExceeded,
+    DcaNotDue,
+    InvalidProof,
+    AlreadyClaimed,
+    MarketNotOpen,
+    PresaleClosed,
+    FeatureDisabled,
+    UnclaimedRewards,
+    LegacyAccount,
+    VaultBalanceMismatch,
+    RouterNotAllowed,
+    InvalidFeeSplit,
+    SuccessionLocked,
+    ReceiptRetained,
+    PositionLocked,
+    FeesStreaming,
+    PriceOutOfBand,
+    DuplicateAccount,
+    WindingDown,
+    RedemptionNotOpen,
+    SellTaxIncrease,
 }
 impl From<FriendtechError> for ProgramError {
     fn from(e: FriendtechError) -> Self {
@@ -32,14 +885,1120 @@ impl From<FriendtechError> for ProgramError {
     }
 }
 
+/// Tag of the `sol_log_data` entry carrying an `ErrorLog`.
+pub const ERROR_LOG_TAG: &[u8] = b"error";
+/// `ErrorLog::account_index` of failures not tied to a specific account.
+pub const NO_ACCOUNT: u8 = u8::MAX;
+
+/// Structured failure details, logged as `[ERROR_LOG_TAG, borsh(ErrorLog)]`
+/// right before the program returns an error.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct ErrorLog {
+    /// `u64::from(ProgramError)`: the custom code or the builtin error code.
+    pub code: u64,
+    /// Index of the offending account in the instruction, or `NO_ACCOUNT`.
+    pub account_index: u8,
+    /// Expected and actual values of the failed check, zero when not numeric.
+    pub expected: u64,
+    pub actual: u64,
+}
+
+/// Error returned by instruction handlers: the `ProgramError` and the
+/// context logged for it by `process_instruction`.
+#[derive(Debug, PartialEq)]
+struct ErrorReport {
+    error: ProgramError,
+    log: ErrorLog,
+}
+
+impl ErrorReport {
+    fn at(error: impl Into<ProgramError>, account_index: u8) -> Self {
+        let error = error.into();
+        let log = ErrorLog { code: u64::from(error.clone()), account_index, expected: 0, actual: 0 };
+        ErrorReport { error, log }
+    }
+
+    fn values(mut self, expected: u64, actual: u64) -> Self {
+        self.log.expected = expected;
+        self.log.actual = actual;
+        self
+    }
+
+    fn log(&self) {
+        if let Ok(payload) = self.log.try_to_vec() {
+            sol_log_data(&[ERROR_LOG_TAG, &payload]);
+        }
+    }
+}
+
+impl From<ProgramError> for ErrorReport {
+    fn from(e: ProgramError) -> Self {
+        ErrorReport::at(e, NO_ACCOUNT)
+    }
+}
+
+impl From<FriendtechError> for ErrorReport {
+    fn from(e: FriendtechError) -> Self {
+        ErrorReport::at(e, NO_ACCOUNT)
+    }
+}
+
+impl From<std::io::Error> for ErrorReport {
+    fn from(e: std::io::Error) -> Self {
+        ErrorReport::at(e, NO_ACCOUNT)
+    }
+}
+
+/// Attaches the index of the account a failed check was about.
+trait AtAccount<T> {
+    fn at_account(self, account_index: u8) -> Result<T, ErrorReport>;
+}
+
+impl<T, E: Into<ProgramError>> AtAccount<T> for Result<T, E> {
+    fn at_account(self, account_index: u8) -> Result<T, ErrorReport> {
+        self.map_err(|e| ErrorReport::at(e, account_index))
+    }
+}
+
+/// Schema version of the events the program logs. Events are logged as
+/// `[tag, [version], borsh(event)]`, and a version that changes an event's
+/// layout only appends fields, so older decoders still read the fields they
+/// know. Events logged before versioning have no version field; see
+/// `sdk::events` for decoding every version.
+pub const EVENT_SCHEMA_VERSION: u8 = 1;
+
+/// Tag of the `sol_log_data` entry carrying a `FeeSwitchEvent`.
+pub const FEE_SWITCH_LOG_TAG: &[u8] = b"fee_switch";
+
+/// Logged as `[FEE_SWITCH_LOG_TAG, [version], borsh(FeeSwitchEvent)]`
+/// whenever a fee switch is set.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct FeeSwitchEvent {
+    /// Market whose switch was set, or `None` for the global switch.
+    pub market: Option<Pubkey>,
+    pub protocol_fees_enabled: bool,
+}
+
+/// Tag of the `sol_log_data` entry carrying a `HoldingProofEvent`.
+pub const HOLDING_PROOF_LOG_TAG: &[u8] = b"holding_proof";
+
+/// Logged as `[HOLDING_PROOF_LOG_TAG, [version], borsh(HoldingProofEvent)]`
+/// by `ProveHolding`. Services verify a proof by finding it in a confirmed
+/// transaction's logs of this program with the nonce they issued.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct HoldingProofEvent {
+    pub market: Pubkey,
+    pub owner: Pubkey,
+    /// Shares the owner proved to hold at least.
+    pub min_balance: u64,
+    pub nonce: [u8; 32],
+    pub slot: u64,
+}
+
+/// Tag of the `sol_log_data` entry carrying an `InsurancePayoutEvent`.
+pub const INSURANCE_LOG_TAG: &[u8] = b"insurance";
+
+/// Logged as `[INSURANCE_LOG_TAG, [version], borsh(InsurancePayoutEvent)]`
+/// for every payout from an insurance vault.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct InsurancePayoutEvent {
+    /// Market whose holders the payout compensates.
+    pub market: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+}
+
+/// Tag of the `sol_log_data` entry carrying a `MarketReportEvent`.
+pub const MARKET_REPORT_LOG_TAG: &[u8] = b"market_report";
+
+/// Logged as `[MARKET_REPORT_LOG_TAG, [version], borsh(MarketReportEvent)]`
+/// by `CollectMarketReport`, a heartbeat of the market's state for
+/// monitoring.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct MarketReportEvent {
+    pub market: Pubkey,
+    /// Unix timestamp the report was collected at.
+    pub timestamp: i64,
+    pub supply: u64,
+    /// Gross trade volume over the 24 hours up to `timestamp`.
+    pub volume_24h: u64,
+    /// Fees accrued in the vault and not claimed yet.
+    pub protocol_fees: u64,
+    pub subject_fees: u64,
+    pub holders: u64,
+    /// Per-share curve price of buying one share, in millionths of a quote
+    /// base unit, as `PriceImpact::pre_trade_price` quotes it.
+    pub price: u64,
+}
+
+/// Tag of the `sol_log_data` entry carrying a `MarketPauseEvent`.
+pub const MARKET_PAUSE_LOG_TAG: &[u8] = b"market_pause";
+
+/// Logged as `[MARKET_PAUSE_LOG_TAG, [version], borsh(MarketPauseEvent)]`
+/// whenever a subject pauses or unpauses their market.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct MarketPauseEvent {
+    pub market: Pubkey,
+    pub pause: MarketPause,
+}
+
+/// Tag of the `sol_log_data` entry carrying a `SuccessionEvent`.
+pub const SUCCESSION_LOG_TAG: &[u8] = b"succession";
+
+/// Logged as `[SUCCESSION_LOG_TAG, [version], borsh(SuccessionEvent)]`
+/// whenever a market's successor is nominated, withdrawn or takes over, so
+/// holders learn of a handoff before it happens.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct SuccessionEvent {
+    pub market: Pubkey,
+    /// `Market::authority`, `Market::successor` and
+    /// `Market::succession_after` after the instruction.
+    pub authority: Pubkey,
+    pub successor: Option<Pubkey>,
+    pub succession_after: i64,
+}
+
+/// Tag of the `sol_log_data` entry carrying a `TradeEvent`.
+pub const TRADE_LOG_TAG: &[u8] = b"trade";
+
+/// Logged as `[TRADE_LOG_TAG, [version], borsh(TradeEvent)]` after every buy
+/// and sell.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct TradeEvent {
+    pub market: Pubkey,
+    pub trader: Pubkey,
+    pub is_buy: bool,
+    pub amount: u64,
+    pub settlement: TradeSettlement,
+    /// Frontend that submitted the trade, as passed in the instruction.
+    pub client_id: Option<[u8; 8]>,
+    /// Token account paid `settlement.interface_fee`, if any.
+    pub interface_fee_recipient: Option<Pubkey>,
+}
+
+/// A frontend's fee on one trade, charged on top of a buy or taken out of a
+/// sale's proceeds and capped by `Config::max_interface_fee_bps`.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct InterfaceFee {
+    /// Fee in basis points of the trade's curve price.
+    pub fee_bps: u16,
+    /// Quote token account receiving the fee.
+    pub recipient: Pubkey,
+}
+
+/// Return data of `CheckMutualHolding`.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct MutualHolding {
+    pub a_holds_b: bool,
+    pub b_holds_a: bool,
+}
+
+impl MutualHolding {
+    /// Whether each side holds the other.
+    pub fn is_mutual(&self) -> bool {
+        self.a_holds_b && self.b_holds_a
+    }
+}
+
+/// Return data of `ViewFees`.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct MarketFees {
+    pub protocol_fee_bps: u16,
+    pub subject_fee_bps: u16,
+    /// Fees accrued in the vault and not claimed yet.
+    pub protocol_fees: u64,
+    pub subject_fees: u64,
+}
+
+/// Outcome of a successful trade, Borsh-encoded into the transaction's
+/// return data so CPI callers and simulations can read it.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct TradeSettlement {
+    /// Curve price of the traded shares, before fees.
+    pub gross_price: u64,
+    pub protocol_fee: u64,
+    pub subject_fee: u64,
+    /// Paid straight to the trade's frontend, see `InterfaceFee`.
+    pub interface_fee: u64,
+    /// Quote tokens paid by the buyer (gross plus fees) or received by the
+    /// seller (gross minus fees).
+    pub net_amount: u64,
+    pub new_supply: u64,
+}
+
+impl TradeSettlement {
+    /// Adds an interface fee of `fee_bps` of the curve price to a quoted buy,
+    /// or takes it out of a quoted sale when `is_buy` is false.
+    pub fn with_interface_fee(mut self, fee_bps: u16, is_buy: bool) -> Result<Self, ProgramError> {
+        self.interface_fee = (self.gross_price as u128 * fee_bps as u128 / BPS_DENOMINATOR as u128) as u64;
+        self.net_amount = if is_buy {
+            self.net_amount.checked_add(self.interface_fee)
+        } else {
+            self.net_amount.checked_sub(self.interface_fee)
+        }
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+        Ok(self)
+    }
+
+    /// Takes a sell tax of `tax_bps` of the curve price out of a quoted sale
+    /// and adds it to the subject fee.
+    pub fn with_sell_tax(mut self, tax_bps: u16) -> Result<Self, ProgramError> {
+        let tax = (self.gross_price as u128 * tax_bps as u128 / BPS_DENOMINATOR as u128) as u64;
+        self.subject_fee = self.subject_fee.checked_add(tax).ok_or(ProgramError::ArithmeticOverflow)?;
+        self.net_amount = self.net_amount.checked_sub(tax).ok_or(ProgramError::ArithmeticOverflow)?;
+        Ok(self)
+    }
+}
+
+/// How far a trade moves the market's per-share price, for UIs warning
+/// before large trades. Prices are in millionths of a quote base unit per
+/// share, the curve price both buys and sells trade at.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PriceImpact {
+    pub pre_trade_price: u64,
+    pub post_trade_price: u64,
+    /// Change from the pre- to the post-trade price in basis points of the
+    /// former, negative for sells; `i64::MAX` when a buy prices the first
+    /// shares of an empty market.
+    pub price_impact_bps: i64,
+}
+
+/// A market and some of its positions, exported by `ExportMarketSnapshot`
+/// and restored by `ImportMarketSnapshot`. The vault's balance is not part of
+/// the snapshot.
+#[cfg(feature = "devnet")]
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
+pub struct MarketSnapshot {
+    pub market: Market,
+    pub positions: Vec<Position>,
+}
+
 /// Instructions supported by the FriendTech program, including buying and selling of shares.
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize)]
 pub enum FriendtechInstruction {
-    BuyShares { amount: u64 },
-    SellShares { amount: u64 },
+    /// Buys `amount` shares, creating the buyer's position on first purchase.
+    /// The buyer pays the curve price plus fees and the `TradeSettlement` is
+    /// set as return data. `client_id` identifies the submitting frontend in
+    /// the logged `TradeEvent`, and `interface_fee` pays it on top. With a
+    /// `receipt_id`, the buy also writes a `TradeReceipt`, its rent paid by
+    /// the payer.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Payer of the position account's rent
+    /// 1. `[signer]` Buyer, authority of the quote token account
+    /// 2. `[writable]` Market PDA
+    /// 3. `[writable]` Buyer's position PDA
+    /// 4. `[writable]` Buyer's quote token account
+    /// 5. `[writable]` Market vault
+    /// 6. `[]` Token program
+    /// 7. `[]` System program
+    /// 8. `[writable]` Stats PDA
+    /// 9. `[]` Config PDA
+    /// 10. `[writable]` Buyer's holding PDA for the subject
+    /// 11. `[writable]` Interface fee recipient, when `interface_fee` is set
+    /// 12. `[writable]` Receipt PDA, when `receipt_id` is set
+    ///
+    /// Optional accounts that are left out shift the ones after them down.
+    /// Trades into a market that `CreateCandles` opted in pass the market's
+    /// `Candles` PDA, writable, after them and record into it. Trades invoked
+    /// by CPI into a market under `RouterPolicy::Allowlist` also pass the
+    /// instructions sysvar after all other accounts.
+    BuyShares {
+        amount: u64,
+        client_id: Option<[u8; 8]>,
+        interface_fee: Option<InterfaceFee>,
+        receipt_id: Option<[u8; 32]>,
+    },
+    /// Sells `amount` shares back to the market vault. The seller receives the
+    /// curve price minus fees, sell tax and dump fee, the latter two counting
+    /// as subject fee, and the `TradeSettlement` is set as return data.
+    /// `client_id`, `interface_fee` and `receipt_id` work as for `BuyShares`,
+    /// with the fee taken out of the proceeds.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Seller
+    /// 1. `[writable]` Market PDA
+    /// 2. `[writable]` Seller's position PDA
+    /// 3. `[writable]` Seller's quote token account
+    /// 4. `[writable]` Market vault
+    /// 5. `[]` Token program
+    /// 6. `[writable]` Stats PDA
+    /// 7. `[]` Config PDA
+    /// 8. `[writable]` Seller's holding PDA for the subject
+    /// 9. `[writable]` Interface fee recipient, when `interface_fee` is set
+    /// 10. `[signer, writable]` Payer of the receipt's rent, when `receipt_id`
+    ///     is set
+    /// 11. `[writable]` Receipt PDA, when `receipt_id` is set
+    /// 12. `[]` System program, when `receipt_id` is set
+    ///
+    /// Optional accounts that are left out shift the ones after them down.
+    /// The market's `Candles` PDA follows them as for `BuyShares`.
+    ///
+    /// While the program winds down, sells skip the sell tax, dump fee, hold
+    /// period and subject pause, and they stop at the wind-down deadline.
+    SellShares {
+        amount: u64,
+        client_id: Option<[u8; 8]>,
+        interface_fee: Option<InterfaceFee>,
+        receipt_id: Option<[u8; 32]>,
+    },
+    /// Creates the global config and stats PDAs with the signer as admin. The
+    /// program's upgrade authority must sign, so only its deployer can.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Admin, pays for the config and stats accounts
+    /// 1. `[writable]` Config PDA
+    /// 2. `[]` System program
+    /// 3. `[writable]` Stats PDA
+    /// 4. `[]` Program's `ProgramData` account, the upgradeable loader's
+    ///    `[program_id]` PDA
+    /// 5. `[signer]` Program's upgrade authority
+    InitializeConfig {
+        attestor: Pubkey,
+        protocol_fee_bps: u16,
+        subject_fee_bps: u16,
+        proposal_delay: i64,
+        veto_threshold_bps: u16,
+        max_interface_fee_bps: u16,
+        insurance_fee_bps: u16,
+    },
+    /// Records an attestor-signed binding between the signing subject and a
+    /// hashed social handle in the subject's profile, creating it if needed.
+    /// The preceding instruction must be an Ed25519 program instruction over
+    /// `attestation_message(program_id, subject, handle_hash)`.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Payer of the profile account's rent
+    /// 1. `[signer]` Subject
+    /// 2. `[writable]` Profile PDA
+    /// 3. `[]` Config PDA
+    /// 4. `[]` Instructions sysvar
+    /// 5. `[]` System program
+    AttestIdentity { handle_hash: [u8; 32], attestor_sig: [u8; 64] },
+    /// Opens the signing subject's market in the given quote mint, with its
+    /// own curve and vault. A subject may open one market per quote mint.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Payer of the market and vault rent
+    /// 1. `[signer]` Subject
+    /// 2. `[writable]` Market PDA
+    /// 3. `[writable]` Vault PDA
+    /// 4. `[]` Quote mint
+    /// 5. `[]` Token program
+    /// 6. `[]` System program
+    /// 7. `[]` Config PDA
+    /// 8. `[writable]` Stats PDA
+    CreateMarket,
+    /// Moves lamports above the rent-exempt minimum out of a program-owned
+    /// account, e.g. after an accidental transfer. The admin may sweep any
+    /// account; a subject may sweep their own market and profile. Account
+    /// data, and with it share supply and balances, is never modified, and
+    /// vaults are owned by the token program so they cannot be targeted.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Config admin or the account's subject
+    /// 1. `[writable]` Program-owned account to sweep
+    /// 2. `[writable]` Recipient
+    /// 3. `[]` Config PDA
+    SweepLamports,
+    /// Transfers the market's accrued subject fees out of the vault, split
+    /// across the members of a group market. Fails while the fees stream to
+    /// a beneficiary; see `SetFeeStream`.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Subject
+    /// 1. `[writable]` Market PDA
+    /// 2. `[writable]` Market vault
+    /// 3. `[writable]` Destination quote token account; of a group market,
+    ///    the first member's
+    /// 4. `[]` Token program
+    /// 5. `[writable]` Quote token accounts of the group's other members,
+    ///    in order
+    ClaimSubjectFees,
+    /// Transfers the market's accrued protocol fees out of the vault, routing
+    /// the config's insurance share of them, if any, to the insurance vault.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Config admin
+    /// 1. `[]` Config PDA
+    /// 2. `[writable]` Market PDA
+    /// 3. `[writable]` Market vault
+    /// 4. `[writable]` Destination quote token account, or the first fee split
+    ///    recipient's destination while `Config::fee_split` is set
+    /// 5. `[]` Token program
+    /// 6. `[writable]` Insurance vault PDA of the market's quote mint, when
+    ///    `Config::insurance_fee_bps` is non-zero
+    /// 7. `[writable]` Destinations of the remaining fee split recipients, in
+    ///    order
+    ///
+    /// Optional accounts that are left out shift the ones after them down.
+    ClaimProtocolFees,
+    /// Splits `budget` across markets by `weights_bps` (summing to 10,000) and
+    /// buys as many shares of each as its slice affords. Unspent rounding stays
+    /// with the buyer, and a market whose slice buys nothing is skipped. The
+    /// per-market `TradeSettlement`s are set as return data, in order, and
+    /// every buy logs a `TradeEvent` with `client_id`. Markets recording
+    /// candles, see `CreateCandles`, cannot be bought in a basket.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Payer of new position accounts' rent
+    /// 1. `[signer]` Buyer, authority of the quote token account
+    /// 2. `[writable]` Buyer's quote token account
+    /// 3. `[]` Token program
+    /// 4. `[]` System program
+    /// 5. `[writable]` Stats PDA
+    /// 6. `[]` Config PDA
+    ///
+    /// Then, for each weight:
+    /// 0. `[writable]` Market PDA
+    /// 1. `[writable]` Buyer's position PDA
+    /// 2. `[writable]` Market vault
+    /// 3. `[writable]` Buyer's holding PDA for the market's subject
+    BuyBasket { budget: u64, weights_bps: Vec<u16>, client_id: Option<[u8; 8]> },
+    /// Proposes a new subject fee and sell tax for the signing subject's
+    /// market, the only way to raise the tax. It takes effect through
+    /// `ExecuteProposal` once the config's proposal delay has passed, unless
+    /// holders veto it first. Replaces any earlier proposal.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Payer of the proposal account's rent
+    /// 1. `[signer]` Subject
+    /// 2. `[]` Market PDA
+    /// 3. `[writable]` Proposal PDA
+    /// 4. `[]` Config PDA
+    /// 5. `[]` System program
+    ProposeParameters { subject_fee_bps: u16, sell_tax_bps: u16, sell_tax_period: i64 },
+    /// Adds the signing holder's shares to the veto weight of a pending
+    /// proposal. Once the weight reaches the config's veto threshold of the
+    /// market's supply when the proposal was made, the proposal can no
+    /// longer execute. The holder's shares cannot be sold or escrowed until
+    /// the proposal's `executable_at`, so they count towards one veto only.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Payer of the veto record's rent
+    /// 1. `[signer]` Holder
+    /// 2. `[]` Market PDA
+    /// 3. `[writable]` Holder's position PDA
+    /// 4. `[writable]` Proposal PDA
+    /// 5. `[writable]` Holder's veto record PDA
+    /// 6. `[]` Config PDA
+    /// 7. `[]` System program
+    VetoProposal,
+    /// Applies a pending proposal whose delay has passed. Anyone may execute.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Market PDA
+    /// 1. `[writable]` Proposal PDA
+    ExecuteProposal,
+    /// Turns protocol fees on or off for one market, or globally when no
+    /// market is passed, and logs a `FeeSwitchEvent`.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Config admin
+    /// 1. `[writable]` Config PDA
+    /// 2. `[writable]` Market PDA (optional)
+    SetFeeSwitch { protocol_fees_enabled: bool },
+    /// Sets how long new positions in the signing subject's market must be
+    /// held before they can sell, up to `MAX_MIN_HOLD_DURATION` seconds. The
+    /// duration applies to existing positions too.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Subject
+    /// 1. `[writable]` Market PDA
+    SetMinHoldDuration { min_hold_duration: i64 },
+    /// Sets a `MutualHolding` of whether `a` holds `b` and `b` holds `a` as
+    /// return data. Holding PDAs that do not exist yet count as not holding.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Holding PDA of `a` for `b`
+    /// 1. `[]` Holding PDA of `b` for `a`
+    CheckMutualHolding { a: Pubkey, b: Pubkey },
+    /// Pauses or unpauses trading in the signing subject's market, e.g.
+    /// during a dispute or migration, and logs a `MarketPauseEvent`. With
+    /// `allow_sells`, a paused market still lets holders exit.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Subject
+    /// 1. `[writable]` Market PDA
+    SetMarketPaused { paused: bool, allow_sells: bool },
+    /// Buys as many whole shares as `budget` pays for, fees included, and
+    /// charges exactly their price; the rest of the budget is never taken.
+    /// Fails if fewer than `min_shares`, or no shares at all, are affordable.
+    ///
+    /// Accounts expected: as for `BuyShares`, without an interface fee
+    /// recipient or receipt PDA.
+    BuyMaxForBudget { budget: u64, min_shares: u64 },
+    /// Sells the seller's whole balance as read on-chain, subject to the
+    /// market's pause and hold period. With `close_position`, the emptied
+    /// position account is closed and its rent refunded.
+    ///
+    /// Accounts expected: as for `SellShares`, without the optional accounts,
+    /// then
+    /// 9. `[writable]` Receiver of the position's rent, when `close_position` is set
+    SellAll { close_position: bool },
+    /// Escrows `total` quote tokens from the owner for a schedule that buys
+    /// up to `amount_per_interval` worth of `market` shares every `interval`
+    /// seconds, starting immediately. One schedule per owner and market.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Payer of the schedule and escrow rent
+    /// 1. `[signer]` Owner, authority of the quote token account
+    /// 2. `[]` Market PDA
+    /// 3. `[writable]` DCA schedule PDA
+    /// 4. `[writable]` DCA escrow PDA
+    /// 5. `[writable]` Owner's quote token account
+    /// 6. `[]` Quote mint
+    /// 7. `[]` Token program
+    /// 8. `[]` System program
+    /// 9. `[]` Config PDA
+    CreateDcaSchedule { market: Pubkey, amount_per_interval: u64, interval: i64, total: u64 },
+    /// Performs a due buy of a DCA schedule for its owner, spending the most
+    /// shares the interval's amount affords. Anyone may execute; the keeper
+    /// pays the rent of the owner's position and holding if they are new.
+    /// The `TradeSettlement` is set as return data.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Keeper
+    /// 1. `[writable]` DCA schedule PDA
+    /// 2. `[writable]` DCA escrow PDA
+    /// 3. `[]` Schedule owner
+    /// 4. `[writable]` Market PDA
+    /// 5. `[writable]` Owner's position PDA
+    /// 6. `[writable]` Market vault
+    /// 7. `[]` Token program
+    /// 8. `[]` System program
+    /// 9. `[writable]` Stats PDA
+    /// 10. `[]` Config PDA
+    /// 11. `[writable]` Owner's holding PDA for the subject
+    ///
+    /// 12. `[writable]` Market's `Candles` PDA, when the market records
+    ///     candles
+    ExecuteDca,
+    /// Returns a DCA schedule's unspent escrow to the owner and closes the
+    /// schedule and escrow, refunding their rent to the owner.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Owner
+    /// 1. `[writable]` DCA schedule PDA
+    /// 2. `[writable]` DCA escrow PDA
+    /// 3. `[writable]` Destination quote token account
+    /// 4. `[]` Token program
+    CancelDcaSchedule,
+    /// Creates the insurance vault for a quote mint, a token account at the
+    /// `[INSURANCE_SEED, quote_mint]` PDA owned by the config PDA. Anyone may
+    /// create it.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Payer of the vault rent
+    /// 1. `[writable]` Insurance vault PDA
+    /// 2. `[]` Quote mint
+    /// 3. `[]` Token program
+    /// 4. `[]` System program
+    /// 5. `[]` Config PDA
+    CreateInsuranceVault,
+    /// Sets the share of claimed protocol fees routed to insurance vaults.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Config admin
+    /// 1. `[writable]` Config PDA
+    SetInsuranceFeeBps { insurance_fee_bps: u16 },
+    /// Pays `amount` out of the insurance vault of a market's quote mint to
+    /// compensate its holders, e.g. after a bug or exploit, and logs an
+    /// `InsurancePayoutEvent`.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Config admin
+    /// 1. `[]` Config PDA
+    /// 2. `[]` Affected market PDA
+    /// 3. `[writable]` Insurance vault PDA of the market's quote mint
+    /// 4. `[writable]` Destination quote token account
+    /// 5. `[]` Token program
+    CompensateFromInsurance { amount: u64 },
+    /// Sets the signing subject's sell tax: `sell_tax_bps` of the curve price,
+    /// up to `MAX_SELL_TAX_BPS`, on sells right after a purchase, decaying to
+    /// zero over `sell_tax_period` seconds, up to `MAX_SELL_TAX_PERIOD`. A zero
+    /// rate or period turns the tax off. Only lowers the rate or shortens the
+    /// period; raising either takes a `ProposeParameters` holders can veto.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Subject
+    /// 1. `[writable]` Market PDA
+    SetSellTax { sell_tax_bps: u16, sell_tax_period: i64 },
+    /// Stakes `lamports` of the treasury, a system-owned PDA at
+    /// `[TREASURY_SEED]` holding the protocol's SOL, with a validator. The
+    /// stake account is created at the `[TREASURY_STAKE_SEED, stake_index]`
+    /// PDA, with the treasury as staker and withdrawer, and the treasury keeps
+    /// its rent-exempt minimum.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Config admin
+    /// 1. `[]` Config PDA
+    /// 2. `[writable]` Treasury PDA
+    /// 3. `[writable]` Treasury stake PDA
+    /// 4. `[]` Validator vote account
+    /// 5. `[]` Clock sysvar
+    /// 6. `[]` Stake history sysvar
+    /// 7. `[]` Stake config
+    /// 8. `[]` Rent sysvar
+    /// 9. `[]` Stake program
+    /// 10. `[]` System program
+    DelegateTreasuryStake { stake_index: u64, lamports: u64 },
+    /// Deactivates a treasury stake account so it can be withdrawn once the
+    /// cooldown ends.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Config admin
+    /// 1. `[]` Config PDA
+    /// 2. `[]` Treasury PDA
+    /// 3. `[writable]` Treasury stake PDA
+    /// 4. `[]` Clock sysvar
+    /// 5. `[]` Stake program
+    DeactivateTreasuryStake { stake_index: u64 },
+    /// Withdraws `lamports` of a treasury stake account, rewards included,
+    /// back to the treasury.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Config admin
+    /// 1. `[]` Config PDA
+    /// 2. `[writable]` Treasury PDA
+    /// 3. `[writable]` Treasury stake PDA
+    /// 4. `[]` Clock sysvar
+    /// 5. `[]` Stake history sysvar
+    /// 6. `[]` Stake program
+    WithdrawTreasuryStake { stake_index: u64, lamports: u64 },
+    /// Transfers `lamports` of unstaked treasury SOL, e.g. for a buyback or
+    /// distribution, keeping the treasury's rent-exempt minimum.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Config admin
+    /// 1. `[]` Config PDA
+    /// 2. `[writable]` Treasury PDA
+    /// 3. `[writable]` Recipient
+    /// 4. `[]` System program
+    WithdrawTreasury { lamports: u64 },
+    /// Creates airdrop `airdrop_id` of the signing subject's market with the
+    /// Merkle `root` of its claims, funding it with `total` shares from the
+    /// subject's position or `total` quote tokens, per `kind`.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Payer of the airdrop rent
+    /// 1. `[signer]` Subject
+    /// 2. `[writable]` Market PDA
+    /// 3. `[writable]` Airdrop PDA
+    /// 4. `[]` System program
+    /// 5. `[]` Config PDA
+    ///
+    /// Then, for `AirdropKind::Shares`:
+    /// 6. `[writable]` Subject's position PDA
+    /// 7. `[writable]` Subject's holding PDA of themselves
+    ///
+    /// Or, for `AirdropKind::QuoteTokens`:
+    /// 6. `[writable]` Airdrop escrow PDA
+    /// 7. `[writable]` Subject's quote token account
+    /// 8. `[]` Quote mint
+    /// 9. `[]` Token program
+    SetAirdropRoot { airdrop_id: u64, root: [u8; 32], kind: AirdropKind, total: u64 },
+    /// Pays out leaf `index` of an airdrop, `amount` shares or quote tokens to
+    /// `claimant`, once `proof` links `airdrop_leaf(index, claimant, amount)`
+    /// to its root. Anyone may submit the claim; the payer covers the rent of
+    /// the claimant's position and holding if they are new.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Payer
+    /// 1. `[]` Claimant
+    /// 2. `[writable]` Market PDA
+    /// 3. `[writable]` Airdrop PDA
+    /// 4. `[]` System program
+    /// 5. `[]` Config PDA
+    ///
+    /// Then, for `AirdropKind::Shares`:
+    /// 6. `[writable]` Claimant's position PDA
+    /// 7. `[writable]` Claimant's holding PDA for the subject
+    ///
+    /// Or, for `AirdropKind::QuoteTokens`:
+    /// 6. `[writable]` Airdrop escrow PDA
+    /// 7. `[writable]` Claimant's quote token account
+    /// 8. `[]` Token program
+    ClaimAirdrop { index: u32, amount: u64, proof: Vec<[u8; 32]> },
+    /// Puts the signing subject's untraded market into a presale until
+    /// `opens_at`: wallets in the Merkle `root` may buy up to their cap of
+    /// `allocation` shares at `price` quote tokens each, and nobody trades on
+    /// the curve. `price` must cover the curve's highest price of a fully
+    /// sold allocation, so presale shares never sell back for more than they
+    /// paid.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Payer of the presale rent
+    /// 1. `[signer]` Subject
+    /// 2. `[writable]` Market PDA
+    /// 3. `[writable]` Presale PDA
+    /// 4. `[]` System program
+    /// 5. `[]` Config PDA
+    StartPresale { root: [u8; 32], price: u64, allocation: u64, opens_at: i64 },
+    /// Buys `amount` presale shares at the presale price, no fees, once
+    /// `proof` links `presale_leaf(buyer, cap)` to the presale's root. The
+    /// buyer's presale purchases may total at most `cap`. The
+    /// `TradeSettlement` is set as return data.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Payer of new position and holding rent
+    /// 1. `[signer]` Buyer, authority of the quote token account
+    /// 2. `[writable]` Market PDA
+    /// 3. `[writable]` Buyer's position PDA
+    /// 4. `[writable]` Buyer's quote token account
+    /// 5. `[writable]` Market vault
+    /// 6. `[]` Token program
+    /// 7. `[]` System program
+    /// 8. `[writable]` Stats PDA
+    /// 9. `[writable]` Buyer's holding PDA for the subject
+    /// 10. `[writable]` Presale PDA
+    /// 11. `[]` Config PDA
+    BuyPresale { amount: u64, cap: u64, proof: Vec<[u8; 32]> },
+    /// Nominates `new_admin`, or withdraws the nomination when `None`. The
+    /// admin changes once the nominee signs `AcceptAdmin`, so control cannot
+    /// pass to a key or governance PDA that is unable to sign.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Config admin
+    /// 1. `[writable]` Config PDA
+    TransferAdmin { new_admin: Option<Pubkey> },
+    /// Makes the signing nominee of `TransferAdmin` the admin. For an
+    /// SPL-Governance PDA, this instruction is executed by a passed proposal.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Nominated admin
+    /// 1. `[writable]` Config PDA
+    AcceptAdmin,
+    /// Sets the config's enabled `FEATURE_*` bits. Instructions of a disabled
+    /// subsystem fail, except those returning funds to their owners, such as
+    /// `CancelDcaSchedule` and the treasury stake withdrawals.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Config admin
+    /// 1. `[writable]` Config PDA
+    SetFeatures { features: u64 },
+    /// Creates the liquidity-mining rewards mint at the `[REWARDS_MINT_SEED]`
+    /// PDA, with the config PDA as its mint authority.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Payer of the mint's rent
+    /// 1. `[signer]` Config admin
+    /// 2. `[]` Config PDA
+    /// 3. `[writable]` Rewards mint PDA
+    /// 4. `[]` Token program
+    /// 5. `[]` System program
+    CreateRewardsMint { decimals: u8 },
+    /// Sets the rewards a market emits to its holders per second, after
+    /// accruing what its previous rate emitted.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Config admin
+    /// 1. `[]` Config PDA
+    /// 2. `[writable]` Market PDA
+    SetEmissionRate { emission_rate: u64 },
+    /// Mints the owner's accrued liquidity-mining rewards for one position.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Position owner
+    /// 1. `[]` Market PDA
+    /// 2. `[writable]` Position PDA
+    /// 3. `[writable]` Rewards mint PDA
+    /// 4. `[writable]` Owner's rewards token account
+    /// 5. `[]` Token program
+    /// 6. `[]` Config PDA
+    ClaimRewards,
+    /// Sets which programs may trade in the signing subject's market by CPI.
+    /// `routers`, at most `MAX_ROUTERS` of them, replace the market's list
+    /// and are only consulted under `RouterPolicy::Allowlist`.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Subject
+    /// 1. `[writable]` Market PDA
+    SetRouterPolicy { policy: RouterPolicy, routers: Vec<Pubkey> },
+    /// Replaces the config's protocol fee split. `recipients`, at most
+    /// `MAX_FEE_RECIPIENTS` of them, must have nonzero weights summing to
+    /// 10,000 basis points; an empty list sends claims to the destination the
+    /// admin passes again.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Config admin
+    /// 1. `[writable]` Config PDA
+    SetFeeSplit { recipients: Vec<FeeRecipient> },
+    /// Nominates `successor` to take over the signing authority's market, or
+    /// withdraws the nomination when `None`, and logs a `SuccessionEvent`.
+    /// The successor may accept once `delay` seconds, at most
+    /// `MAX_SUCCESSION_DELAY`, have passed, giving holders notice. A subject
+    /// can nominate a key kept elsewhere ahead of time, so losing their own
+    /// key does not lose the market.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Market authority
+    /// 1. `[writable]` Market PDA
+    NominateSuccessor { successor: Option<Pubkey>, delay: i64 },
+    /// Makes the signing nominee the market's authority once its nomination's
+    /// delay has passed, and logs a `SuccessionEvent`.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Nominated successor
+    /// 1. `[writable]` Market PDA
+    AcceptSubjectRole,
+    /// Closes a `TradeReceipt` once `RECEIPT_RETENTION_SLOTS` have passed
+    /// since its trade, returning its rent to the payer. Anyone may close it.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Receipt PDA
+    /// 1. `[writable]` Payer of the receipt's rent
+    CloseTradeReceipt,
+    /// Sets the market's share supply as return data, a Borsh `u64`. Like
+    /// the other views it writes nothing, so other programs can call it by
+    /// CPI to read market state without parsing the account themselves.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Market PDA
+    ViewSupply,
+    /// Sets `owner`'s share balance in the market as return data, a Borsh
+    /// `u64`. A position PDA that does not exist yet counts as no shares.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Market PDA
+    /// 1. `[]` Position PDA of `owner` in the market
+    ViewHolderBalance { owner: Pubkey },
+    /// Sets the market's `MarketFees` as return data.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Market PDA
+    ViewFees,
+    /// Replaces the programs approved to lock positions as collateral, at
+    /// most `MAX_COLLATERAL_PROGRAMS`. Positions already locked stay locked
+    /// for their program.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Config admin
+    /// 1. `[writable]` Config PDA
+    SetCollateralPrograms { programs: Vec<Pubkey> },
+    /// Locks the signing owner's position for an approved collateral
+    /// program, e.g. a lending protocol opening a loan against it. The
+    /// program signs by CPI with its collateral authority, the PDA
+    /// `[COLLATERAL_SEED, bump]` under its own id. The shares stay in the
+    /// position.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Position owner
+    /// 1. `[writable]` Position PDA
+    /// 2. `[]` Config PDA
+    /// 3. `[]` Collateral program
+    /// 4. `[signer]` Collateral authority PDA
+    LockPositionAsCollateral { bump: u8 },
+    /// Unlocks a position. Only the collateral program it is locked for can
+    /// unlock it, signing with its collateral authority by CPI, e.g. once its
+    /// loan is repaid.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Position PDA
+    /// 1. `[signer]` Collateral authority PDA
+    UnlockPosition { bump: u8 },
+    /// Sets the quote tokens a sender pays per message in the signing
+    /// subject's market; zero makes messages free.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Subject
+    /// 1. `[writable]` Market PDA
+    SetMessageFee { message_fee: u64 },
+    /// Pays the market's message fee for `count` messages to its subject,
+    /// accruing it as protocol and subject fees in the vault, and counts the
+    /// messages in the sender's `MessageAccess` PDA, created on first use.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Sender, paying the access PDA's rent
+    /// 1. `[writable]` Market PDA
+    /// 2. `[writable]` Access PDA of the sender in the market
+    /// 3. `[writable]` Sender's quote token account
+    /// 4. `[writable]` Vault of the market
+    /// 5. `[]` Token program
+    /// 6. `[]` System program
+    /// 7. `[]` Config PDA
+    PayMessageFee { count: u32 },
+    /// Streams the signing subject's accrued fees to the `beneficiary`
+    /// quote token account at `rate_per_second`, in place of lump-sum
+    /// `ClaimSubjectFees`, or stops streaming with no beneficiary. Fees
+    /// already streamed to the current beneficiary are paid to them first, as
+    /// by `ClaimStreamedFees`. Group markets cannot stream, as their fees are
+    /// split across the members.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Subject
+    /// 1. `[writable]` Market PDA
+    /// 2. `[writable]` Market vault, while a stream is set
+    /// 3. `[writable]` Current beneficiary's quote token account, while a
+    ///    stream is set
+    /// 4. `[]` Token program, while a stream is set
+    SetFeeStream { beneficiary: Option<Pubkey>, rate_per_second: u64 },
+    /// Pays the fees streamed since the last claim to the market's
+    /// beneficiary, capped at the subject fees accrued. Anyone may claim.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Market PDA
+    /// 1. `[writable]` Market vault
+    /// 2. `[writable]` Beneficiary quote token account
+    /// 3. `[]` Token program
+    ClaimStreamedFees,
+    /// Opens a market shared by a group, like `CreateMarket` with the signing
+    /// subject acting for the group, whose subject fees `ClaimSubjectFees`
+    /// splits across `members`. Members, at most `MAX_GROUP_MEMBERS` of
+    /// them, must have nonzero weights summing to 10,000 basis points.
+    ///
+    /// Accounts expected: as for `CreateMarket`.
+    CreateGroupMarket { members: Vec<FeeRecipient> },
+    /// Buys `amount` shares only if the market's per-share curve price for
+    /// the order lies between `min` and `max` inclusive, in millionths of a
+    /// quote base unit as `PriceImpact::pre_trade_price` quotes it. Unlike a
+    /// slippage limit, the band is absolute, for bots buying at set prices.
+    ///
+    /// Accounts expected: as for `BuyShares`, without an interface fee
+    /// recipient or receipt PDA.
+    BuyIfPriceBetween { min: u64, max: u64, amount: u64 },
+    /// Logs a `MarketReportEvent` of the market's supply, 24-hour volume,
+    /// accrued fees, holders and price. Anyone may collect one, e.g. a keeper
+    /// reporting every market periodically to monitoring.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Market PDA
+    CollectMarketReport,
+    /// Proves the signing owner holds at least `min_balance` shares of the
+    /// market by logging a `HoldingProofEvent` bound to `nonce`, e.g. a
+    /// challenge a Discord bot issued, so the proof cannot be replayed for
+    /// another challenge. Fails if the position holds less.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Position owner
+    /// 1. `[]` Market PDA
+    /// 2. `[]` Position PDA of the owner in the market
+    ProveHolding { min_balance: u64, nonce: [u8; 32] },
+    /// Opts the market into recording hourly price candles, creating its
+    /// `Candles` PDA paid by the payer. Signed by the market authority, as
+    /// every trade into the market passes the PDA from then on.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Payer of the candles account's rent
+    /// 1. `[signer]` Market authority
+    /// 2. `[writable]` Market PDA
+    /// 3. `[writable]` Candles PDA of the market
+    /// 4. `[]` System program
+    CreateCandles,
+    /// Starts winding the program down, irreversibly: buys, presale buys and
+    /// new markets stop, and holders sell at the curve price until
+    /// `deadline`, from which they redeem their shares with `RedeemShares`
+    /// instead; see `WindDownStage`. The deadline is at least 30 days away.
+    /// Signed by the admin, e.g. the governance of an SPL-Governance realm.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Admin
+    /// 1. `[writable]` Config PDA
+    StartWindDown { deadline: i64 },
+    /// Redeems the holder's whole position once the wind-down deadline has
+    /// passed, paying its pro-rata part of the vault net of unclaimed fees
+    /// (see `redemption_amount`), set as return data. The position stays
+    /// open for claiming rewards.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Holder
+    /// 1. `[writable]` Market PDA
+    /// 2. `[writable]` Holder's position PDA
+    /// 3. `[writable]` Holder's quote token account
+    /// 4. `[writable]` Market vault
+    /// 5. `[]` Token program
+    /// 6. `[]` Config PDA
+    /// 7. `[writable]` Holder's holding PDA for the subject
+    RedeemShares,
+    /// Sets a `MarketSnapshot` of the market and the passed positions as
+    /// return data. Snapshots are capped at `MAX_RETURN_DATA` bytes, so large
+    /// markets are exported a batch of positions at a time.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Market PDA
+    /// 1. `[]` Position PDAs of the market, any number
+    #[cfg(feature = "devnet")]
+    ExportMarketSnapshot,
+    /// Recreates a snapshotted market, its empty vault and its positions on
+    /// a cluster where they do not exist yet. Fund the vault separately.
+    /// Holding PDAs are not recreated, so imported positions only show up in
+    /// the social graph once they trade.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Payer of the accounts' rent
+    /// 1. `[signer]` Config admin
+    /// 2. `[]` Config PDA
+    /// 3. `[writable]` Market PDA
+    /// 4. `[writable]` Vault PDA
+    /// 5. `[]` Quote mint
+    /// 6. `[]` Token program
+    /// 7. `[]` System program
+    /// 8. `[writable]` Position PDAs, one per snapshot position, in order
+    #[cfg(feature = "devnet")]
+    ImportMarketSnapshot { snapshot: Box<MarketSnapshot> },
+    /// Creates the faucet's quote mint at the `[FAUCET_MINT_SEED]` PDA, with
+    /// the config PDA as its mint authority, so every devnet deployment gets
+    /// a quote mint at a known address.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer, writable]` Payer of the mint's rent
+    /// 1. `[signer]` Config admin
+    /// 2. `[]` Config PDA
+    /// 3. `[writable]` Faucet mint PDA
+    /// 4. `[]` Token program
+    /// 5. `[]` System program
+    #[cfg(feature = "devnet")]
+    CreateFaucetMint { decimals: u8 },
+    /// Mints `amount` faucet quote tokens to any token account of the faucet
+    /// mint, e.g. a test wallet's.
+    ///
+    /// Accounts expected:
+    /// 0. `[]` Config PDA
+    /// 1. `[writable]` Faucet mint PDA
+    /// 2. `[writable]` Destination token account
+    /// 3. `[]` Token program
+    #[cfg(feature = "devnet")]
+    FaucetMint { amount: u64 },
+    /// Fast-forwards the market and the passed positions by `seconds` and
+    /// `epochs`, moving their stored timestamps and epochs back so the next
+    /// instructions reading the clock see that much time passed, e.g. to
+    /// test sell tax decay or epoch rollovers without waiting. The volume
+    /// window moves in whole hours.
+    ///
+    /// Accounts expected:
+    /// 0. `[signer]` Config admin
+    /// 1. `[]` Config PDA
+    /// 2. `[writable]` Market PDA
+    /// 3. `[writable]` Position PDAs of the market, any number
+    #[cfg(feature = "devnet")]
+    FastForwardMarket { seconds: i64, epochs: u64 },
+}
+
+impl FriendtechInstruction {
+    /// Decodes instruction data, accepting only the exact canonical Borsh
+    /// encoding of one instruction: truncated payloads, trailing bytes and
+    /// alternative encodings of the same values are all rejected.
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let mut rest = input;
+        let instruction = Self::deserialize(&mut rest).map_err(|_| ProgramError::InvalidInstructionData)?;
+        if !rest.is_empty() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        // Re-encoding pins the length of every variable-size field as well.
+        if instruction.try_to_vec().map_err(|_| ProgramError::InvalidInstructionData)? != input {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        Ok(instruction)
+    }
+}
+
+/// Message the attestor signs to bind `subject` to `handle_hash` for this program.
+pub fn attestation_message(program_id: &Pubkey, subject: &Pubkey, handle_hash: &[u8; 32]) -> Vec<u8> {
+    [program_id.as_ref(), subject.as_ref(), handle_hash.as_ref()].concat()
 }
 
 /// Calculate base price derived from the number of current holders.
-fn base_price_from_holders(current_holders: u32) -> f64 {
+pub fn base_price_from_holders(current_holders: u32) -> f64 {
     if current_holders <= 10 {
         0.1 * current_holders as f64
     } else {
@@ -49,98 +2008,466 @@ fn base_price_from_holders(current_holders: u32) -> f64 {
 
 /// Dual-phase pricing algorithm considering trading volume, 
 /// number of current holders, and the time elapsed since the last trade.
-fn dual_phase_pricing(current_holders: u32, current_volume: f64, average_volume: f64, time_since_last_trade: f64) -> f64 {
+pub fn dual_phase_pricing(current_holders: u32, current_volume: f64, average_volume: f64, time_since_last_trade: f64) -> f64 {
+    base_price_from_holders(current_holders) * activity_multiplier(current_volume / average_volume, time_since_last_trade)
+}
+
+/// Factor `dual_phase_pricing` applies to the base price for the trading
+/// activity.
+fn activity_multiplier(volume_ratio: f64, time_since_last_trade: f64) -> f64 {
     const VOLUME_ADJUSTMENT_FACTOR: f64 = 0.01;
     const INACTIVITY_ADJUSTMENT_FACTOR: f64 = 0.005;
     const INACTIVITY_THRESHOLD: f64 = 24.0;
 
-    let base_price = base_price_from_holders(current_holders);
-    let volume_ratio = current_volume / average_volume;
-
     if time_since_last_trade > INACTIVITY_THRESHOLD {
-        base_price * (1.0 - INACTIVITY_ADJUSTMENT_FACTOR)
+        1.0 - INACTIVITY_ADJUSTMENT_FACTOR
     } else {
-        base_price * (1.0 + VOLUME_ADJUSTMENT_FACTOR * volume_ratio)
+        1.0 + VOLUME_ADJUSTMENT_FACTOR * volume_ratio
     }
 }
 
-/// Main entry point for processing instructions related to the FriendTech program.
-entrypoint!(process_instruction);
-fn process_instruction(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    instruction_data: &[u8],
-) -> Result<(), ProgramError> {
-    let accounts_iter = &mut accounts.iter();
-    let account = next_account_info(accounts_iter)?;
-    let token_account = next_account_info(accounts_iter)?;
+// Main entry point for processing instructions related to the FriendTech program.
+// Builds that link the crate as a library, such as the WASM quoter, leave it out.
+#[cfg(not(feature = "no-entrypoint"))]
+solana_program::entrypoint!(process_instruction);
+
+/// Prices a buy of `amount` shares at the market's current state; the buyer
+/// pays the curve cost of the shares from the current supply up plus fees.
+pub fn quote_buy(config: &Config, market: &Market, amount: u64) -> Result<TradeSettlement, ProgramError> {
+    let new_supply = market.supply.checked_add(amount).ok_or(ProgramError::ArithmeticOverflow)?;
+    let gross_price = curve_cost(market, market.supply, new_supply)?;
+    let (protocol_fee, subject_fee) = trade_fees(config, market, gross_price);
+    let net_amount = gross_price
+        .checked_add(protocol_fee)
+        .and_then(|total| total.checked_add(subject_fee))
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    Ok(TradeSettlement { gross_price, protocol_fee, subject_fee, interface_fee: 0, net_amount, new_supply })
+}
+
+/// Prices a sale of `amount` shares at the market's current state; the
+/// seller receives the curve cost of the shares from the current supply down
+/// minus fees. A sale of the shares a buy just added covers the same part of
+/// the curve as the buy, so at the same volume premium the round trip costs
+/// exactly its fees.
+pub fn quote_sell(config: &Config, market: &Market, amount: u64) -> Result<TradeSettlement, ProgramError> {
+    let new_supply = market.supply.checked_sub(amount).ok_or(FriendtechError::InsufficientFunds)?;
+    let gross_price = curve_cost(market, new_supply, market.supply)?;
+    let (protocol_fee, subject_fee) = trade_fees(config, market, gross_price);
+    let net_amount = gross_price - protocol_fee - subject_fee;
+    Ok(TradeSettlement { gross_price, protocol_fee, subject_fee, interface_fee: 0, net_amount, new_supply })
+}
+
+/// Per-share prices the market quotes a buy, or a sale when `is_buy` is
+/// false, of `amount` shares right before and after it.
+pub fn price_impact(market: &Market, amount: u64, is_buy: bool) -> Result<PriceImpact, ProgramError> {
+    let new_supply = if is_buy {
+        market.supply.checked_add(amount).ok_or(ProgramError::ArithmeticOverflow)?
+    } else {
+        market.supply.checked_sub(amount).ok_or(FriendtechError::InsufficientFunds)?
+    };
+    let (lo, hi) = (market.supply.min(new_supply), market.supply.max(new_supply));
+    let multiplier = order_multiplier(market, lo, hi)?;
+    let share_price = |supply: u64| {
+        let base_price = base_price_tenths(supply + 1) - base_price_tenths(supply);
+        u64::try_from(base_price * multiplier / 10).map_err(|_| ProgramError::ArithmeticOverflow)
+    };
+    let (pre_trade_price, post_trade_price) = (share_price(market.supply)?, share_price(new_supply)?);
+    let price_impact_bps = match pre_trade_price {
+        0 if post_trade_price > 0 => i64::MAX,
+        0 => 0,
+        pre => {
+            let change = post_trade_price as i128 - pre as i128;
+            (change * BPS_DENOMINATOR as i128 / pre as i128) as i64
+        }
+    };
+    Ok(PriceImpact { pre_trade_price, post_trade_price, price_impact_bps })
+}
+
+/// Curve cost in base units of the shares between supplies `lo` and `hi`,
+/// each priced by `dual_phase_pricing` at the supply it is bought at or sold
+/// back to, under the volume premium of an order for all of them. Buying
+/// from `lo` to `hi` and selling back to `lo` sum the same prices.
+fn curve_cost(market: &Market, lo: u64, hi: u64) -> Result<u64, ProgramError> {
+    let multiplier = order_multiplier(market, lo, hi)?;
+    let cost = (base_price_tenths(hi) - base_price_tenths(lo))
+        .checked_mul(multiplier)
+        .ok_or(ProgramError::ArithmeticOverflow)?
+        / (10 * PRICE_PRECISION);
+    u64::try_from(cost).map_err(|_| ProgramError::ArithmeticOverflow)
+}
+
+/// Activity multiplier, fixed to `PRICE_PRECISION`, of an order for the
+/// shares between supplies `lo` and `hi`. The supply is the curve's holder
+/// count, which is capped at `u32::MAX`.
+fn order_multiplier(market: &Market, lo: u64, hi: u64) -> Result<u128, ProgramError> {
+    u32::try_from(hi).map_err(|_| ProgramError::ArithmeticOverflow)?;
+    let order_value = (base_price_tenths(hi) - base_price_tenths(lo)) as f64 / 10.0;
+    let multiplier = activity_multiplier(volume_ratio(market, order_value), DEFAULT_TIME_SINCE_LAST_TRADE);
+    Ok((multiplier * PRICE_PRECISION as f64) as u128)
+}
 
-    if account.owner != program_id {
-        return Err(FriendtechError::IncorrectOwner.into());
+/// Sum in tenths of `base_price_from_holders` over the supplies below
+/// `supply`: a tenth per holder up to 10, then `supply - 9` per share.
+fn base_price_tenths(supply: u64) -> u128 {
+    let n = supply as u128;
+    if n <= 11 {
+        n * n.saturating_sub(1) / 2
+    } else {
+        55 + 10 * ((n - 10) * (n - 9) / 2 - 1)
     }
+}
 
-    let instruction = FriendtechInstruction::try_from_slice(instruction_data)?;
+/// Ratio of the newest hour's volume to the hourly average over the window,
+/// as fed to `dual_phase_pricing`, for an order worth `order_value` before
+/// volume. Markets without volume in the window price at the default ratio.
+/// Trades roll the window first, so on-chain prices never see stale hours,
+/// and record their own volume after pricing, so it never lifts their price.
+///
+/// The ratio is capped at `MAX_VOLUME_RATIO` and scaled down for orders worth
+/// more than an average hour, which keeps a trade's volume premium under 1% of
+/// the newest hour's volume. At fees of 1% or more, wash trading volume into
+/// the hour costs more than it adds to a later sale.
+fn volume_ratio(market: &Market, order_value: f64) -> f64 {
+    let total = market.volume_buckets.iter().fold(0u64, |total, volume| total.saturating_add(*volume));
+    if total == 0 {
+        return DEFAULT_CURRENT_VOLUME / DEFAULT_AVERAGE_VOLUME;
+    }
+    let current = market.volume_buckets[volume_bucket(market.volume_hour)] as f64;
+    let average = total as f64 / VOLUME_BUCKETS as f64;
+    (current / average).min(MAX_VOLUME_RATIO) * (average / order_value).min(1.0)
+}
 
-    match instruction {
-        FriendtechInstruction::BuyShares { amount } => {
-            let mut share_account = ShareAccount::unpack(&account.data.borrow())?;
+/// Gross trade volume of `market` over the 24 hours up to `now`.
+pub fn rolling_volume(market: &Market, now: i64) -> u64 {
+    let age = (now.div_euclid(VOLUME_BUCKET_SECS) - market.volume_hour).max(0);
+    (age..VOLUME_BUCKETS as i64)
+        .map(|hours_back| market.volume_buckets[volume_bucket(market.volume_hour - hours_back + age)])
+        .fold(0u64, u64::saturating_add)
+}
 
-            let price_per_share = dual_phase_pricing(
-                share_account.balance as u32,
-                DEFAULT_CURRENT_VOLUME,
-                DEFAULT_AVERAGE_VOLUME,
-                DEFAULT_TIME_SINCE_LAST_TRADE,
-            );
-            let total_price = (price_per_share * amount as f64) as u64;
+fn volume_bucket(hour: i64) -> usize {
+    hour.rem_euclid(VOLUME_BUCKETS as i64) as usize
+}
 
-            let user_spl_token_account = SplTokenAccount::unpack(&token_account.data.borrow())?;
-            if user_spl_token_account.amount < total_price {
-                return Err(FriendtechError::InsufficientFunds.into());
-            }
+/// Advances `market`'s volume window to the hour of `now`, clearing the
+/// buckets of hours that passed since its last trade.
+fn roll_volume_window(market: &mut Market, now: i64) {
+    let hour = now.div_euclid(VOLUME_BUCKET_SECS);
+    if hour <= market.volume_hour {
+        return;
+    }
+    let passed = (hour - market.volume_hour).min(VOLUME_BUCKETS as i64);
+    for stale in hour - passed + 1..=hour {
+        market.volume_buckets[volume_bucket(stale)] = 0;
+    }
+    market.volume_hour = hour;
+}
 
-            let ix = spl_token_instruction::transfer(
-                &spl_token::id(),
-                &token_account.key,
-                &token_account.key,
-                &account.owner,
-                &[],
-                total_price,
-            );
-            invoke(&ix, &[token_account.clone(), account.clone()])?;
+/// Adds `volume` to the current hour of `market`'s window.
+fn record_volume(market: &mut Market, volume: u64, now: i64) {
+    roll_volume_window(market, now);
+    let bucket = &mut market.volume_buckets[volume_bucket(market.volume_hour)];
+    *bucket = bucket.saturating_add(volume);
+}
 
-            share_account.balance += amount;
-            ShareAccount::pack(share_account, &mut account.data.borrow_mut())?;
+/// Records a trade of `amount` shares for `gross_price` at `now` in the
+/// candle of its hour, clearing the candles of hours that passed since the
+/// last recorded trade.
+pub fn record_candle(candles: &mut Candles, amount: u64, gross_price: u64, now: i64) {
+    let hour = now.div_euclid(CANDLE_SECS);
+    if hour > candles.hour {
+        let passed = (hour - candles.hour).min(CANDLE_BUCKETS as i64);
+        for stale in hour - passed + 1..=hour {
+            candles.candles[candle_bucket(stale)] = Candle::default();
         }
-        FriendtechInstruction::SellShares { amount } => {
-            let mut share_account = ShareAccount::unpack(&account.data.borrow())?;
+        candles.hour = hour;
+    }
+    let price = gross_price / amount.max(1);
+    let candle = &mut candles.candles[candle_bucket(candles.hour)];
+    if candle.trades == 0 {
+        *candle = Candle { open: price, high: price, low: price, ..Candle::default() };
+    }
+    candle.high = candle.high.max(price);
+    candle.low = candle.low.min(price);
+    candle.close = price;
+    candle.volume = candle.volume.saturating_add(gross_price);
+    candle.trades = candle.trades.saturating_add(1);
+}
 
-            if share_account.balance < amount {
-                return Err(FriendtechError::InsufficientFunds.into());
-            }
+/// Candles of the `CANDLE_BUCKETS` hours up to `now` with their unix hours,
+/// oldest first, leaving out hours without trades.
+pub fn recent_candles(candles: &Candles, now: i64) -> Vec<(i64, Candle)> {
+    let hour = now.div_euclid(CANDLE_SECS).max(candles.hour);
+    (hour - CANDLE_BUCKETS as i64 + 1..=candles.hour)
+        .map(|hour| (hour, candles.candles[candle_bucket(hour)]))
+        .filter(|(_, candle)| candle.trades > 0)
+        .collect()
+}
+
+fn candle_bucket(hour: i64) -> usize {
+    hour.rem_euclid(CANDLE_BUCKETS as i64) as usize
+}
 
-            let total_price = (base_price_from_holders(share_account.balance as u32) * amount as f64) as u64;
-            let ix = spl_token_instruction::transfer(
-                &spl_token::id(),
-                &token_account.key,
-                &token_account.key,
-                &account.owner,
-                &[],
-                total_price,
-            );
-            invoke(&ix, &[token_account.clone(), account.clone()])?;
+/// Largest number of shares a buy can take for at most `budget` quote tokens.
+fn max_shares_for_budget(config: &Config, market: &Market, budget: u64) -> u64 {
+    // A buy whose cost overflows costs more than any budget.
+    let fits = |amount: u64| quote_buy(config, market, amount).is_ok_and(|quote| quote.net_amount <= budget);
+    let max_amount = u64::MAX - market.supply;
+    if max_amount == 0 {
+        return 0;
+    }
 
-            share_account.balance -= amount;
-            ShareAccount::pack(share_account, &mut account.data.borrow_mut())?;
+    // Grow an upper bound whose cost exceeds the budget, then binary search below it.
+    let mut hi = 1u64;
+    while fits(hi) {
+        if hi == max_amount {
+            return max_amount;
+        }
+        hi = hi.saturating_mul(2).min(max_amount);
+    }
+    let mut lo = 0u64;
+    while hi - lo > 1 {
+        let mid = lo + (hi - lo) / 2;
+        if fits(mid) {
+            lo = mid;
+        } else {
+            hi = mid;
         }
     }
+    lo
+}
+
+/// Fees charged on a trade with the given curve price: `(protocol, subject)`.
+fn trade_fees(config: &Config, market: &Market, gross_price: u64) -> (u64, u64) {
+    let fee = |bps: u16| (gross_price as u128 * bps as u128 / BPS_DENOMINATOR as u128) as u64;
+    let protocol_fee = if config.protocol_fees_enabled && market.protocol_fees_enabled {
+        fee(market.protocol_fee_bps)
+    } else {
+        0
+    };
+    (protocol_fee, fee(market.subject_fee_bps))
+}
+
+/// Part of `protocol_fees` routed to the insurance vault when claimed.
+fn insurance_share(config: &Config, protocol_fees: u64) -> u64 {
+    (protocol_fees as u128 * config.insurance_fee_bps as u128 / BPS_DENOMINATOR as u128) as u64
+}
+
+/// The configured fee split recipients, without unused slots.
+pub fn fee_recipients(config: &Config) -> &[FeeRecipient] {
+    weighted_slots(&config.fee_split)
+}
+
+/// The members of a group market, without unused slots.
+pub fn group_members(market: &Market) -> &[FeeRecipient] {
+    weighted_slots(&market.group)
+}
+
+fn weighted_slots(slots: &[FeeRecipient]) -> &[FeeRecipient] {
+    let len = slots.iter().take_while(|recipient| recipient.weight_bps > 0).count();
+    &slots[..len]
+}
+
+/// Splits `amount` of claimed protocol fees across the config's fee split
+/// recipients by weight. Rounding dust goes to the first recipient.
+pub fn split_protocol_fees(config: &Config, amount: u64) -> Vec<u64> {
+    split_by_weight(fee_recipients(config), amount)
+}
+
+/// Splits `amount` of claimed subject fees across a group market's members
+/// by weight. Rounding dust goes to the first member.
+pub fn split_group_fees(market: &Market, amount: u64) -> Vec<u64> {
+    split_by_weight(group_members(market), amount)
+}
+
+fn split_by_weight(recipients: &[FeeRecipient], amount: u64) -> Vec<u64> {
+    let mut shares: Vec<u64> = recipients
+        .iter()
+        .map(|recipient| (amount as u128 * recipient.weight_bps as u128 / BPS_DENOMINATOR as u128) as u64)
+        .collect();
+    let dust = amount - shares.iter().sum::<u64>();
+    if let Some(first) = shares.first_mut() {
+        *first += dust;
+    }
+    shares
+}
+
+/// Splits message fees of `amount` into protocol and subject fees in the
+/// ratio of the market's trading fee rates, the subject taking any remainder
+/// and all of it while protocol fees are switched off.
+pub fn split_message_fee(config: &Config, market: &Market, amount: u64) -> (u64, u64) {
+    let total_bps = market.protocol_fee_bps as u128 + market.subject_fee_bps as u128;
+    if !(config.protocol_fees_enabled && market.protocol_fees_enabled) || total_bps == 0 {
+        return (0, amount);
+    }
+    let protocol_fee = (amount as u128 * market.protocol_fee_bps as u128 / total_bps) as u64;
+    (protocol_fee, amount - protocol_fee)
+}
+
+/// Subject fees streamed to the market's beneficiary by unix timestamp `now`
+/// and not claimed yet. Time when no fees were left to stream is not made
+/// up later.
+pub fn streamed_subject_fees(market: &Market, now: i64) -> u64 {
+    if market.fee_beneficiary.is_none() {
+        return 0;
+    }
+    let elapsed = now.saturating_sub(market.fee_streamed_at).max(0) as u64;
+    market.fee_stream_rate.saturating_mul(elapsed).min(market.subject_fees)
+}
+
+/// Report of `market`, stored at `address`, as of unix timestamp `now`.
+pub fn market_report(address: &Pubkey, market: &Market, now: i64) -> Result<MarketReportEvent, ProgramError> {
+    // Trades roll the volume window before pricing, so the report prices as
+    // a trade at `now` would.
+    let mut rolled = market.clone();
+    roll_volume_window(&mut rolled, now);
+    Ok(MarketReportEvent {
+        market: *address,
+        timestamp: now,
+        supply: market.supply,
+        volume_24h: rolling_volume(market, now),
+        protocol_fees: market.protocol_fees,
+        subject_fees: market.subject_fees,
+        holders: market.holders,
+        price: price_impact(&rolled, 1, true)?.pre_trade_price,
+    })
+}
+
+/// Brings `market`'s reward index up to `now` at its emission rate. Call
+/// before its supply or emission rate changes.
+fn update_reward_index(market: &mut Market, now: i64) -> Result<(), ProgramError> {
+    let elapsed = now.saturating_sub(market.rewards_updated_at);
+    market.reward_index = emissions::advance_index(market.reward_index, market.emission_rate, elapsed, market.supply)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    market.rewards_updated_at = market.rewards_updated_at.max(now);
+    Ok(())
+}
+
+/// Credits `position` with its rewards up to `market`'s reward index. Call
+/// after `update_reward_index` and before the position's balance changes.
+fn accrue_rewards(market: &Market, position: &mut Position) -> Result<(), ProgramError> {
+    let earned = emissions::earned(position.balance, position.reward_index, market.reward_index)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    position.rewards_owed = position.rewards_owed.checked_add(earned).ok_or(ProgramError::ArithmeticOverflow)?;
+    position.reward_index = market.reward_index;
+    Ok(())
+}
 
+/// Records fees that stay in the vault until claimed.
+fn accrue_fees(market: &mut Market, protocol_fee: u64, subject_fee: u64) -> Result<(), ProgramError> {
+    market.protocol_fees = market.protocol_fees.checked_add(protocol_fee).ok_or(ProgramError::ArithmeticOverflow)?;
+    market.subject_fees = market.subject_fees.checked_add(subject_fee).ok_or(ProgramError::ArithmeticOverflow)?;
     Ok(())
 }
 
+/// Stage of the program's wind-down at unix timestamp `now`.
+pub fn wind_down_stage(config: &Config, now: i64) -> WindDownStage {
+    match config.wind_down_deadline {
+        None => WindDownStage::Active,
+        Some(deadline) if now < deadline => WindDownStage::SellsOnly,
+        Some(_) => WindDownStage::Redeeming,
+    }
+}
+
+/// Quote tokens `shares` of `market` redeem for once the program winds
+/// down: their pro-rata part of the `vault_balance` left after the fees
+/// still owed to the protocol and the subject. Every redemption leaves the
+/// same value per share for the holders after it.
+pub fn redemption_amount(market: &Market, vault_balance: u64, shares: u64) -> u64 {
+    if market.supply == 0 {
+        return 0;
+    }
+    let pool = vault_balance.saturating_sub(market.protocol_fees.saturating_add(market.subject_fees));
+    (pool as u128 * shares.min(market.supply) as u128 / market.supply as u128) as u64
+}
+
+/// Whether a trade by CPI from a transaction instruction of `router` may
+/// reach the market.
+pub fn router_allowed(market: &Market, router: &Pubkey) -> bool {
+    match market.router_policy {
+        RouterPolicy::Open => true,
+        RouterPolicy::DirectOnly => false,
+        RouterPolicy::Allowlist => *router != Pubkey::default() && market.routers.contains(router),
+    }
+}
+
+/// Sell tax rate for the position in the market at unix timestamp `now`,
+/// falling linearly from `Market::sell_tax_bps` at the position's tax basis
+/// to zero `Market::sell_tax_period` seconds later.
+pub fn sell_tax_bps(market: &Market, position: &Position, now: i64) -> u16 {
+    if market.sell_tax_period <= 0 {
+        return 0;
+    }
+    let elapsed = now.saturating_sub(position.tax_basis_at()).max(0);
+    if elapsed >= market.sell_tax_period {
+        return 0;
+    }
+    let remaining = (market.sell_tax_period - elapsed) as u128;
+    (market.sell_tax_bps as u128 * remaining / market.sell_tax_period as u128) as u16
+}
+
+/// Extra fee rate, paid to the subject like the sell tax, for selling
+/// `amount` shares at unix timestamp `now`. It is a quarter of the share of
+/// the window's opening supply sold in the window, this sale included, up to
+/// `MAX_DUMP_FEE_BPS`, so each sale of a coordinated dump costs more than the
+/// last. A window lasts `DUMP_WINDOW` seconds from the first sale after the
+/// previous one closed.
+pub fn dump_fee_bps(market: &Market, amount: u64, now: i64) -> u16 {
+    let (supply, sold) = if now < market.dump_window_start.saturating_add(DUMP_WINDOW) {
+        (market.dump_window_supply.max(market.supply), market.dump_window_sold)
+    } else {
+        (market.supply, 0)
+    };
+    if supply == 0 {
+        return 0;
+    }
+    let drawdown_bps = (sold as u128 + amount as u128) * BPS_DENOMINATOR as u128 / supply as u128;
+    (drawdown_bps / DUMP_FEE_DIVISOR).min(MAX_DUMP_FEE_BPS as u128) as u16
+}
+
+/// Adds a sale of `amount` shares at `now` to the market's dump fee window,
+/// opening a new window if the current one has closed.
+fn record_dump_window_sell(market: &mut Market, amount: u64, now: i64) {
+    if now >= market.dump_window_start.saturating_add(DUMP_WINDOW) {
+        market.dump_window_start = now;
+        market.dump_window_supply = market.supply;
+        market.dump_window_sold = 0;
+    }
+    market.dump_window_sold = market.dump_window_sold.saturating_add(amount);
+}
+
+/// Tax basis of the position after buying `amount` more shares at `now`: the
+/// average of its current basis and `now`, weighted by shares.
+fn weighted_tax_basis(position: &Position, amount: u64, now: i64) -> i64 {
+    let total = position.balance as i128 + amount as i128;
+    if position.balance == 0 || total == 0 {
+        return now;
+    }
+    ((position.tax_basis_at() as i128 * position.balance as i128 + now as i128 * amount as i128) / total) as i64
+}
+
+/// Lowest presale price for `allocation` shares: the curve's per-share
+/// price once all of them are sold, at the highest volume ratio, rounded up
+/// so that no allocation sells for less than the curve, or for free.
+fn presale_price_floor(allocation: u64) -> Result<u64, ProgramError> {
+    let holders = u32::try_from(allocation).map_err(|_| ProgramError::ArithmeticOverflow)?;
+    Ok(dual_phase_pricing(holders, MAX_VOLUME_RATIO, 1.0, DEFAULT_TIME_SINCE_LAST_TRADE).ceil() as u64)
+}
+
 /// Tests to validate the dual-phase pricing algorithm's logic and outcomes.
 #[cfg(test)]
 mod tests {
     use super::*;
+    use solana_program::{
+        account_info::AccountInfo,
+        clock::Clock,
+        entrypoint::ProgramResult,
+        instruction::Instruction,
+        program_utils::limited_deserialize,
+        rent::Rent,
+        system_instruction::SystemInstruction,
+        system_program,
+    };
 
     #[test]
     fn test_dual_phase_pricing() {
@@ -161,4 +2488,818 @@ mod tests {
         let expected_exact = base_price_exact * (1.0 + 0.01 * volume_ratio);
         assert_eq!(dual_phase_pricing(10, 10.0, 7.0, 1.0), expected_exact);
     }
+
+    pub(crate) fn sample_market(subject: Pubkey) -> Market {
+        let (quote_mint, vault) = (Pubkey::new_unique(), Pubkey::new_unique());
+        Market {
+            supply: 12,
+            subject_fee_bps: 300,
+            ..Market::new(subject, quote_mint, vault, &sample_config(subject), 255)
+        }
+    }
+
+    pub(crate) fn sample_config(admin: Pubkey) -> Config {
+        Config {
+            protocol_fee_bps: 500,
+            subject_fee_bps: 500,
+            proposal_delay: 86_400,
+            veto_threshold_bps: 3_000,
+            max_interface_fee_bps: 100,
+            insurance_fee_bps: 2_000,
+            ..Config::new(admin, 255)
+        }
+    }
+
+    /// Writable accounts over `keys`, `lamports` and `data`, all owned by
+    /// `owner` and signing if `is_signer`.
+    pub(crate) fn test_accounts<'a>(
+        keys: &'a [Pubkey],
+        lamports: &'a mut [u64],
+        data: &'a mut [Vec<u8>],
+        owner: &'a Pubkey,
+        is_signer: bool,
+    ) -> Vec<AccountInfo<'a>> {
+        keys.iter()
+            .zip(lamports.iter_mut())
+            .zip(data.iter_mut())
+            .map(|((key, lamports), data)| AccountInfo::new(key, is_signer, true, lamports, data, owner, false, 0))
+            .collect()
+    }
+
+    /// Unix timestamp of the clock handlers read once `install_test_runtime`
+    /// has run.
+    pub(crate) const TEST_NOW: i64 = 1_700_000_000;
+
+    /// Installs syscalls that let handlers run to completion in unit tests:
+    /// a clock at `TEST_NOW`, the default rent, per-thread return data and
+    /// stack height, and CPIs into the token program and the lamport-moving
+    /// system instructions. Created accounts must already have their space, and
+    /// keep their owner, as test accounts only borrow it.
+    pub(crate) fn install_test_runtime() {
+        static INSTALL: std::sync::Once = std::sync::Once::new();
+        INSTALL.call_once(|| {
+            solana_program::program_stubs::set_syscall_stubs(Box::new(TestRuntime));
+        });
+    }
+
+    struct TestRuntime;
+
+    thread_local! {
+        static RETURN_DATA: std::cell::RefCell<Option<(Pubkey, Vec<u8>)>> = const { std::cell::RefCell::new(None) };
+        static STACK_HEIGHT: std::cell::Cell<u64> =
+            const { std::cell::Cell::new(solana_program::instruction::TRANSACTION_LEVEL_STACK_HEIGHT as u64) };
+    }
+
+    /// Runs the current thread's handlers as if invoked at `height`, e.g.
+    /// by CPI above `TRANSACTION_LEVEL_STACK_HEIGHT`.
+    pub(crate) fn set_test_stack_height(height: usize) {
+        STACK_HEIGHT.with(|stack_height| stack_height.set(height as u64));
+    }
+
+    impl solana_program::program_stubs::SyscallStubs for TestRuntime {
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            let clock = Clock { slot: 1_000, epoch: 10, unix_timestamp: TEST_NOW, ..Clock::default() };
+            unsafe { *(var_addr as *mut Clock) = clock };
+            solana_program::entrypoint::SUCCESS
+        }
+
+        fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+            unsafe { *(var_addr as *mut Rent) = Rent::default() };
+            solana_program::entrypoint::SUCCESS
+        }
+
+        fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+            RETURN_DATA.with(|return_data| return_data.borrow().clone())
+        }
+
+        fn sol_get_stack_height(&self) -> u64 {
+            STACK_HEIGHT.with(|stack_height| stack_height.get())
+        }
+
+        fn sol_set_return_data(&self, data: &[u8]) {
+            RETURN_DATA.with(|return_data| *return_data.borrow_mut() = Some((Pubkey::default(), data.to_vec())));
+        }
+
+        fn sol_invoke_signed(
+            &self,
+            instruction: &Instruction,
+            account_infos: &[AccountInfo],
+            _signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            let accounts = instruction
+                .accounts
+                .iter()
+                .map(|meta| {
+                    let mut account = account_infos
+                        .iter()
+                        .find(|account| *account.key == meta.pubkey)
+                        .ok_or(ProgramError::NotEnoughAccountKeys)?
+                        .clone();
+                    account.is_signer |= meta.is_signer;
+                    account.is_writable = meta.is_writable;
+                    Ok(account)
+                })
+                .collect::<Result<Vec<_>, ProgramError>>()?;
+            if instruction.program_id == spl_token::id() {
+                return spl_token::processor::Processor::process(&instruction.program_id, &accounts, &instruction.data);
+            }
+            if instruction.program_id != system_program::id() {
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            let move_lamports = |from: &AccountInfo, to: &AccountInfo, lamports: u64| -> ProgramResult {
+                **from.try_borrow_mut_lamports()? =
+                    from.lamports().checked_sub(lamports).ok_or(ProgramError::InsufficientFunds)?;
+                **to.try_borrow_mut_lamports()? += lamports;
+                Ok(())
+            };
+            match limited_deserialize(&instruction.data, 1_232).map_err(|_| ProgramError::InvalidInstructionData)? {
+                SystemInstruction::CreateAccount { lamports, space, .. } if accounts[1].data_len() == space as usize => {
+                    move_lamports(&accounts[0], &accounts[1], lamports)
+                }
+                SystemInstruction::Transfer { lamports } => move_lamports(&accounts[0], &accounts[1], lamports),
+                SystemInstruction::Allocate { space } if accounts[0].data_len() == space as usize => Ok(()),
+                SystemInstruction::Assign { .. } => Ok(()),
+                _ => Err(ProgramError::InvalidInstructionData),
+            }
+        }
+    }
+
+    #[test]
+    fn test_dca_schedule_pack_round_trip() {
+        let schedule = DcaSchedule {
+            account_type: AccountType::DcaSchedule,
+            owner: Pubkey::new_unique(),
+            market: Pubkey::new_unique(),
+            escrow: Pubkey::new_unique(),
+            amount_per_interval: 1_000,
+            interval: 3_600,
+            remaining: 24_000,
+            next_execution_at: 1_700_000_000,
+            bump: 252,
+        };
+        let mut data = vec![0u8; DcaSchedule::LEN];
+        DcaSchedule::pack(schedule.clone(), &mut data).unwrap();
+        assert_eq!(DcaSchedule::unpack(&data).unwrap(), schedule);
+        assert_eq!(Holding::unpack(&data), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn test_airdrop_claims() {
+        let claimants: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let leaves: Vec<[u8; 32]> =
+            claimants.iter().enumerate().map(|(i, claimant)| airdrop_leaf(i as u32, claimant, 100 * (i as u64 + 1))).collect();
+        let root = merkle::root(&leaves);
+        let proof = merkle::proof(&leaves, 1).unwrap();
+        assert!(merkle::verify(&root, airdrop_leaf(1, &claimants[1], 200), &proof));
+        assert!(!merkle::verify(&root, airdrop_leaf(1, &claimants[1], 201), &proof));
+        assert!(!merkle::verify(&root, airdrop_leaf(1, &claimants[0], 200), &proof));
+
+        let mut airdrop = Airdrop {
+            account_type: AccountType::Airdrop,
+            market: Pubkey::new_unique(),
+            id: 0,
+            root,
+            kind: AirdropKind::Shares,
+            remaining: 600,
+            claimed: [0; AIRDROP_BITMAP_BYTES],
+            bump: 255,
+        };
+        airdrop.set_claimed(MAX_AIRDROP_CLAIMS - 1);
+        assert!(airdrop.is_claimed(MAX_AIRDROP_CLAIMS - 1) && !airdrop.is_claimed(MAX_AIRDROP_CLAIMS - 2));
+        let mut data = vec![0u8; Airdrop::LEN];
+        Airdrop::pack(airdrop.clone(), &mut data).unwrap();
+        assert_eq!(Airdrop::unpack(&data).unwrap(), airdrop);
+    }
+
+    #[test]
+    fn test_presale() {
+        // Up to 10 holders the curve prices at 0.1 base units per holder,
+        // plus at most 2% for volume, so a single share still costs a base
+        // unit rather than nothing.
+        assert_eq!(presale_price_floor(1).unwrap(), 1);
+        assert_eq!(presale_price_floor(5).unwrap(), 1);
+        assert_eq!(presale_price_floor(110).unwrap(), 104);
+        assert_eq!(presale_price_floor(u32::MAX as u64 + 1).unwrap_err(), ProgramError::ArithmeticOverflow);
+
+        let (buyer, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let leaves = [presale_leaf(&buyer, 50), presale_leaf(&other, 20)];
+        let root = merkle::root(&leaves);
+        let proof = merkle::proof(&leaves, 0).unwrap();
+        assert!(merkle::verify(&root, presale_leaf(&buyer, 50), &proof));
+        assert!(!merkle::verify(&root, presale_leaf(&buyer, 500), &proof));
+
+        let presale = Presale {
+            account_type: AccountType::Presale,
+            market: Pubkey::new_unique(),
+            root,
+            price: 150,
+            allocation: 100,
+            sold: 30,
+            bump: 254,
+        };
+        let mut data = vec![0u8; Presale::LEN];
+        Presale::pack(presale.clone(), &mut data).unwrap();
+        assert_eq!(Presale::unpack(&data).unwrap(), presale);
+    }
+
+    #[test]
+    fn test_position_packing() {
+        let mut position = Position { balance: u64::MAX, ..Position::new(Pubkey::new_unique(), 254) };
+        (position.reward_index, position.rewards_owed) = (u128::MAX, u64::MAX);
+        assert_eq!(position.try_to_vec().unwrap().len(), Position::LEN);
+
+        // Every combination of flags and tier reads back as set, and survives
+        // packing.
+        let lender = Pubkey::new_unique();
+        for tier in 0..=Position::MAX_TIER {
+            for bits in 0..8 {
+                let (locked, delegated, vesting) = (bits & 1 != 0, bits & 2 != 0, bits & 4 != 0);
+                position.set_tier(tier);
+                position.set_locked_by(locked.then_some(lender));
+                position.set_delegated(delegated);
+                position.set_vesting(vesting);
+                let mut data = vec![0u8; Position::LEN];
+                Position::pack(position.clone(), &mut data).unwrap();
+                let unpacked = Position::unpack(&data).unwrap();
+                assert_eq!(unpacked, position);
+                assert_eq!(unpacked.tier(), tier);
+                assert_eq!(unpacked.locked_by(), locked.then_some(lender));
+                assert_eq!((unpacked.is_delegated(), unpacked.is_vesting()), (delegated, vesting));
+            }
+        }
+        position.set_tier(u8::MAX);
+        assert_eq!(position.tier(), Position::MAX_TIER);
+        position.set_locked_by(None);
+        assert_eq!(position.locked_by(), None);
+        assert_eq!(position.tier(), Position::MAX_TIER);
+
+        for (timestamp, stored) in [(-1, 0), (0, 0), (1_700_000_000, 1_700_000_000), (i64::MAX, u32::MAX as i64)] {
+            position.set_acquired_at(timestamp);
+            position.set_tax_basis_at(timestamp);
+            position.set_veto_locked_until(timestamp);
+            let mut data = vec![0u8; Position::LEN];
+            Position::pack(position.clone(), &mut data).unwrap();
+            let unpacked = Position::unpack(&data).unwrap();
+            assert_eq!((unpacked.acquired_at(), unpacked.tax_basis_at()), (stored, stored));
+            assert_eq!(unpacked.veto_locked_until(), stored);
+        }
+    }
+
+    #[test]
+    fn test_reward_accrual() {
+        let mut market = sample_market(Pubkey::new_unique());
+        (market.supply, market.emission_rate, market.rewards_updated_at) = (100, 10, 1_000);
+        let mut position = Position { balance: 25, ..Position::new(Pubkey::new_unique(), 255) };
+        update_reward_index(&mut market, 1_020).unwrap();
+        accrue_rewards(&market, &mut position).unwrap();
+        assert_eq!(position.rewards_owed, 50);
+        // Accruing again without the index moving earns nothing more.
+        accrue_rewards(&market, &mut position).unwrap();
+        assert_eq!(position.rewards_owed, 50);
+
+        // A clock behind the last update neither rewinds nor accrues.
+        update_reward_index(&mut market, 1_010).unwrap();
+        assert_eq!(market.rewards_updated_at, 1_020);
+        market.supply = 0;
+        let index = market.reward_index;
+        update_reward_index(&mut market, 2_000).unwrap();
+        assert_eq!(market.reward_index, index);
+    }
+
+    #[test]
+    fn test_router_allowed() {
+        let mut market = sample_market(Pubkey::new_unique());
+        let router = Pubkey::new_unique();
+        assert!(router_allowed(&market, &router));
+        market.router_policy = RouterPolicy::DirectOnly;
+        assert!(!router_allowed(&market, &router));
+        market.router_policy = RouterPolicy::Allowlist;
+        assert!(!router_allowed(&market, &router));
+        market.routers[1] = router;
+        assert!(router_allowed(&market, &router));
+        assert!(!router_allowed(&market, &Pubkey::new_unique()));
+        assert!(!router_allowed(&market, &Pubkey::default()));
+    }
+
+    #[test]
+    fn test_sell_tax() {
+        let mut market = sample_market(Pubkey::new_unique());
+        let mut position = Position::new(Pubkey::new_unique(), 255);
+        position.set_tax_basis_at(weighted_tax_basis(&position, 10, 1_000));
+        assert_eq!(position.tax_basis_at(), 1_000);
+        assert_eq!(sell_tax_bps(&market, &position, 1_000), 0);
+
+        (market.sell_tax_bps, market.sell_tax_period) = (1_000, 800);
+        assert_eq!(sell_tax_bps(&market, &position, 1_000), 1_000);
+        assert_eq!(sell_tax_bps(&market, &position, 1_200), 750);
+        assert_eq!(sell_tax_bps(&market, &position, 1_800), 0);
+
+        // Buying 10 more at 1,400 averages the basis to 1,200.
+        position.balance = 10;
+        position.set_tax_basis_at(weighted_tax_basis(&position, 10, 1_400));
+        assert_eq!(position.tax_basis_at(), 1_200);
+        assert_eq!(sell_tax_bps(&market, &position, 1_600), 500);
+
+        let config = sample_config(Pubkey::new_unique());
+        let quote = quote_sell(&config, &sample_market(Pubkey::new_unique()), 1).unwrap();
+        let taxed = quote.clone().with_sell_tax(500).unwrap();
+        let tax = quote.gross_price / 20;
+        assert_eq!((taxed.subject_fee, taxed.net_amount), (quote.subject_fee + tax, quote.net_amount - tax));
+    }
+
+    #[test]
+    fn test_dump_fee() {
+        let mut market = sample_market(Pubkey::new_unique());
+        market.supply = 100;
+        assert_eq!(dump_fee_bps(&market, 0, 5_000), 0);
+        assert_eq!(dump_fee_bps(&market, 20, 5_000), 500);
+
+        record_dump_window_sell(&mut market, 20, 5_000);
+        market.supply = 80;
+        assert_eq!((market.dump_window_start, market.dump_window_supply), (5_000, 100));
+        // The second sale pays for the first one's drawdown too.
+        assert_eq!(dump_fee_bps(&market, 20, 5_100), 1_000);
+        assert_eq!(dump_fee_bps(&market, 80, 5_100), MAX_DUMP_FEE_BPS);
+        // Once the window closes, only the new sale counts.
+        assert_eq!(dump_fee_bps(&market, 20, 5_000 + DUMP_WINDOW), 625);
+        record_dump_window_sell(&mut market, 20, 5_000 + DUMP_WINDOW);
+        assert_eq!((market.dump_window_supply, market.dump_window_sold), (80, 20));
+    }
+
+    #[test]
+    fn test_trade_fees() {
+        let mut config = sample_config(Pubkey::new_unique());
+        let market = sample_market(Pubkey::new_unique());
+        assert_eq!(trade_fees(&config, &market, 10_000), (500, 300));
+        // Fees round down, so tiny trades can be fee-free.
+        assert_eq!(trade_fees(&config, &market, 19), (0, 0));
+        // The product is taken in u128, so the largest prices do not overflow.
+        assert_eq!(trade_fees(&config, &market, u64::MAX).0, u64::MAX / 20);
+
+        // Either fee switch being off waives the protocol fee only.
+        let mut switched_off = market.clone();
+        switched_off.protocol_fees_enabled = false;
+        assert_eq!(trade_fees(&config, &switched_off, 10_000), (0, 300));
+        config.protocol_fees_enabled = false;
+        assert_eq!(trade_fees(&config, &market, 10_000), (0, 300));
+
+        let mut market = market;
+        accrue_fees(&mut market, 5, 3).unwrap();
+        accrue_fees(&mut market, 5, 3).unwrap();
+        assert_eq!((market.protocol_fees, market.subject_fees), (10, 6));
+        market.protocol_fees = u64::MAX;
+        assert!(accrue_fees(&mut market, 1, 0).is_err());
+    }
+
+    #[test]
+    fn test_insurance_share() {
+        let mut config = sample_config(Pubkey::new_unique());
+        assert_eq!(insurance_share(&config, 1_000), 200);
+        assert_eq!(insurance_share(&config, 4), 0);
+        config.insurance_fee_bps = BPS_DENOMINATOR as u16;
+        assert_eq!(insurance_share(&config, u64::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn test_split_protocol_fees() {
+        let mut config = sample_config(Pubkey::new_unique());
+        assert!(split_protocol_fees(&config, 1_000).is_empty());
+        config.fee_split[0] = FeeRecipient { destination: Pubkey::new_unique(), weight_bps: 5_000 };
+        config.fee_split[1] = FeeRecipient { destination: Pubkey::new_unique(), weight_bps: 3_333 };
+        config.fee_split[2] = FeeRecipient { destination: Pubkey::new_unique(), weight_bps: 1_667 };
+        assert_eq!(fee_recipients(&config).len(), 3);
+        assert_eq!(split_protocol_fees(&config, 10_000), vec![5_000, 3_333, 1_667]);
+        // 49.995 + 16.67 round down, leaving a unit of dust for the first recipient.
+        assert_eq!(split_protocol_fees(&config, 100), vec![51, 33, 16]);
+        assert_eq!(split_protocol_fees(&config, u64::MAX).iter().map(|&share| share as u128).sum::<u128>(), u64::MAX as u128);
+    }
+
+    #[test]
+    fn test_split_message_fee() {
+        let mut config = sample_config(Pubkey::new_unique());
+        let mut market = sample_market(Pubkey::new_unique());
+        market.protocol_fee_bps = 500;
+        market.subject_fee_bps = 1_000;
+        assert_eq!(split_message_fee(&config, &market, 3_000), (1_000, 2_000));
+        assert_eq!(split_message_fee(&config, &market, 1), (0, 1));
+        assert_eq!(split_message_fee(&config, &market, u64::MAX).1, u64::MAX - u64::MAX / 3);
+        market.subject_fee_bps = 0;
+        assert_eq!(split_message_fee(&config, &market, 3_000), (3_000, 0));
+        config.protocol_fees_enabled = false;
+        assert_eq!(split_message_fee(&config, &market, 3_000), (0, 3_000));
+    }
+
+    #[test]
+    fn test_streamed_subject_fees() {
+        let mut market = sample_market(Pubkey::new_unique());
+        market.subject_fees = 1_000;
+        market.fee_stream_rate = 3;
+        market.fee_streamed_at = 100;
+        assert_eq!(streamed_subject_fees(&market, 200), 0);
+        market.fee_beneficiary = Some(Pubkey::new_unique());
+        assert_eq!(streamed_subject_fees(&market, 50), 0);
+        assert_eq!(streamed_subject_fees(&market, 200), 300);
+        assert_eq!(streamed_subject_fees(&market, 1_000), 1_000);
+        market.fee_stream_rate = u64::MAX;
+        assert_eq!(streamed_subject_fees(&market, i64::MAX), 1_000);
+    }
+
+    #[test]
+    fn test_interface_fee() {
+        let config = sample_config(Pubkey::new_unique());
+        let market = sample_market(Pubkey::new_unique());
+        let buy = quote_buy(&config, &market, 10).unwrap();
+        let with_fee = buy.clone().with_interface_fee(100, true).unwrap();
+        assert_eq!(with_fee.interface_fee, buy.gross_price / 100);
+        assert_eq!(with_fee.net_amount, buy.net_amount + with_fee.interface_fee);
+        let sell = quote_sell(&config, &market, 10).unwrap();
+        let with_fee = sell.clone().with_interface_fee(100, false).unwrap();
+        assert_eq!(with_fee.net_amount, sell.net_amount - with_fee.interface_fee);
+    }
+
+    #[test]
+    fn test_max_shares_for_budget() {
+        let config = sample_config(Pubkey::new_unique());
+        let mut market = sample_market(Pubkey::new_unique());
+        // A market with no supply yet prices its first share at zero.
+        for supply in [12, 0] {
+            market.supply = supply;
+            for budget in [0, 3, 100, 1_000_000] {
+                let amount = max_shares_for_budget(&config, &market, budget);
+                if amount > 0 {
+                    assert!(quote_buy(&config, &market, amount).unwrap().net_amount <= budget);
+                }
+                assert!(quote_buy(&config, &market, amount + 1).unwrap().net_amount > budget);
+            }
+        }
+        assert!(max_shares_for_budget(&config, &market, 0) > 0);
+
+        // Amounts whose cost overflows are over any budget, so the largest
+        // budget buys the most shares that can still be priced.
+        let amount = max_shares_for_budget(&config, &market, u64::MAX);
+        assert!(amount > 0);
+        assert!(quote_buy(&config, &market, amount).is_ok());
+        assert!(quote_buy(&config, &market, amount + 1).is_err());
+    }
+
+    #[test]
+    fn test_price_impact() {
+        let mut market = sample_market(Pubkey::new_unique());
+        let sell = price_impact(&market, 2, false).unwrap();
+        assert_eq!(sell, PriceImpact { pre_trade_price: 3_042_855, post_trade_price: 1_014_285, price_impact_bps: -6_666 });
+        assert_eq!(price_impact(&market, 0, true).unwrap().price_impact_bps, 0);
+        let buy = price_impact(&market, 10, true).unwrap();
+        assert!(buy.post_trade_price > buy.pre_trade_price && buy.price_impact_bps > 0);
+        assert_eq!(price_impact(&market, 13, false).unwrap_err(), FriendtechError::InsufficientFunds.into());
+
+        market.supply = 0;
+        assert_eq!(price_impact(&market, 1, true).unwrap().price_impact_bps, i64::MAX);
+    }
+
+    #[test]
+    fn test_buy_sell_parity() {
+        let mut config = sample_config(Pubkey::new_unique());
+        let mut market = sample_market(Pubkey::new_unique());
+        let now = 1_000 * VOLUME_BUCKET_SECS;
+        record_volume(&mut market, 500, now);
+        for (supply, amount) in [(1, 1), (12, 5), (12, 1_000), (1, 9_999), (10_000, 250), (1_000_000, 1_000_000)] {
+            market.supply = supply;
+            assert_eq!(price_impact(&market, 0, true).unwrap(), price_impact(&market, 0, false).unwrap());
+
+            // Selling the shares just bought sums the same curve prices, so
+            // at the same volume premium the trader loses exactly the fees.
+            let buy = quote_buy(&config, &market, amount).unwrap();
+            let bought = Market { supply: buy.new_supply, ..market.clone() };
+            let sell = quote_sell(&config, &bought, amount).unwrap();
+            assert_eq!((buy.gross_price, sell.new_supply), (sell.gross_price, supply));
+            let fees = buy.protocol_fee + buy.subject_fee + sell.protocol_fee + sell.subject_fee;
+            assert_eq!(buy.net_amount - sell.net_amount, fees);
+
+            // The buy's own volume lifts the sale's premium by less than the
+            // fees.
+            let mut traded = market.clone();
+            let flow = settle(&config, &mut traded, amount, true, now) + settle(&config, &mut traded, amount, false, now);
+            assert!(flow <= 0, "round trip of {amount} shares at supply {supply} made {flow}");
+        }
+
+        // Without fees, buying and selling the same shares cost the same.
+        config.protocol_fees_enabled = false;
+        market.subject_fee_bps = 0;
+        let buy = quote_buy(&config, &market, 7).unwrap();
+        let sell = quote_sell(&config, &Market { supply: buy.new_supply, ..market.clone() }, 7).unwrap();
+        assert_eq!(buy.net_amount, sell.net_amount);
+    }
+
+    #[test]
+    fn test_volume_window() {
+        let config = sample_config(Pubkey::new_unique());
+        let mut market = sample_market(Pubkey::new_unique());
+        market.supply = 20;
+        let unpriced = quote_buy(&config, &market, 1_000).unwrap();
+
+        let hour = 1_000 * VOLUME_BUCKET_SECS;
+        record_volume(&mut market, 100, hour);
+        record_volume(&mut market, 50, hour + 10);
+        record_volume(&mut market, 30, hour + 5 * VOLUME_BUCKET_SECS);
+        assert_eq!(rolling_volume(&market, hour + 5 * VOLUME_BUCKET_SECS), 180);
+        // Hours drop out of the window as it passes them.
+        assert_eq!(rolling_volume(&market, hour + 24 * VOLUME_BUCKET_SECS), 30);
+        assert_eq!(rolling_volume(&market, hour + 29 * VOLUME_BUCKET_SECS), 0);
+        roll_volume_window(&mut market, hour + 24 * VOLUME_BUCKET_SECS);
+        assert_eq!(market.volume_buckets.iter().sum::<u64>(), 30);
+        // A clock behind the newest hour neither rewinds nor clears it.
+        roll_volume_window(&mut market, hour);
+        assert_eq!(rolling_volume(&market, hour), 30);
+
+        // Buys price in the newest hour's volume against the hourly average.
+        assert_eq!(volume_ratio(&market, 1.0), 0.0);
+        assert!(quote_buy(&config, &market, 1_000).unwrap().gross_price < unpriced.gross_price);
+        record_volume(&mut market, 30, hour + 24 * VOLUME_BUCKET_SECS);
+        // 12 times the hourly average of 2.5, capped.
+        assert_eq!(volume_ratio(&market, 1.0), MAX_VOLUME_RATIO);
+        // An order worth four average hours sees a quarter of the ratio.
+        assert_eq!(volume_ratio(&market, 10.0), MAX_VOLUME_RATIO / 4.0);
+    }
+
+    #[test]
+    fn test_market_report() {
+        let (address, mut market) = (Pubkey::new_unique(), sample_market(Pubkey::new_unique()));
+        (market.supply, market.holders, market.protocol_fees, market.subject_fees) = (20, 3, 40, 60);
+        let hour = 1_000 * VOLUME_BUCKET_SECS;
+        record_volume(&mut market, 100, hour);
+        record_volume(&mut market, 30, hour + 5 * VOLUME_BUCKET_SECS);
+
+        let report = market_report(&address, &market, hour + 5 * VOLUME_BUCKET_SECS).unwrap();
+        assert_eq!((report.market, report.supply, report.holders), (address, 20, 3));
+        assert_eq!((report.volume_24h, report.protocol_fees, report.subject_fees), (130, 40, 60));
+        assert_eq!(report.price, price_impact(&market, 1, true).unwrap().pre_trade_price);
+
+        // A day later the report prices as a trade would, without the stale
+        // hours.
+        let later = hour + 29 * VOLUME_BUCKET_SECS;
+        let report = market_report(&address, &market, later).unwrap();
+        roll_volume_window(&mut market, later);
+        assert_eq!((report.timestamp, report.volume_24h), (later, 0));
+        assert_eq!(report.price, price_impact(&market, 1, true).unwrap().pre_trade_price);
+    }
+
+    #[test]
+    fn test_wind_down() {
+        let mut config = sample_config(Pubkey::new_unique());
+        assert_eq!(wind_down_stage(&config, 1_000), WindDownStage::Active);
+        config.wind_down_deadline = Some(1_000);
+        assert_eq!(wind_down_stage(&config, 999), WindDownStage::SellsOnly);
+        assert_eq!(wind_down_stage(&config, 1_000), WindDownStage::Redeeming);
+
+        let mut market = sample_market(Pubkey::new_unique());
+        (market.supply, market.protocol_fees, market.subject_fees) = (10, 30, 70);
+        assert_eq!(redemption_amount(&market, 1_100, 3), 300);
+        assert_eq!(redemption_amount(&market, 1_100, 20), 1_000);
+        // The fees owed are covered before any holder.
+        assert_eq!(redemption_amount(&market, 90, 10), 0);
+        market.supply = 0;
+        assert_eq!(redemption_amount(&market, 1_100, 3), 0);
+    }
+
+    #[test]
+    fn test_record_candle() {
+        let mut candles = Candles {
+            account_type: AccountType::Candles,
+            market: Pubkey::new_unique(),
+            candles: [Candle::default(); CANDLE_BUCKETS],
+            hour: 0,
+            bump: 255,
+        };
+        let hour = 1_000 * CANDLE_SECS;
+        record_candle(&mut candles, 2, 200, hour);
+        record_candle(&mut candles, 1, 150, hour + 60);
+        record_candle(&mut candles, 4, 280, hour + 120);
+        record_candle(&mut candles, 1, 0, hour + 180);
+        let candle = Candle { open: 100, high: 150, low: 0, close: 0, volume: 630, trades: 4 };
+        assert_eq!(recent_candles(&candles, hour + 180), vec![(1_000, candle)]);
+
+        // Hours without trades are left out, and the oldest hours drop out
+        // of the window.
+        record_candle(&mut candles, 1, 90, hour + 3 * CANDLE_SECS);
+        let later = Candle { open: 90, high: 90, low: 90, close: 90, volume: 90, trades: 1 };
+        assert_eq!(recent_candles(&candles, hour + 3 * CANDLE_SECS), vec![(1_000, candle), (1_003, later)]);
+        let end = hour + CANDLE_BUCKETS as i64 * CANDLE_SECS;
+        assert_eq!(recent_candles(&candles, end), vec![(1_003, later)]);
+        assert_eq!(recent_candles(&candles, end + 3 * CANDLE_SECS), vec![]);
+
+        // A trade after the window passed starts from empty candles.
+        record_candle(&mut candles, 1, 80, end + 3 * CANDLE_SECS);
+        assert_eq!(candles.candles.iter().filter(|candle| candle.trades > 0).count(), 1);
+    }
+
+    /// Applies a quoted trade to `market` at `now` as the processor does and
+    /// returns the trader's quote token flow: negative for buys, positive for
+    /// sales.
+    fn settle(config: &Config, market: &mut Market, amount: u64, is_buy: bool, now: i64) -> i128 {
+        roll_volume_window(market, now);
+        let settlement =
+            if is_buy { quote_buy(config, market, amount) } else { quote_sell(config, market, amount) }.unwrap();
+        market.supply = settlement.new_supply;
+        record_volume(market, settlement.gross_price, now);
+        if is_buy {
+            -(settlement.net_amount as i128)
+        } else {
+            settlement.net_amount as i128
+        }
+    }
+
+    #[test]
+    fn test_wash_trading_unprofitable() {
+        // A market trading about 50 shares an hour, whose whale sells 2,000
+        // of its 5,000 shares.
+        let config = sample_config(Pubkey::new_unique());
+        let mut market = sample_market(Pubkey::new_unique());
+        market.supply = 5_000;
+        let hour = 1_000 * VOLUME_BUCKET_SECS;
+        for h in 0..VOLUME_BUCKETS as i64 {
+            record_volume(&mut market, 250_000, hour + h * VOLUME_BUCKET_SECS);
+        }
+        let now = hour + (VOLUME_BUCKETS as i64 - 1) * VOLUME_BUCKET_SECS;
+        let honest = settle(&config, &mut market.clone(), 2_000, false, now);
+
+        // Round trips pump the sale's hour's volume without changing supply,
+        // and each sells back down the part of the curve it bought, however
+        // large.
+        for (round_trip, rounds) in [(10, 50), (100, 5), (100, 50), (250, 4), (1_000, 3), (5_000, 2), (50_000, 1)] {
+            let mut washed = market.clone();
+            let mut flow = 0;
+            for _ in 0..rounds {
+                flow += settle(&config, &mut washed, round_trip, true, now);
+                flow += settle(&config, &mut washed, round_trip, false, now);
+            }
+            flow += settle(&config, &mut washed, 2_000, false, now);
+            assert!(flow < honest, "{rounds} round trips of {round_trip} shares: {flow} >= {honest}");
+        }
+    }
+
+    #[test]
+    fn test_quotes_at_large_supply() {
+        let config = sample_config(Pubkey::new_unique());
+        let mut market = sample_market(Pubkey::new_unique());
+        market.supply = 1_000_000;
+        // Past 10 holders the base price is `supply - 9` per share, so the
+        // whole supply's base prices sum to 499,990,500,049.5, and the
+        // volume premium adds at most 2%.
+        let base_prices = 499_990_500_049u64;
+
+        let sell = quote_sell(&config, &market, 1_000_000).unwrap();
+        assert!(sell.gross_price > base_prices && sell.gross_price < base_prices / 100 * 102);
+        assert_eq!(sell.protocol_fee, sell.gross_price / 20);
+        assert_eq!(sell.net_amount, sell.gross_price - sell.protocol_fee - sell.subject_fee);
+        assert_eq!(sell.new_supply, 0);
+
+        // The next million shares cost about three times as much.
+        let buy = quote_buy(&config, &market, 1_000_000).unwrap();
+        assert!(buy.gross_price / 3 > base_prices && buy.gross_price / 3 < base_prices / 100 * 102);
+        assert_eq!(buy.net_amount, buy.gross_price + buy.protocol_fee + buy.subject_fee);
+        assert_eq!(buy.new_supply, 2_000_000);
+
+        // Totals beyond u64 are rejected rather than saturated.
+        assert_eq!(quote_buy(&config, &market, u64::MAX / 1_000).unwrap_err(), ProgramError::ArithmeticOverflow);
+
+        market.supply = u32::MAX as u64 + 1;
+        assert_eq!(quote_sell(&config, &market, 1).unwrap_err(), ProgramError::ArithmeticOverflow);
+    }
+
+    #[test]
+    fn test_stats_record_trade() {
+        let config = sample_config(Pubkey::new_unique());
+        let mut stats = Stats {
+            account_type: AccountType::Stats,
+            total_markets: 2,
+            total_volume: 0,
+            total_fees: 0,
+            epoch: 7,
+            epoch_active_markets: 0,
+            last_epoch_active_markets: 0,
+            bump: 255,
+        };
+        let (mut first, mut second) = (sample_market(Pubkey::new_unique()), sample_market(Pubkey::new_unique()));
+        let settlement = quote_buy(&config, &first, 10).unwrap();
+        let (volume, fees) = (settlement.gross_price, settlement.protocol_fee + settlement.subject_fee);
+
+        // Repeat trades in one market count it as active once per epoch.
+        stats.record_trade(&mut first, &settlement, 7);
+        stats.record_trade(&mut first, &settlement, 7);
+        stats.record_trade(&mut second, &settlement, 7);
+        assert_eq!((stats.epoch_active_markets, stats.total_volume, stats.total_fees), (2, 3 * volume, 3 * fees));
+        assert_eq!(first.last_trade_epoch, 7);
+
+        stats.record_trade(&mut first, &settlement, 8);
+        assert_eq!((stats.epoch, stats.epoch_active_markets, stats.last_epoch_active_markets), (8, 1, 2));
+
+        // Skipping an epoch leaves no markets active in the previous one.
+        stats.record_trade(&mut second, &settlement, 10);
+        assert_eq!((stats.epoch_active_markets, stats.last_epoch_active_markets), (1, 0));
+    }
+
+    #[test]
+    fn test_proposal_veto_reached() {
+        let mut proposal = Proposal {
+            account_type: AccountType::Proposal,
+            market: Pubkey::new_unique(),
+            id: 0,
+            subject_fee_bps: 800,
+            sell_tax_bps: 500,
+            sell_tax_period: 600,
+            executable_at: 0,
+            supply: 100,
+            veto_weight: 29,
+            status: ProposalStatus::Pending,
+            bump: 255,
+        };
+        assert!(!proposal.veto_reached(3_000));
+        proposal.veto_weight = 30;
+        assert!(proposal.veto_reached(3_000));
+        // The comparison is taken in u128, so full supply always reaches a full threshold.
+        proposal.supply = u64::MAX;
+        proposal.veto_weight = u64::MAX;
+        assert!(proposal.veto_reached(10_000));
+
+        let mut data = vec![0u8; Proposal::LEN];
+        Proposal::pack(proposal.clone(), &mut data).unwrap();
+        assert_eq!(Proposal::unpack(&data).unwrap(), proposal);
+    }
+
+    #[test]
+    fn test_error_report_payload() {
+        let report = Err::<(), _>(FriendtechError::InsufficientFunds).at_account(4).unwrap_err().values(10, 3);
+        assert_eq!(report.error, ProgramError::Custom(FriendtechError::InsufficientFunds as u32));
+        let log = ErrorLog::try_from_slice(&report.log.try_to_vec().unwrap()).unwrap();
+        assert_eq!(log, ErrorLog { code: 1, account_index: 4, expected: 10, actual: 3 });
+
+        let report = ErrorReport::from(ProgramError::MissingRequiredSignature);
+        assert_eq!(report.log.account_index, NO_ACCOUNT);
+        assert_eq!(report.log.code, u64::from(ProgramError::MissingRequiredSignature));
+    }
+
+    #[test]
+    fn test_profile_pack_round_trip() {
+        let profile = Profile {
+            account_type: AccountType::Profile,
+            subject: Pubkey::new_unique(),
+            handle_hash: [3; 32],
+            attestor: Pubkey::new_unique(),
+            attestor_sig: [4; 64],
+            bump: 254,
+        };
+        let mut data = vec![0u8; Profile::LEN];
+        assert!(Profile::unpack(&data).is_err());
+        Profile::pack(profile.clone(), &mut data).unwrap();
+        assert_eq!(Profile::unpack(&data).unwrap(), profile);
+    }
+
+    #[test]
+    fn test_instruction_unpack_rejects_malformed_data() {
+        let unattributed =
+            FriendtechInstruction::BuyShares { amount: 7, client_id: None, interface_fee: None, receipt_id: None };
+        let buy = unattributed.try_to_vec().unwrap();
+        assert_eq!(FriendtechInstruction::unpack(&buy), Ok(unattributed));
+        let attributed = FriendtechInstruction::SellShares {
+            amount: 7,
+            client_id: Some(*b"frontend"),
+            interface_fee: Some(InterfaceFee { fee_bps: 50, recipient: Pubkey::new_unique() }),
+            receipt_id: Some([7; 32]),
+        };
+        assert_eq!(FriendtechInstruction::unpack(&attributed.try_to_vec().unwrap()), Ok(attributed));
+
+        let invalid = Err(ProgramError::InvalidInstructionData);
+        assert_eq!(FriendtechInstruction::unpack(&[]), invalid);
+        assert_eq!(FriendtechInstruction::unpack(&[u8::MAX]), invalid);
+        assert_eq!(FriendtechInstruction::unpack(&buy[..buy.len() - 1]), invalid);
+        assert_eq!(FriendtechInstruction::unpack(&[buy.as_slice(), &[0]].concat()), invalid);
+
+        let basket = FriendtechInstruction::BuyBasket { budget: 100, weights_bps: vec![5_000, 5_000], client_id: None }
+            .try_to_vec()
+            .unwrap();
+        assert!(FriendtechInstruction::unpack(&basket).is_ok());
+        // A length prefix promising a third weight that is not there.
+        let mut short_basket = basket.clone();
+        short_basket[9] = 3;
+        assert_eq!(FriendtechInstruction::unpack(&short_basket), invalid);
+
+        let mut fee_switch = FriendtechInstruction::SetFeeSwitch { protocol_fees_enabled: true }.try_to_vec().unwrap();
+        fee_switch[1] = 2;
+        assert_eq!(FriendtechInstruction::unpack(&fee_switch), invalid);
+    }
+
+    #[test]
+    fn test_unpack_rejects_uninitialized() {
+        let uninitialized = ProgramError::from(FriendtechError::UninitializedAccount);
+        assert_eq!(Market::unpack(&[]), Err(uninitialized.clone()));
+        assert_eq!(Market::unpack(&[0; Market::LEN]), Err(uninitialized.clone()));
+        assert_eq!(Stats::unpack(&[0]), Err(uninitialized));
+
+        let mut data = vec![0u8; Market::LEN];
+        Market::pack(sample_market(Pubkey::new_unique()), &mut data).unwrap();
+        assert_eq!(Market::unpack(&data[..Market::LEN - 1]), Err(ProgramError::InvalidAccountData));
+        // A market-sized buffer tagged as another account type.
+        data[0] = AccountType::Position as u8;
+        assert_eq!(Market::unpack(&data), Err(ProgramError::InvalidAccountData));
+    }
 }
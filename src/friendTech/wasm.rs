@@ -0,0 +1,94 @@
+//! wasm-bindgen wrappers around the program's quote functions, so web
+//! frontends price trades exactly as the program will. Build with
+//! `wasm-pack build --target web -- --features wasm`.
+//!
+//! Quotes take the raw `Config` and `Market` account data as fetched over
+//! RPC, and amounts are passed and returned as `bigint`.
+
+use crate::{Config, Market, PriceImpact, TradeSettlement};
+use solana_program::program_pack::Pack;
+use wasm_bindgen::prelude::*;
+
+/// A quoted trade, mirroring `TradeSettlement` and `PriceImpact`.
+#[wasm_bindgen]
+pub struct Quote(TradeSettlement, PriceImpact);
+
+#[wasm_bindgen]
+impl Quote {
+    /// Curve price of the shares, before fees.
+    #[wasm_bindgen(getter, js_name = grossPrice)]
+    pub fn gross_price(&self) -> u64 {
+        self.0.gross_price
+    }
+
+    #[wasm_bindgen(getter, js_name = protocolFee)]
+    pub fn protocol_fee(&self) -> u64 {
+        self.0.protocol_fee
+    }
+
+    #[wasm_bindgen(getter, js_name = subjectFee)]
+    pub fn subject_fee(&self) -> u64 {
+        self.0.subject_fee
+    }
+
+    #[wasm_bindgen(getter, js_name = interfaceFee)]
+    pub fn interface_fee(&self) -> u64 {
+        self.0.interface_fee
+    }
+
+    /// Quote tokens the buyer pays or the seller receives.
+    #[wasm_bindgen(getter, js_name = netAmount)]
+    pub fn net_amount(&self) -> u64 {
+        self.0.net_amount
+    }
+
+    #[wasm_bindgen(getter, js_name = newSupply)]
+    pub fn new_supply(&self) -> u64 {
+        self.0.new_supply
+    }
+
+    /// Per-share price before the trade, in millionths of a base unit.
+    #[wasm_bindgen(getter, js_name = preTradePrice)]
+    pub fn pre_trade_price(&self) -> u64 {
+        self.1.pre_trade_price
+    }
+
+    #[wasm_bindgen(getter, js_name = postTradePrice)]
+    pub fn post_trade_price(&self) -> u64 {
+        self.1.post_trade_price
+    }
+
+    /// Price change in basis points, negative for sells.
+    #[wasm_bindgen(getter, js_name = priceImpactBps)]
+    pub fn price_impact_bps(&self) -> i64 {
+        self.1.price_impact_bps
+    }
+}
+
+/// Quotes a buy of `amount` shares.
+#[wasm_bindgen(js_name = quoteBuy)]
+pub fn quote_buy(config_data: &[u8], market_data: &[u8], amount: u64) -> Result<Quote, JsError> {
+    let (config, market) = unpack_accounts(config_data, market_data)?;
+    let settlement = crate::quote_buy(&config, &market, amount).map_err(to_js_error)?;
+    let impact = crate::price_impact(&market, amount, true).map_err(to_js_error)?;
+    Ok(Quote(settlement, impact))
+}
+
+/// Quotes a sale of `amount` shares.
+#[wasm_bindgen(js_name = quoteSell)]
+pub fn quote_sell(config_data: &[u8], market_data: &[u8], amount: u64) -> Result<Quote, JsError> {
+    let (config, market) = unpack_accounts(config_data, market_data)?;
+    let settlement = crate::quote_sell(&config, &market, amount).map_err(to_js_error)?;
+    let impact = crate::price_impact(&market, amount, false).map_err(to_js_error)?;
+    Ok(Quote(settlement, impact))
+}
+
+fn unpack_accounts(config_data: &[u8], market_data: &[u8]) -> Result<(Config, Market), JsError> {
+    let config = Config::unpack(config_data).map_err(to_js_error)?;
+    let market = Market::unpack(market_data).map_err(to_js_error)?;
+    Ok((config, market))
+}
+
+fn to_js_error(error: solana_program::program_error::ProgramError) -> JsError {
+    JsError::new(&error.to_string())
+}
@@ -0,0 +1,69 @@
+//! Share accounts of the program's first release, left at position
+//! addresses, migrating in place the first time an instruction loads them.
+#![cfg(feature = "test-fixtures")]
+
+use borsh::BorshSerialize;
+use friendtech::{
+    find_position_address,
+    sdk::{
+        self,
+        fixtures::{Fixture, FixtureBuilder},
+    },
+    FriendtechError, LegacyShareAccount, Position,
+};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+};
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+async fn send(fixture: &mut Fixture, ix: Instruction, signer: &Keypair) -> Result<(), TransactionError> {
+    let payer = fixture.context.payer.insecure_clone();
+    let blockhash = fixture.context.get_new_latest_blockhash().await.unwrap();
+    let tx = Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer, signer], blockhash);
+    fixture.context.banks_client.process_transaction(tx).await.map_err(|err| err.unwrap())
+}
+
+#[tokio::test]
+async fn legacy_share_account_migrates_on_first_touch() {
+    let mut fixture = FixtureBuilder::new(Pubkey::new_unique()).wallets(1, 1_000_000_000_000).market(10).start().await;
+    let (program_id, quote_mint) = (fixture.program_id, fixture.quote_mint);
+    let (subject, market_key) = (fixture.markets[0].subject.keypair.pubkey(), fixture.markets[0].market);
+    let owner = fixture.wallets[0].keypair.insecure_clone();
+    let token_account = fixture.wallets[0].token_account;
+    let (position_key, bump) = find_position_address(&program_id, &market_key, &owner.pubkey());
+
+    // The first release's account holds only the owner and balance, with
+    // rent for those 40 bytes.
+    let data = LegacyShareAccount { owner: owner.pubkey(), balance: 3 }.try_to_vec().unwrap();
+    let lamports = Rent::default().minimum_balance(data.len());
+    let legacy = Account { lamports, data, owner: program_id, executable: false, rent_epoch: 0 };
+    fixture.context.set_account(&position_key, &legacy.into());
+
+    // A sale names no payer for the larger account's rent, so it cannot be
+    // the first touch.
+    let ix = sdk::sell_shares(&program_id, &owner.pubkey(), &subject, &quote_mint, &token_account, 1, None, None, None);
+    let error = send(&mut fixture, ix, &owner).await.unwrap_err();
+    let legacy_account = InstructionError::Custom(FriendtechError::LegacyAccount as u32);
+    assert_eq!(error, TransactionError::InstructionError(0, legacy_account));
+
+    // A buy migrates the account, its payer topping up the rent, and adds
+    // to the legacy balance.
+    let payer = fixture.context.payer.pubkey();
+    let ix = sdk::buy_shares(&program_id, &payer, &owner.pubkey(), &subject, &quote_mint, &token_account, 2, None, None, None);
+    send(&mut fixture, ix, &owner).await.unwrap();
+    let account = fixture.context.banks_client.get_account(position_key).await.unwrap().expect("account exists");
+    assert_eq!(account.data.len(), Position::LEN);
+    assert!(account.lamports >= Rent::default().minimum_balance(Position::LEN));
+    let position = Position::unpack(&account.data).unwrap();
+    assert_eq!((position.owner, position.balance, position.bump), (owner.pubkey(), 5, bump));
+
+    let ix = sdk::sell_shares(&program_id, &owner.pubkey(), &subject, &quote_mint, &token_account, 1, None, None, None);
+    send(&mut fixture, ix, &owner).await.unwrap();
+}
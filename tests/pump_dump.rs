@@ -0,0 +1,124 @@
+//! Pump and dump scenarios run through the real instruction processor,
+//! checking vault balances, prices and fee accrual after every trade.
+#![cfg(feature = "test-fixtures")]
+
+use friendtech::{
+    dump_fee_bps, quote_buy, quote_sell,
+    sdk::{
+        self,
+        fixtures::{Fixture, FixtureBuilder},
+    },
+    Config, Market, TradeSettlement,
+};
+use solana_program::{clock::Clock, instruction::Instruction, program_pack::{IsInitialized, Pack}, pubkey::Pubkey};
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use spl_token::state::Account as TokenAccount;
+
+async fn load<T: Pack + IsInitialized>(fixture: &mut Fixture, address: Pubkey) -> T {
+    let account = fixture.context.banks_client.get_account(address).await.unwrap().expect("account exists");
+    T::unpack(&account.data).unwrap()
+}
+
+async fn token_balance(fixture: &mut Fixture, address: Pubkey) -> u64 {
+    load::<TokenAccount>(fixture, address).await.amount
+}
+
+async fn send(fixture: &mut Fixture, ix: Instruction, signer: &Keypair) {
+    let payer = fixture.context.payer.insecure_clone();
+    let blockhash = fixture.context.get_new_latest_blockhash().await.unwrap();
+    let tx = Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer, signer], blockhash);
+    fixture.context.banks_client.process_transaction(tx).await.unwrap();
+}
+
+/// Checks the market's supply and fees moved by exactly `settlement`.
+fn assert_accrued(before: &Market, after: &Market, settlement: &TradeSettlement) {
+    assert_eq!(after.supply, settlement.new_supply);
+    assert_eq!(after.protocol_fees, before.protocol_fees + settlement.protocol_fee);
+    assert_eq!(after.subject_fees, before.subject_fees + settlement.subject_fee);
+}
+
+#[tokio::test]
+async fn pump_from_one_to_ten_thousand_holders() {
+    // Each wallet buys one step, growing the market from its subject's single
+    // share to 10,000.
+    let steps = [9, 40, 50, 400, 500, 4_000, 5_000];
+    let mut fixture = FixtureBuilder::new(Pubkey::new_unique())
+        .wallets(steps.len(), 1_000_000_000_000)
+        .market(1)
+        .start()
+        .await;
+    let (program_id, quote_mint) = (fixture.program_id, fixture.quote_mint);
+    let (subject, market_key, vault) = {
+        let market = &fixture.markets[0];
+        (market.subject.keypair.pubkey(), market.market, market.vault)
+    };
+    let config_key = fixture.config;
+    let config: Config = load(&mut fixture, config_key).await;
+
+    let mut last_share_price = 0;
+    let mut vault_balance = token_balance(&mut fixture, vault).await;
+    for (i, amount) in steps.into_iter().enumerate() {
+        let buyer = fixture.wallets[i].keypair.insecure_clone();
+        let token_account = fixture.wallets[i].token_account;
+        let before: Market = load(&mut fixture, market_key).await;
+        let expected = quote_buy(&config, &before, amount).unwrap();
+        let wallet_balance = token_balance(&mut fixture, token_account).await;
+
+        let payer = fixture.context.payer.pubkey();
+        let ix = sdk::buy_shares(&program_id, &payer, &buyer.pubkey(), &subject, &quote_mint, &token_account, amount, None, None, None);
+        send(&mut fixture, ix, &buyer).await;
+
+        let after: Market = load(&mut fixture, market_key).await;
+        assert_accrued(&before, &after, &expected);
+        assert_eq!(token_balance(&mut fixture, token_account).await, wallet_balance - expected.net_amount);
+        vault_balance += expected.net_amount;
+        assert_eq!(token_balance(&mut fixture, vault).await, vault_balance);
+
+        // Prices only rise while the market is pumped.
+        let share_price = expected.gross_price / amount;
+        assert!(share_price >= last_share_price, "step {i}: {share_price} < {last_share_price}");
+        last_share_price = share_price;
+    }
+    assert_eq!(load::<Market>(&mut fixture, market_key).await.supply, 10_000);
+}
+
+#[tokio::test]
+async fn dump_from_ten_thousand_holders_to_zero() {
+    let mut fixture = FixtureBuilder::new(Pubkey::new_unique()).market(10_000).start().await;
+    let (program_id, quote_mint) = (fixture.program_id, fixture.quote_mint);
+    let (seller, token_account, market_key, vault) = {
+        let market = &fixture.markets[0];
+        (market.subject.keypair.insecure_clone(), market.subject.token_account, market.market, market.vault)
+    };
+    let config_key = fixture.config;
+    let config: Config = load(&mut fixture, config_key).await;
+
+    let mut last_share_price = u64::MAX;
+    for amount in [5_000, 4_000, 500, 400, 50, 40, 9, 1] {
+        let before: Market = load(&mut fixture, market_key).await;
+        // Each sale of the dump pays a growing dump fee on top of the curve's.
+        let now = fixture.context.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp;
+        let tax_bps = dump_fee_bps(&before, amount, now);
+        let expected = quote_sell(&config, &before, amount).unwrap().with_sell_tax(tax_bps).unwrap();
+        let (vault_balance, seller_balance) =
+            (token_balance(&mut fixture, vault).await, token_balance(&mut fixture, token_account).await);
+
+        let ix = sdk::sell_shares(&program_id, &seller.pubkey(), &seller.pubkey(), &quote_mint, &token_account, amount, None, None, None);
+        send(&mut fixture, ix, &seller).await;
+
+        let after: Market = load(&mut fixture, market_key).await;
+        assert_accrued(&before, &after, &expected);
+        assert_eq!(token_balance(&mut fixture, vault).await, vault_balance - expected.net_amount);
+        assert_eq!(token_balance(&mut fixture, token_account).await, seller_balance + expected.net_amount);
+
+        // Prices only fall while holders dump, and the vault always covers the fees it owes.
+        let share_price = expected.gross_price / amount;
+        assert!(share_price <= last_share_price);
+        last_share_price = share_price;
+        assert!(token_balance(&mut fixture, vault).await >= after.protocol_fees + after.subject_fees);
+    }
+    assert_eq!(load::<Market>(&mut fixture, market_key).await.supply, 0);
+}
@@ -0,0 +1,116 @@
+//! A long pseudo-random trade sequence run through the real instruction
+//! processor, replayed from its logged trade events and compared with the
+//! resulting accounts byte for byte.
+#![cfg(feature = "test-fixtures")]
+
+use friendtech::{
+    replay::{decode_trade_event, Replay},
+    sdk::{
+        self,
+        fixtures::{take_logged_data, Fixture, FixtureBuilder},
+    },
+    Config, Market, Stats, TradeEvent,
+};
+use solana_program::{clock::Clock, instruction::Instruction, program_pack::Pack, pubkey::Pubkey};
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+const TRADES: usize = 300;
+const WALLETS: usize = 4;
+const SEED: u64 = 0x5eed_f00d;
+
+/// xorshift64, so every run trades the same sequence.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+}
+
+async fn account_data(fixture: &mut Fixture, address: Pubkey) -> Vec<u8> {
+    fixture.context.banks_client.get_account(address).await.unwrap().expect("account exists").data
+}
+
+/// Sends `ix` and returns the trade events it logged.
+async fn send(fixture: &mut Fixture, ix: Instruction, signer: &Keypair) -> Vec<TradeEvent> {
+    let payer = fixture.context.payer.insecure_clone();
+    let blockhash = fixture.context.get_new_latest_blockhash().await.unwrap();
+    let tx = Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer, signer], blockhash);
+    fixture.context.banks_client.process_transaction(tx).await.unwrap();
+    take_logged_data()
+        .iter()
+        .filter_map(|fields| decode_trade_event(&fields.iter().map(Vec::as_slice).collect::<Vec<_>>()))
+        .collect()
+}
+
+#[tokio::test]
+async fn replayed_trades_match_program_state() {
+    let mut fixture =
+        FixtureBuilder::new(Pubkey::new_unique()).wallets(WALLETS, 1_000_000_000_000).market(1).start().await;
+    let (program_id, quote_mint) = (fixture.program_id, fixture.quote_mint);
+    let (config_key, stats_key) = (fixture.config, fixture.stats);
+    let (subject, market_key) = (fixture.markets[0].subject.keypair.pubkey(), fixture.markets[0].market);
+
+    let config = Config::unpack(&account_data(&mut fixture, config_key).await).unwrap();
+    let mut replay = Replay::new(config, Stats::unpack(&account_data(&mut fixture, stats_key).await).unwrap());
+    replay.track(market_key, Market::unpack(&account_data(&mut fixture, market_key).await).unwrap());
+
+    let mut rng = Rng(SEED);
+    let mut clock: Clock = fixture.context.banks_client.get_sysvar().await.unwrap();
+    let mut balances = [0u64; WALLETS];
+    for _ in 0..TRADES {
+        // Minutes to hours between trades, so volume buckets, the dump fee
+        // window and epochs all roll over during the run.
+        clock.unix_timestamp += rng.below(2 * 3_600) as i64;
+        if rng.below(20) == 0 {
+            clock.epoch += 1;
+        }
+        fixture.context.set_sysvar(&clock);
+
+        let i = rng.below(WALLETS as u64) as usize;
+        let trader = fixture.wallets[i].keypair.insecure_clone();
+        let token_account = fixture.wallets[i].token_account;
+        let ix = if balances[i] > 0 && rng.below(2) == 0 {
+            let amount = 1 + rng.below(balances[i]);
+            balances[i] -= amount;
+            sdk::sell_shares(&program_id, &trader.pubkey(), &subject, &quote_mint, &token_account, amount, None, None, None)
+        } else {
+            let amount = 1 + rng.below(50);
+            balances[i] += amount;
+            let payer = fixture.context.payer.pubkey();
+            sdk::buy_shares(
+                &program_id,
+                &payer,
+                &trader.pubkey(),
+                &subject,
+                &quote_mint,
+                &token_account,
+                amount,
+                None,
+                None,
+                None,
+            )
+        };
+
+        let events = send(&mut fixture, ix, &trader).await;
+        assert_eq!(events.len(), 1);
+        replay.apply(&events[0], clock.unix_timestamp, clock.epoch).unwrap();
+    }
+
+    let mut replayed = vec![0u8; Market::LEN];
+    Market::pack(replay.markets[&market_key].clone(), &mut replayed).unwrap();
+    assert_eq!(account_data(&mut fixture, market_key).await, replayed);
+    let mut replayed = vec![0u8; Stats::LEN];
+    Stats::pack(replay.stats, &mut replayed).unwrap();
+    assert_eq!(account_data(&mut fixture, stats_key).await, replayed);
+}